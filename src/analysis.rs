@@ -0,0 +1,1891 @@
+use super::*;
+use fxhash::FxHashMap;
+use std::collections::HashMap;
+
+/// Information about a class definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassInfo {
+    /// qualified class name. (e.g. "Outer::Inner")
+    pub name: String,
+    /// superclass expression, if it is a constant path.
+    pub superclass: Option<String>,
+    /// true for `class << obj`.
+    pub is_singleton: bool,
+    pub loc: Loc,
+    /// locations of methods defined directly in the class body.
+    pub method_locs: Vec<Loc>,
+}
+
+/// A class which is defined more than once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenClassInfo {
+    /// qualified class name. (e.g. "Outer::Inner")
+    pub name: String,
+    pub first_loc: Loc,
+    pub reopen_locs: Vec<Loc>,
+}
+
+/// Information about a module definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleInfo {
+    /// qualified module name. (e.g. "Outer::Inner")
+    pub name: String,
+    pub loc: Loc,
+    /// locations of methods defined directly in the module body.
+    pub method_locs: Vec<Loc>,
+}
+
+/// Kind of a numeric literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericKind {
+    Integer,
+    Bignum,
+    Float,
+    Rational,
+    Complex,
+}
+
+/// Information about a numeric literal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericLiteralInfo {
+    pub kind: NumericKind,
+    /// source text of the literal. (e.g. "1_000", "-3.14")
+    pub value_repr: String,
+    pub loc: Loc,
+}
+
+/// A call site of a method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallSite {
+    /// None for receiverless calls.
+    pub receiver: Option<Node>,
+    pub args: Vec<Node>,
+    pub block: Option<Node>,
+    pub loc: Loc,
+}
+
+/// A block argument passed with `&expr`, like `arr.map(&:to_s)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockPassSite {
+    pub method_name: String,
+    /// the expression after `&`.
+    pub block_expr: Node,
+    /// true if *block_expr* is a symbol literal.
+    pub is_symbol_shorthand: bool,
+    pub loc: Loc,
+}
+
+/// The effective scope at a certain location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeInfo {
+    pub kind: ScopeKind,
+    /// local variables visible in the scope, from the innermost scope outward.
+    pub local_vars: Vec<String>,
+    pub enclosing_method: Option<String>,
+    /// qualified name of the enclosing class or module.
+    pub enclosing_class: Option<String>,
+}
+
+/// Conversion of a frequency map into a vec sorted by count.
+pub trait IntoSortedVec {
+    /// Convert into `(key, count)` pairs sorted by count in descending order.
+    /// Keys with the same count are sorted in ascending order.
+    fn into_sorted_vec(self) -> Vec<(String, usize)>;
+}
+
+impl<S> IntoSortedVec for HashMap<String, usize, S> {
+    fn into_sorted_vec(self) -> Vec<(String, usize)> {
+        let mut v: Vec<_> = self.into_iter().collect();
+        v.sort_by(|(k1, c1), (k2, c2)| c2.cmp(c1).then_with(|| k1.cmp(k2)));
+        v
+    }
+}
+
+/// A definition found at the top level or in class bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    Method {
+        name: String,
+        /// qualified name of the enclosing class or module.
+        owner: Option<String>,
+        /// true for `def self.foo` or `def obj.foo`.
+        is_singleton: bool,
+        loc: Loc,
+    },
+    Class {
+        name: String,
+        loc: Loc,
+    },
+    Module {
+        name: String,
+        loc: Loc,
+    },
+    /// `alias new_name old_name`
+    Alias {
+        new_name: String,
+        old_name: String,
+        loc: Loc,
+    },
+    /// `alias_method :new_name, :old_name`
+    AliasMethod {
+        new_name: String,
+        old_name: String,
+        loc: Loc,
+    },
+    /// `private_class_method :name` or `public_class_method :name`
+    ClassMethodVisibility {
+        name: String,
+        visibility: Visibility,
+        loc: Loc,
+    },
+}
+
+/// An access to an instance variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IvarAccess {
+    /// name of the instance variable without `@`.
+    pub name: String,
+    pub is_write: bool,
+    pub in_method: Option<String>,
+    pub loc: Loc,
+}
+
+/// An edge of the mixin graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixinEdge {
+    /// qualified name of the class or module, or "Object" for the top level.
+    pub from_class: String,
+    pub to_module: String,
+    pub kind: MixinKind,
+}
+
+/// A struct class defined by `Struct.new`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    /// qualified name of the constant or the class, or `Struct::Name` for `Struct.new("Name")`.
+    /// None for an anonymous struct.
+    pub name: Option<String>,
+    pub fields: Vec<String>,
+    /// true for `keyword_init: true`.
+    pub keyword_init: bool,
+    /// location of the `Struct.new` call.
+    pub loc: Loc,
+}
+
+/// Default threshold of the nesting depth for `ParseResult::deeply_nested_methods()`.
+pub const DEFAULT_METHOD_NESTING_THRESHOLD: usize = 2;
+
+/// Well-known mixin modules in the standard library.
+const KNOWN_MIXINS: &[&str] = &[
+    "Comparable",
+    "Enumerable",
+    "Forwardable",
+    "SingleForwardable",
+    "Observable",
+    "Singleton",
+    "MonitorMixin",
+];
+
+/// How a module is mixed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MixinKind {
+    Include,
+    Extend,
+    Prepend,
+}
+
+/// A use of `include`, `extend` or `prepend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixinUse {
+    pub module_name: String,
+    /// qualified name of the enclosing class or module. None for toplevel.
+    pub in_class: Option<String>,
+    pub kind: MixinKind,
+    pub loc: Loc,
+}
+
+/// Kind of a method call which terminates the program or raises an exception.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EarlyExitKind {
+    /// `exit` or `exit!`
+    Exit,
+    Abort,
+    Raise,
+    Fail,
+}
+
+/// A call of `exit`, `abort`, `raise` or `fail`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EarlyExit {
+    pub kind: EarlyExitKind,
+    /// the first argument.
+    pub arg: Option<Node>,
+    pub loc: Loc,
+}
+
+/// A part of the content of a string literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Str(String),
+    /// `#{..}`
+    Interpolation(Box<Node>),
+}
+
+/// Information about a here document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeredocInfo {
+    /// the identifier which terminates the here document.
+    pub tag: String,
+    /// true for `<<~`.
+    pub stripped: bool,
+    pub content_parts: Vec<StringPart>,
+    /// location of the body.
+    pub loc: Loc,
+}
+
+impl HeredocInfo {
+    pub(crate) fn new(tag: String, stripped: bool, node: &Node, loc: Loc) -> Self {
+        fn push_parts(node: &Node, parts: &mut Vec<StringPart>) {
+            match &node.kind {
+                NodeKind::String(s) => parts.push(StringPart::Str(s.clone())),
+                NodeKind::Bytes(b) => {
+                    parts.push(StringPart::Str(String::from_utf8_lossy(b).into_owned()))
+                }
+                NodeKind::InterporatedString(nodes) => {
+                    for node in nodes {
+                        match &node.kind {
+                            NodeKind::String(_) | NodeKind::Bytes(_) => push_parts(node, parts),
+                            _ => parts.push(StringPart::Interpolation(Box::new(node.clone()))),
+                        }
+                    }
+                }
+                NodeKind::Command(content) => push_parts(content, parts),
+                _ => parts.push(StringPart::Interpolation(Box::new(node.clone()))),
+            }
+        }
+        let mut content_parts = vec![];
+        push_parts(node, &mut content_parts);
+        HeredocInfo {
+            tag,
+            stripped,
+            content_parts,
+            loc,
+        }
+    }
+}
+
+impl ParseResult {
+    /// Get the source text of *node*.
+    pub fn source_text_for(&self, node: &Node) -> &str {
+        self.source_info
+            .try_extract_span(node.loc)
+            .unwrap_or_default()
+    }
+
+    /// Get all here documents in the code.
+    pub fn heredoc_bodies(&self) -> Vec<HeredocInfo> {
+        self.heredocs.clone()
+    }
+
+    /// Compute the longest method call chain for each root expression.
+    ///
+    /// The key is the source text of the root receiver, e.g. `"a"` for `a.b.c.d` (length 3).
+    pub fn call_chain_lengths(&self) -> HashMap<String, usize> {
+        let mut chains = HashMap::default();
+        self.each_node_with_depth(|node, _| {
+            let mut len = 0;
+            let mut root = node;
+            while let NodeKind::MethodCall { receiver, .. } = &root.kind {
+                len += 1;
+                root = receiver;
+            }
+            if len != 0 {
+                let max = chains
+                    .entry(self.source_text_for(root).to_string())
+                    .or_default();
+                if *max < len {
+                    *max = len;
+                }
+            }
+        });
+        chains
+    }
+
+    /// Get the path from the root to the deepest node which contains *loc*.
+    ///
+    /// The path is ordered from the outermost node to the innermost one.
+    pub fn loc_to_node_path(&self, loc: Loc) -> Vec<&Node> {
+        fn search<'a>(node: &'a Node, loc: Loc, path: &mut Vec<&'a Node>) -> bool {
+            path.push(node);
+            for child in node.children() {
+                if search(child, loc, path) {
+                    return true;
+                }
+            }
+            if node.loc.0 <= loc.0 && loc.1 <= node.loc.1 {
+                return true;
+            }
+            path.pop();
+            false
+        }
+        let mut path = vec![];
+        search(&self.node, loc, &mut path);
+        path
+    }
+
+    /// Collect calls of `exit`, `exit!`, `abort`, `raise` and `fail`,
+    /// including those with an explicit `Kernel` receiver.
+    pub fn early_exits(&self) -> Vec<EarlyExit> {
+        let mut exits = vec![];
+        self.each_node_with_depth(|node, _| {
+            let (method, arg) = match &node.kind {
+                NodeKind::Ident(method) => (method, None),
+                NodeKind::FuncCall {
+                    method, arglist, ..
+                } => (method, arglist.args.first()),
+                NodeKind::MethodCall {
+                    receiver,
+                    method,
+                    arglist,
+                    ..
+                } if const_path(receiver) == Some(vec!["Kernel".to_string()]) => {
+                    (method, arglist.args.first())
+                }
+                _ => return,
+            };
+            let kind = match method.as_str() {
+                "exit" | "exit!" => EarlyExitKind::Exit,
+                "abort" => EarlyExitKind::Abort,
+                "raise" => EarlyExitKind::Raise,
+                "fail" => EarlyExitKind::Fail,
+                _ => return,
+            };
+            exits.push(EarlyExit {
+                kind,
+                arg: arg.cloned(),
+                loc: node.loc,
+            });
+        });
+        exits
+    }
+
+    /// Collect all numeric literals in source order.
+    pub fn numeric_literals(&self) -> Vec<NumericLiteralInfo> {
+        let mut literals = vec![];
+        self.each_node_with_depth(|node, _| {
+            let kind = match &node.kind {
+                NodeKind::Integer(_) => NumericKind::Integer,
+                NodeKind::Bignum(_) => NumericKind::Bignum,
+                NodeKind::Float(_) => NumericKind::Float,
+                NodeKind::Rational(_) => NumericKind::Rational,
+                NodeKind::Imaginary(_) => NumericKind::Complex,
+                _ => return,
+            };
+            let value_repr = self.source_text_for(node);
+            literals.push(NumericLiteralInfo {
+                kind,
+                value_repr: value_repr.to_string(),
+                loc: node.loc,
+            });
+        });
+        literals
+    }
+
+    /// Collect all `true` and `false` literals in source order.
+    pub fn boolean_literals(&self) -> Vec<(bool, Loc)> {
+        let mut literals = vec![];
+        self.each_node_with_depth(|node, _| {
+            if let NodeKind::Bool(b) = node.kind {
+                literals.push((b, node.loc));
+            }
+        });
+        literals
+    }
+
+    /// Collect locations of all `nil` literals in source order.
+    ///
+    /// Nil nodes implicitly supplied by the parser (e.g. a missing `else` clause) are excluded.
+    pub fn nil_literals(&self) -> Vec<Loc> {
+        let mut literals = vec![];
+        self.each_node_with_depth(|node, _| {
+            if node.kind == NodeKind::Nil && self.source_text_for(node) == "nil" {
+                literals.push(node.loc);
+            }
+        });
+        literals
+    }
+
+    /// Find the first method definition (including singleton methods) named *name* in pre-order.
+    pub fn find_method(&self, name: &str) -> Option<&Node> {
+        fn find<'a>(node: &'a Node, name: &str) -> Option<&'a Node> {
+            if method_def_name(node) == Some(name) {
+                return Some(node);
+            }
+            node.children()
+                .into_iter()
+                .find_map(|child| find(child, name))
+        }
+        find(&self.node, name)
+    }
+
+    /// Find all method definitions (including singleton methods) named *name* in pre-order.
+    pub fn find_all_methods(&self, name: &str) -> Vec<&Node> {
+        fn find<'a>(node: &'a Node, name: &str, methods: &mut Vec<&'a Node>) {
+            if method_def_name(node) == Some(name) {
+                methods.push(node);
+            }
+            for child in node.children() {
+                find(child, name, methods);
+            }
+        }
+        let mut methods = vec![];
+        find(&self.node, name, &mut methods);
+        methods
+    }
+
+    /// Collect all call sites of the method *method_name* in pre-order, regardless of the receiver.
+    pub fn call_sites_for(&self, method_name: &str) -> Vec<CallSite> {
+        let mut sites = vec![];
+        self.each_node_with_depth(|node, _| {
+            let (receiver, arglist) = match &node.kind {
+                NodeKind::Ident(method) if method == method_name => (None, None),
+                NodeKind::FuncCall {
+                    method, arglist, ..
+                } if method == method_name => (None, Some(arglist)),
+                NodeKind::MethodCall {
+                    receiver,
+                    method,
+                    arglist,
+                    ..
+                } if method == method_name => (Some(receiver.as_ref().clone()), Some(arglist)),
+                _ => return,
+            };
+            sites.push(CallSite {
+                receiver,
+                args: arglist
+                    .map(|arglist| arglist.args.clone())
+                    .unwrap_or_default(),
+                block: arglist.and_then(|arglist| arglist.block.as_deref().cloned()),
+                loc: node.loc,
+            });
+        });
+        sites
+    }
+
+    /// Collect all `&expr` block arguments in pre-order.
+    pub fn block_pass_sites(&self) -> Vec<BlockPassSite> {
+        let mut sites = vec![];
+        self.each_node_with_depth(|node, _| {
+            let (method, arglist) = match &node.kind {
+                NodeKind::FuncCall {
+                    method, arglist, ..
+                }
+                | NodeKind::MethodCall {
+                    method, arglist, ..
+                } => (method.as_str(), arglist),
+                NodeKind::Super(Some(arglist)) => ("super", arglist),
+                NodeKind::Yield(arglist) => ("yield", arglist),
+                _ => return,
+            };
+            match arglist.block.as_deref() {
+                Some(block) if !matches!(block.kind, NodeKind::Lambda(_)) => {
+                    sites.push(BlockPassSite {
+                        method_name: method.to_string(),
+                        block_expr: block.clone(),
+                        is_symbol_shorthand: matches!(block.kind, NodeKind::Symbol(_)),
+                        loc: node.loc,
+                    })
+                }
+                _ => {}
+            }
+        });
+        sites
+    }
+
+    /// Get the effective scope at *loc*.
+    ///
+    /// Local variables are those of the innermost scope and of its outer scopes
+    /// visible from blocks, regardless of the position of their first assignment.
+    pub fn scope_at(&self, loc: Loc) -> ScopeInfo {
+        struct State<'a> {
+            scopes: Vec<(ScopeKind, &'a LvarCollector)>,
+            method: Option<String>,
+            classes: Vec<String>,
+        }
+        fn visit<'a>(node: &'a Node, loc: Loc, state: &mut State<'a>) {
+            match &node.kind {
+                NodeKind::MethodDef(name, info) | NodeKind::SingletonMethodDef(_, name, info) => {
+                    state.scopes.push((ScopeKind::Method, &info.lvar));
+                    state.method = Some(name.clone());
+                }
+                NodeKind::ClassDef { info, .. } | NodeKind::SingletonClassDef { info, .. } => {
+                    state.scopes.push((ScopeKind::Class, &info.lvar));
+                    state.method = None;
+                    let name = class_name(node, state.classes.last());
+                    state.classes.push(name);
+                }
+                NodeKind::Lambda(info) => state.scopes.push((ScopeKind::Block, &info.lvar)),
+                NodeKind::For { body, .. } => state.scopes.push((ScopeKind::For, &body.lvar)),
+                _ => {}
+            }
+            if let Some(child) = node.children().into_iter().find(|child| {
+                let span = subtree_span(child);
+                span.0 <= loc.0 && loc.1 <= span.1
+            }) {
+                visit(child, loc, state);
+            }
+        }
+        let mut state = State {
+            scopes: vec![(ScopeKind::Eval, &self.lvar_collector)],
+            method: None,
+            classes: vec![],
+        };
+        visit(&self.node, loc, &mut state);
+        let kind = state.scopes.last().unwrap().0;
+        let mut local_vars = vec![];
+        for (kind, lvar) in state.scopes.iter().rev() {
+            local_vars.extend(lvar.table().iter().cloned());
+            if !matches!(kind, ScopeKind::Block | ScopeKind::For) {
+                break;
+            }
+        }
+        ScopeInfo {
+            kind,
+            local_vars,
+            enclosing_method: state.method,
+            enclosing_class: state.classes.pop(),
+        }
+    }
+
+    /// Check whether the top level has a guard like `if __FILE__ == $0`.
+    ///
+    /// `==`, `equal?` and `eql?` are recognized with either ordering of the operands,
+    /// and `$PROGRAM_NAME` is accepted as well as `$0`.
+    pub fn has_main_guard(&self) -> bool {
+        let stmts = match &self.node.kind {
+            NodeKind::CompStmt(nodes) => nodes.iter().collect(),
+            _ => vec![&self.node],
+        };
+        stmts.into_iter().any(|stmt| match &stmt.kind {
+            NodeKind::If { cond, .. } => {
+                let (lhs, rhs) = match &cond.kind {
+                    NodeKind::BinOp(BinOp::Cmp(CmpKind::Eq), lhs, rhs) => (lhs.as_ref(), rhs.as_ref()),
+                    NodeKind::MethodCall {
+                        receiver,
+                        method,
+                        arglist,
+                        ..
+                    } if (method == "equal?" || method == "eql?") && arglist.args.len() == 1 => {
+                        (receiver.as_ref(), &arglist.args[0])
+                    }
+                    _ => return false,
+                };
+                let is_file = |node: &Node| node.kind == NodeKind::FileLiteral;
+                let is_program_name = |node: &Node| {
+                    matches!(&node.kind, NodeKind::GlobalVar(name) if name == "$0" || name == "$PROGRAM_NAME")
+                };
+                (is_file(lhs) && is_program_name(rhs)) || (is_program_name(lhs) && is_file(rhs))
+            }
+            _ => false,
+        })
+    }
+
+    /// Return a new `ParseResult` in which all nodes matching *predicate* are removed.
+    ///
+    /// Removed nodes are replaced with an empty statement to preserve the structure of
+    /// their parents. Descendants of a removed node are not examined.
+    pub fn prune(&self, predicate: impl Fn(&Node) -> bool) -> ParseResult {
+        fn prune_node(node: &mut Node, predicate: &impl Fn(&Node) -> bool) {
+            if predicate(node) {
+                *node = Node::new_comp_stmt(vec![], node.loc);
+                return;
+            }
+            for child in node.children_mut() {
+                prune_node(child, predicate);
+            }
+        }
+        let mut result = self.clone();
+        prune_node(&mut result.node, &predicate);
+        result
+    }
+
+    /// Count calls for each method name.
+    ///
+    /// Use `IntoSortedVec::into_sorted_vec()` to sort the result by count.
+    pub fn method_calls_count(&self) -> FxHashMap<String, usize> {
+        let mut count = FxHashMap::default();
+        self.each_node_with_depth(|node, _| match &node.kind {
+            NodeKind::Ident(method)
+            | NodeKind::FuncCall { method, .. }
+            | NodeKind::MethodCall { method, .. } => *count.entry(method.clone()).or_default() += 1,
+            _ => {}
+        });
+        count
+    }
+
+    /// Convert a constant reference like `A::B::C` to `["A", "B", "C"]`.
+    ///
+    /// Return None if *node* is not a constant reference or contains a dynamic expression,
+    /// e.g. `foo::Bar`.
+    pub fn constant_path_for(node: &Node) -> Option<Vec<String>> {
+        const_path(node)
+    }
+
+    /// Collect all reads and writes of instance variables in evaluation order.
+    ///
+    /// The right-hand side of an assignment comes before its left-hand side, and
+    /// `@a += 1` is a read followed by a write.
+    pub fn instance_variable_accesses(&self) -> Vec<IvarAccess> {
+        fn push(
+            node: &Node,
+            is_write: bool,
+            method: Option<&String>,
+            accesses: &mut Vec<IvarAccess>,
+        ) {
+            if let NodeKind::InstanceVar(name) = &node.kind {
+                accesses.push(IvarAccess {
+                    name: name.trim_start_matches('@').to_string(),
+                    is_write,
+                    in_method: method.cloned(),
+                    loc: node.loc,
+                });
+            }
+        }
+        fn visit(node: &Node, method: Option<&String>, accesses: &mut Vec<IvarAccess>) {
+            let method = match &node.kind {
+                NodeKind::InstanceVar(_) => {
+                    push(node, false, method, accesses);
+                    return;
+                }
+                NodeKind::MulAssign(mlhs, mrhs) => {
+                    for rhs in mrhs {
+                        visit(rhs, method, accesses);
+                    }
+                    for lhs in mlhs {
+                        match lhs.kind {
+                            NodeKind::InstanceVar(_) => push(lhs, true, method, accesses),
+                            _ => visit(lhs, method, accesses),
+                        }
+                    }
+                    return;
+                }
+                NodeKind::AssignOp(_, lhs, rhs) if matches!(lhs.kind, NodeKind::InstanceVar(_)) => {
+                    push(lhs, false, method, accesses);
+                    visit(rhs, method, accesses);
+                    push(lhs, true, method, accesses);
+                    return;
+                }
+                NodeKind::MethodDef(name, _) | NodeKind::SingletonMethodDef(_, name, _) => {
+                    Some(name)
+                }
+                NodeKind::ClassDef { .. } | NodeKind::SingletonClassDef { .. } => None,
+                _ => method,
+            };
+            for child in node.children() {
+                visit(child, method, accesses);
+            }
+        }
+        let mut accesses = vec![];
+        visit(&self.node, None, &mut accesses);
+        accesses
+    }
+
+    /// Visit all nodes in pre-order, with the depth of each node. (the root is 0)
+    pub fn each_node_with_depth(&self, mut f: impl FnMut(&Node, usize)) {
+        fn visit(node: &Node, depth: usize, f: &mut impl FnMut(&Node, usize)) {
+            f(node, depth);
+            for child in node.children() {
+                visit(child, depth + 1, f);
+            }
+        }
+        visit(&self.node, 0, &mut f);
+    }
+
+    /// Collect methods nested deeper than `DEFAULT_METHOD_NESTING_THRESHOLD`.
+    ///
+    /// See `deeply_nested_methods_with_threshold()`.
+    pub fn deeply_nested_methods(&self) -> Vec<(String, usize)> {
+        self.deeply_nested_methods_with_threshold(DEFAULT_METHOD_NESTING_THRESHOLD)
+    }
+
+    /// Collect `(method_name, depth)` of methods whose depth exceeds *threshold*.
+    ///
+    /// The depth is the number of enclosing class, module and `begin` blocks.
+    /// The implicit `begin` block of a class or method body is not counted.
+    pub fn deeply_nested_methods_with_threshold(&self, threshold: usize) -> Vec<(String, usize)> {
+        fn visit(node: &Node, depth: usize, threshold: usize, methods: &mut Vec<(String, usize)>) {
+            let (body, depth) = match &node.kind {
+                NodeKind::MethodDef(name, info) | NodeKind::SingletonMethodDef(_, name, info) => {
+                    if depth > threshold {
+                        methods.push((name.clone(), depth));
+                    }
+                    (Some(&info.body), depth)
+                }
+                NodeKind::ClassDef { info, .. } | NodeKind::SingletonClassDef { info, .. } => {
+                    (Some(&info.body), depth + 1)
+                }
+                NodeKind::Begin { .. } => (None, depth + 1),
+                _ => (None, depth),
+            };
+            for child in node.children() {
+                match body {
+                    // the implicit `begin` block of the body is transparent.
+                    Some(body)
+                        if std::ptr::eq(child, body.as_ref())
+                            && matches!(body.kind, NodeKind::Begin { .. }) =>
+                    {
+                        for child in child.children() {
+                            visit(child, depth, threshold, methods);
+                        }
+                    }
+                    _ => visit(child, depth, threshold, methods),
+                }
+            }
+        }
+        let mut methods = vec![];
+        visit(&self.node, 0, threshold, &mut methods);
+        methods
+    }
+
+    /// Format the AST in a canonical form.
+    ///
+    /// Each node is printed on its own line with its kind name and a brief summary,
+    /// indented by `indent * depth` spaces.
+    pub fn format_ast(&self, indent: usize) -> String {
+        let mut res = String::new();
+        self.each_node_with_depth(|node, depth| {
+            res += &" ".repeat(indent * depth);
+            res += node.kind.kind_name();
+            if let Some(summary) = node_summary(node) {
+                res += " ";
+                res += &summary;
+            }
+            res += "\n";
+        });
+        res
+    }
+
+    /// Collect definitions at the top level and in class and module bodies in source order.
+    ///
+    /// Method bodies are not examined.
+    pub fn collect_definitions(&self) -> Vec<Definition> {
+        fn visit(node: &Node, outer: &mut Vec<String>, defs: &mut Vec<Definition>) {
+            match &node.kind {
+                NodeKind::MethodDef(name, _) | NodeKind::SingletonMethodDef(_, name, _) => {
+                    defs.push(Definition::Method {
+                        name: name.clone(),
+                        owner: outer.last().cloned(),
+                        is_singleton: matches!(node.kind, NodeKind::SingletonMethodDef(..)),
+                        loc: node.loc,
+                    });
+                    return;
+                }
+                NodeKind::ClassDef { is_module, .. } => {
+                    let name = class_name(node, outer.last());
+                    defs.push(if *is_module {
+                        Definition::Module {
+                            name: name.clone(),
+                            loc: node.loc,
+                        }
+                    } else {
+                        Definition::Class {
+                            name: name.clone(),
+                            loc: node.loc,
+                        }
+                    });
+                    outer.push(name);
+                    for child in node.children() {
+                        visit(child, outer, defs);
+                    }
+                    outer.pop();
+                    return;
+                }
+                NodeKind::SingletonClassDef { .. } => {
+                    outer.push(class_name(node, outer.last()));
+                    for child in node.children() {
+                        visit(child, outer, defs);
+                    }
+                    outer.pop();
+                    return;
+                }
+                NodeKind::AliasMethod(new_name, old_name) => {
+                    if let (NodeKind::Symbol(new_name), NodeKind::Symbol(old_name)) =
+                        (&new_name.kind, &old_name.kind)
+                    {
+                        defs.push(Definition::Alias {
+                            new_name: new_name.clone(),
+                            old_name: old_name.clone(),
+                            loc: node.loc,
+                        });
+                    }
+                }
+                NodeKind::FuncCall {
+                    method, arglist, ..
+                }
+                | NodeKind::MethodCall {
+                    method, arglist, ..
+                } if method == "alias_method" && is_self_call(node) => {
+                    if let [new_name, old_name] = &arglist.args[..] {
+                        if let (Some(new_name), Some(old_name)) =
+                            (symbol_name(new_name), symbol_name(old_name))
+                        {
+                            defs.push(Definition::AliasMethod {
+                                new_name,
+                                old_name,
+                                loc: node.loc,
+                            });
+                        }
+                    }
+                }
+                NodeKind::FuncCall {
+                    method, arglist, ..
+                }
+                | NodeKind::MethodCall {
+                    method, arglist, ..
+                } if (method == "private_class_method" || method == "public_class_method")
+                    && is_self_call(node) =>
+                {
+                    let visibility = if method == "private_class_method" {
+                        Visibility::Private
+                    } else {
+                        Visibility::Public
+                    };
+                    for arg in &arglist.args {
+                        // `private_class_method def self.foo .. end`
+                        let name = match &arg.kind {
+                            NodeKind::SingletonMethodDef(_, name, _) => Some(name.clone()),
+                            _ => symbol_name(arg),
+                        };
+                        if let Some(name) = name {
+                            defs.push(Definition::ClassMethodVisibility {
+                                name,
+                                visibility,
+                                loc: arg.loc,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+            for child in node.children() {
+                visit(child, outer, defs);
+            }
+        }
+        let mut defs = vec![];
+        visit(&self.node, &mut vec![], &mut defs);
+        defs
+    }
+
+    /// Collect struct classes defined by `Struct.new` in pre-order.
+    ///
+    /// The name is taken from the assigned constant (`Point = Struct.new(..)`), the class which
+    /// inherits the struct (`class Point < Struct.new(..)`), or the first string argument.
+    pub fn struct_definitions(&self) -> Vec<StructDef> {
+        fn struct_new(node: &Node) -> Option<&ArgList> {
+            match &node.kind {
+                NodeKind::MethodCall {
+                    receiver,
+                    method,
+                    arglist,
+                    ..
+                } if method == "new"
+                    && const_path(receiver) == Some(vec!["Struct".to_string()]) =>
+                {
+                    Some(arglist)
+                }
+                _ => None,
+            }
+        }
+        let mut names: Vec<(&Node, String)> = vec![];
+        let mut defs = vec![];
+        each_node_in_class(
+            &self.node,
+            &mut vec![],
+            &mut |node, outer| match &node.kind {
+                NodeKind::MulAssign(mlhs, mrhs) if mlhs.len() == 1 && mrhs.len() == 1 => {
+                    if let (Some(path), Some(_)) = (const_path(&mlhs[0]), struct_new(&mrhs[0])) {
+                        let mut name: Vec<_> = outer.last().into_iter().cloned().collect();
+                        name.extend(path);
+                        names.push((&mrhs[0], name.join("::")));
+                    }
+                }
+                NodeKind::ClassDef {
+                    superclass: Some(superclass),
+                    ..
+                } if struct_new(superclass).is_some() => {
+                    names.push((superclass, class_name(node, outer.last())));
+                }
+                _ => {
+                    if let Some(arglist) = struct_new(node) {
+                        let mut args = arglist.args.iter().peekable();
+                        let class_name = match args.peek().map(|arg| &arg.kind) {
+                            Some(NodeKind::String(name)) => {
+                                args.next();
+                                Some(format!("Struct::{}", name))
+                            }
+                            _ => None,
+                        };
+                        let name = names
+                            .iter()
+                            .find(|(n, _)| std::ptr::eq(*n, node))
+                            .map(|(_, name)| name.clone())
+                            .or(class_name);
+                        defs.push(StructDef {
+                            name,
+                            fields: args.filter_map(symbol_name).collect(),
+                            keyword_init: arglist.kw_args.iter().any(|(key, val)| {
+                                key == "keyword_init" && val.kind == NodeKind::Bool(true)
+                            }),
+                            loc: node.loc,
+                        });
+                    }
+                }
+            },
+        );
+        defs
+    }
+
+    /// Collect all class definitions including nested ones in pre-order.
+    pub fn classes(&self) -> Vec<ClassInfo> {
+        let mut classes = vec![];
+        each_node_in_class(&self.node, &mut vec![], &mut |node, outer| {
+            let (superclass, info, is_singleton) = match &node.kind {
+                NodeKind::ClassDef {
+                    superclass,
+                    info,
+                    is_module: false,
+                    ..
+                } => (
+                    superclass
+                        .as_deref()
+                        .and_then(const_path)
+                        .map(|path| path.join("::")),
+                    info,
+                    false,
+                ),
+                NodeKind::SingletonClassDef { info, .. } => (None, info, true),
+                _ => return,
+            };
+            classes.push(ClassInfo {
+                name: class_name(node, outer.last()),
+                superclass,
+                is_singleton,
+                loc: node.loc,
+                method_locs: method_locs(&info.body),
+            });
+        });
+        classes
+    }
+
+    /// Collect classes which are defined more than once, in order of their first definition.
+    ///
+    /// Classes are compared by their qualified names, so `class ::Foo` at the top level
+    /// reopens `class Foo`. Singleton classes are ignored.
+    pub fn open_classes(&self) -> Vec<OpenClassInfo> {
+        let mut open_classes: Vec<OpenClassInfo> = vec![];
+        let mut map: HashMap<String, usize> = HashMap::new();
+        for class in self.classes() {
+            if class.is_singleton {
+                continue;
+            }
+            match map.get(&class.name) {
+                Some(i) => open_classes[*i].reopen_locs.push(class.loc),
+                None => {
+                    map.insert(class.name.clone(), open_classes.len());
+                    open_classes.push(OpenClassInfo {
+                        name: class.name,
+                        first_loc: class.loc,
+                        reopen_locs: vec![],
+                    });
+                }
+            }
+        }
+        open_classes.retain(|class| !class.reopen_locs.is_empty());
+        open_classes
+    }
+
+    /// Collect all module definitions including nested ones in pre-order.
+    pub fn modules(&self) -> Vec<ModuleInfo> {
+        let mut modules = vec![];
+        each_node_in_class(&self.node, &mut vec![], &mut |node, outer| {
+            if let NodeKind::ClassDef {
+                info,
+                is_module: true,
+                ..
+            } = &node.kind
+            {
+                modules.push(ModuleInfo {
+                    name: class_name(node, outer.last()),
+                    loc: node.loc,
+                    method_locs: method_locs(&info.body),
+                });
+            }
+        });
+        modules
+    }
+
+    /// Collect uses of well-known mixin modules like `Comparable` or `Enumerable`.
+    pub fn known_mixins(&self) -> Vec<MixinUse> {
+        let mut mixins = self.mixin_uses();
+        mixins.retain(|mixin| KNOWN_MIXINS.contains(&mixin.module_name.as_str()));
+        mixins
+    }
+
+    /// Build the edges of the mixin graph from `include`, `extend` and `prepend` calls
+    /// with constant arguments.
+    pub fn mixin_graph(&self) -> Vec<MixinEdge> {
+        self.mixin_uses()
+            .into_iter()
+            .map(|mixin| MixinEdge {
+                from_class: mixin.in_class.unwrap_or_else(|| "Object".to_string()),
+                to_module: mixin.module_name,
+                kind: mixin.kind,
+            })
+            .collect()
+    }
+
+    /// Collect all `include`, `extend` and `prepend` calls with constant arguments.
+    fn mixin_uses(&self) -> Vec<MixinUse> {
+        let mut mixins = vec![];
+        each_node_in_class(&self.node, &mut vec![], &mut |node, outer| {
+            let (method, arglist) = match &node.kind {
+                NodeKind::FuncCall {
+                    method, arglist, ..
+                } => (method, arglist),
+                NodeKind::MethodCall {
+                    receiver,
+                    method,
+                    arglist,
+                    ..
+                } if receiver.kind == NodeKind::SelfValue => (method, arglist),
+                _ => return,
+            };
+            let kind = match method.as_str() {
+                "include" => MixinKind::Include,
+                "extend" => MixinKind::Extend,
+                "prepend" => MixinKind::Prepend,
+                _ => return,
+            };
+            for arg in &arglist.args {
+                if let Some(path) = const_path(arg) {
+                    mixins.push(MixinUse {
+                        module_name: path.join("::"),
+                        in_class: outer.last().cloned(),
+                        kind,
+                        loc: arg.loc,
+                    });
+                }
+            }
+        });
+        mixins
+    }
+
+    /// Check whether `# frozen_string_literal: true` magic comment exists.
+    pub fn frozen(&self) -> bool {
+        self.frozen_string_literal == Some(true)
+    }
+}
+
+/// Check whether *node* is a call without a receiver or with `self` receiver.
+fn is_self_call(node: &Node) -> bool {
+    match &node.kind {
+        NodeKind::FuncCall { .. } => true,
+        NodeKind::MethodCall { receiver, .. } => receiver.kind == NodeKind::SelfValue,
+        _ => false,
+    }
+}
+
+/// Get the name of a symbol or string literal.
+fn symbol_name(node: &Node) -> Option<String> {
+    match &node.kind {
+        NodeKind::Symbol(name) | NodeKind::String(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Get the span which covers *node* and all of its descendants.
+fn subtree_span(node: &Node) -> Loc {
+    node.children()
+        .into_iter()
+        .fold(node.loc, |span, child| span.merge(subtree_span(child)))
+}
+
+/// Get the method name if *node* is a method definition.
+fn method_def_name(node: &Node) -> Option<&str> {
+    match &node.kind {
+        NodeKind::MethodDef(name, _) | NodeKind::SingletonMethodDef(_, name, _) => Some(name),
+        _ => None,
+    }
+}
+
+/// A brief summary of *node* for `ParseResult::format_ast()`.
+fn node_summary(node: &Node) -> Option<String> {
+    const MAX_STRING_LEN: usize = 20;
+    let summary = match &node.kind {
+        NodeKind::Integer(i) => i.to_string(),
+        NodeKind::Bignum(b) => b.to_string(),
+        NodeKind::Float(f) => format!("{:?}", f),
+        NodeKind::Rational(r) => r.to_string(),
+        NodeKind::Imaginary(r) => format!("{:?}", r),
+        NodeKind::Bool(b) => b.to_string(),
+        NodeKind::String(s) => {
+            let s: String = s.chars().take(MAX_STRING_LEN).collect();
+            format!("{:?}", s)
+        }
+        NodeKind::Symbol(s) => format!(":{}", s),
+        NodeKind::LocalVar(_, name)
+        | NodeKind::Ident(name)
+        | NodeKind::InstanceVar(name)
+        | NodeKind::GlobalVar(name)
+        | NodeKind::ClassVar(name)
+        | NodeKind::MethodDef(name, _)
+        | NodeKind::SingletonMethodDef(_, name, _)
+        | NodeKind::ClassDef { name, .. }
+        | NodeKind::MethodCall { method: name, .. }
+        | NodeKind::FuncCall { method: name, .. } => name.clone(),
+        NodeKind::Const { .. } => const_path(node)?.join("::"),
+        NodeKind::BinOp(op, ..) | NodeKind::AssignOp(op, ..) => format!("{:?}", op),
+        NodeKind::UnOp(op, _) => format!("{:?}", op),
+        NodeKind::VisibilityBlock { visibility, .. } => format!("{:?}", visibility),
+        _ => return None,
+    };
+    Some(summary)
+}
+
+/// Call *f* for each node in pre-order, with the qualified names of the enclosing classes and modules.
+fn each_node_in_class<'a>(
+    node: &'a Node,
+    outer: &mut Vec<String>,
+    f: &mut impl FnMut(&'a Node, &[String]),
+) {
+    f(node, outer);
+    let is_class = matches!(
+        node.kind,
+        NodeKind::ClassDef { .. } | NodeKind::SingletonClassDef { .. }
+    );
+    if is_class {
+        outer.push(class_name(node, outer.last()));
+    }
+    for child in node.children() {
+        each_node_in_class(child, outer, f);
+    }
+    if is_class {
+        outer.pop();
+    }
+}
+
+/// Get the qualified name of the class or module defined by *node*.
+fn class_name(node: &Node, outer: Option<&String>) -> String {
+    let (toplevel, mut path) = match &node.kind {
+        NodeKind::ClassDef { base, name, .. } => {
+            let (toplevel, mut path) = match base.as_deref() {
+                Some(base) => (
+                    matches!(base.kind, NodeKind::Const { toplevel: true, .. }),
+                    const_path(base).unwrap_or_default(),
+                ),
+                None => (false, vec![]),
+            };
+            path.push(name.clone());
+            (toplevel, path)
+        }
+        NodeKind::SingletonClassDef { singleton, .. } => match &singleton.kind {
+            NodeKind::SelfValue => return outer.cloned().unwrap_or_else(|| "main".to_string()),
+            NodeKind::Const { toplevel, .. } => {
+                (*toplevel, const_path(singleton).unwrap_or_default())
+            }
+            NodeKind::LocalVar(_, name)
+            | NodeKind::Ident(name)
+            | NodeKind::InstanceVar(name)
+            | NodeKind::GlobalVar(name)
+            | NodeKind::ClassVar(name) => return name.clone(),
+            _ => return "(singleton)".to_string(),
+        },
+        _ => unreachable!(),
+    };
+    match outer {
+        Some(outer) if !toplevel => {
+            path.insert(0, outer.clone());
+            path.join("::")
+        }
+        _ => path.join("::"),
+    }
+}
+
+/// Convert a constant reference like `A::B::C` to `["A", "B", "C"]`.
+fn const_path(node: &Node) -> Option<Vec<String>> {
+    match &node.kind {
+        NodeKind::Const {
+            parent,
+            prefix,
+            name,
+            ..
+        } => {
+            let mut path = match parent.as_deref() {
+                Some(parent) => const_path(parent)?,
+                None => vec![],
+            };
+            path.extend(prefix.iter().cloned());
+            path.push(name.clone());
+            Some(path)
+        }
+        _ => None,
+    }
+}
+
+/// Collect locations of the methods defined in *body*, excluding nested classes and modules.
+fn method_locs(body: &Node) -> Vec<Loc> {
+    fn collect(node: &Node, locs: &mut Vec<Loc>) {
+        match &node.kind {
+            NodeKind::MethodDef(..) | NodeKind::SingletonMethodDef(..) => locs.push(node.loc),
+            NodeKind::ClassDef { .. } | NodeKind::SingletonClassDef { .. } => return,
+            _ => {}
+        }
+        for child in node.children() {
+            collect(child, locs);
+        }
+    }
+    let mut locs = vec![];
+    collect(body, &mut locs);
+    locs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::test::parse_ok;
+
+    #[test]
+    fn frozen() {
+        assert!(parse_ok("# frozen_string_literal: true\n1").frozen());
+        assert!(parse_ok("#!/usr/bin/env ruby\n# frozen_string_literal: true\n1").frozen());
+        assert!(parse_ok("# -*- frozen-string-literal: true -*-\n1").frozen());
+        assert!(!parse_ok("# frozen_string_literal: false\n1").frozen());
+        assert!(!parse_ok("1\n2\n# frozen_string_literal: true\n").frozen());
+        assert!(!parse_ok("1").frozen());
+    }
+
+    #[test]
+    fn each_node_with_depth() {
+        let res = parse_ok("a = 1 + 2\nb");
+        let mut nodes = vec![];
+        res.each_node_with_depth(|node, depth| nodes.push((node.kind.clone(), depth)));
+        let depths: Vec<_> = nodes.iter().map(|(_, depth)| *depth).collect();
+        assert_eq!(vec![0, 1, 2, 2, 3, 3, 1], depths);
+        assert_eq!(NodeKind::Integer(2), nodes[5].0);
+    }
+
+    #[test]
+    fn call_chain_lengths() {
+        let res = parse_ok("a.b.c.d.e\nx = 1\nx.foo(a.b).bar\n@v.to_s\nfoo");
+        let chains = res.call_chain_lengths();
+        assert_eq!(3, chains.len());
+        assert_eq!(4, chains["a"]);
+        assert_eq!(2, chains["x"]);
+        assert_eq!(1, chains["@v"]);
+    }
+
+    #[test]
+    fn loc_to_node_path() {
+        let res = parse_ok("class C\n  def f(x)\n    x + 1\n  end\nend");
+        let path: Vec<_> = res
+            .loc_to_node_path(Loc(27, 27))
+            .into_iter()
+            .map(|node| match node.kind {
+                NodeKind::ClassDef { .. } => "class",
+                NodeKind::MethodDef(..) => "def",
+                NodeKind::Begin { .. } => "begin",
+                NodeKind::BinOp(..) => "binop",
+                NodeKind::Integer(_) => "integer",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(
+            vec!["class", "begin", "def", "begin", "binop", "integer"],
+            path
+        );
+        assert!(res.loc_to_node_path(Loc(100, 100)).is_empty());
+    }
+
+    #[test]
+    fn early_exits() {
+        let res = parse_ok(
+            r#"
+            exit
+            exit!(1)
+            Kernel.exit(2)
+            abort "failed"
+            raise ArgumentError
+            fail
+            foo.exit
+            "#,
+        );
+        let exits: Vec<_> = res
+            .early_exits()
+            .into_iter()
+            .map(|e| (e.kind, e.arg.map(|arg| arg.kind)))
+            .collect();
+        assert_eq!(
+            vec![
+                (EarlyExitKind::Exit, None),
+                (EarlyExitKind::Exit, Some(NodeKind::Integer(1))),
+                (EarlyExitKind::Exit, Some(NodeKind::Integer(2))),
+                (
+                    EarlyExitKind::Abort,
+                    Some(NodeKind::String("failed".to_string()))
+                ),
+                (
+                    EarlyExitKind::Raise,
+                    Some(NodeKind::Const {
+                        toplevel: false,
+                        parent: None,
+                        prefix: vec![],
+                        name: "ArgumentError".to_string()
+                    })
+                ),
+                (EarlyExitKind::Fail, None),
+            ],
+            exits
+        );
+    }
+
+    #[test]
+    fn heredoc_bodies() {
+        let res = parse_ok(
+            r#"a = <<~EOS + <<-'RAW'
+  Hello, #{name}!
+EOS
+  raw #{x}
+  RAW
+"#,
+        );
+        let heredocs = res.heredoc_bodies();
+        assert_eq!(2, heredocs.len());
+        assert_eq!("EOS", heredocs[0].tag);
+        assert!(heredocs[0].stripped);
+        assert_eq!(3, heredocs[0].content_parts.len());
+        assert!(matches!(
+            heredocs[0].content_parts[1],
+            StringPart::Interpolation(_)
+        ));
+        assert_eq!(
+            "  Hello, #{name}!\n",
+            res.source_info
+                .code
+                .get(heredocs[0].loc.0..=heredocs[0].loc.1)
+                .unwrap()
+        );
+        assert_eq!("RAW", heredocs[1].tag);
+        assert!(!heredocs[1].stripped);
+        assert_eq!(
+            vec![StringPart::Str("  raw #{x}\n".to_string())],
+            heredocs[1].content_parts
+        );
+    }
+
+    #[test]
+    fn deeply_nested_methods() {
+        let res = parse_ok(
+            r#"
+            def top; end
+            module A
+              class B
+                def f; end
+                class << self
+                  def g; end
+                end
+                begin
+                  def h; end
+                end
+              end
+            end
+            "#,
+        );
+        assert_eq!(
+            vec![("g".to_string(), 3), ("h".to_string(), 3)],
+            res.deeply_nested_methods()
+        );
+        assert_eq!(
+            vec![
+                ("f".to_string(), 2),
+                ("g".to_string(), 3),
+                ("h".to_string(), 3)
+            ],
+            res.deeply_nested_methods_with_threshold(1)
+        );
+    }
+
+    #[test]
+    fn format_ast() {
+        let res = parse_ok("def foo(x)\n  puts \"Hello, world! Hello, world!\", x + 1\nend");
+        assert_eq!(
+            r#"MethodDef foo
+  Begin
+    FuncCall puts
+      String "Hello, world! Hello,"
+      BinOp Add
+        LocalVar x
+        Integer 1
+"#,
+            res.format_ast(2)
+        );
+    }
+
+    #[test]
+    fn numeric_literals() {
+        let res = parse_ok("a = [1_000, -3.14, 2i, 99999999999999999999, __LINE__, -7]");
+        let literals: Vec<_> = res
+            .numeric_literals()
+            .into_iter()
+            .map(|info| (info.kind, info.value_repr))
+            .collect();
+        assert_eq!(
+            vec![
+                (NumericKind::Integer, "1_000".to_string()),
+                (NumericKind::Float, "-3.14".to_string()),
+                (NumericKind::Complex, "2i".to_string()),
+                (NumericKind::Bignum, "99999999999999999999".to_string()),
+                (NumericKind::Integer, "-7".to_string()),
+            ],
+            literals
+        );
+    }
+
+    #[test]
+    fn boolean_literals() {
+        let res = parse_ok("if true then f(false, nil) end\nx = nil");
+        assert_eq!(
+            vec![(true, Loc(3, 6)), (false, Loc(15, 19))],
+            res.boolean_literals()
+        );
+        assert_eq!(vec![Loc(22, 24), Loc(35, 37)], res.nil_literals());
+    }
+
+    #[test]
+    fn find_method() {
+        let res = parse_ok(
+            r#"
+            class A
+              def foo; end
+              def self.bar; end
+            end
+            class A
+              def foo(x); end
+            end
+            "#,
+        );
+        let foo = res.find_all_methods("foo");
+        assert_eq!(2, foo.len());
+        assert_eq!(Some(foo[0]), res.find_method("foo"));
+        assert!(matches!(
+            res.find_method("bar").unwrap().kind,
+            NodeKind::SingletonMethodDef(..)
+        ));
+        assert_eq!(None, res.find_method("baz"));
+        assert!(res.find_all_methods("baz").is_empty());
+    }
+
+    #[test]
+    fn block_pass_sites() {
+        let res = parse_ok("arr.map(&:to_s)\neach(&blk)\nsuper(1, &m)\narr.each { |x| x }\nf(1)");
+        let sites: Vec<_> = res
+            .block_pass_sites()
+            .into_iter()
+            .map(|site| {
+                (
+                    site.method_name,
+                    site.block_expr.kind,
+                    site.is_symbol_shorthand,
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "map".to_string(),
+                    NodeKind::Symbol("to_s".to_string()),
+                    true
+                ),
+                (
+                    "each".to_string(),
+                    NodeKind::Ident("blk".to_string()),
+                    false
+                ),
+                ("super".to_string(), NodeKind::Ident("m".to_string()), false),
+            ],
+            sites
+        );
+    }
+
+    #[test]
+    fn call_sites_for() {
+        let res = parse_ok("validate!\nvalidate!(1, 2)\nobj.validate! { x }\nvalidate");
+        let sites = res.call_sites_for("validate!");
+        assert_eq!(3, sites.len());
+        assert_eq!(
+            (None, 0, false),
+            (
+                sites[0].receiver.clone(),
+                sites[0].args.len(),
+                sites[0].block.is_some()
+            )
+        );
+        assert_eq!(
+            (None, 2, false),
+            (
+                sites[1].receiver.clone(),
+                sites[1].args.len(),
+                sites[1].block.is_some()
+            )
+        );
+        assert_eq!(
+            "obj",
+            res.source_text_for(sites[2].receiver.as_ref().unwrap())
+        );
+        assert!(sites[2].block.is_some());
+        assert!(res.call_sites_for("other").is_empty());
+    }
+
+    #[test]
+    fn scope_at() {
+        let code = r#"a = 1
+class Foo
+  def bar(x)
+    y = 2
+    [1].each do |z|
+      puts z
+    end
+  end
+end
+"#;
+        let res = parse_ok(code);
+        let pos = code.find("puts").unwrap();
+        let scope = res.scope_at(Loc(pos, pos));
+        assert_eq!(ScopeKind::Block, scope.kind);
+        assert_eq!(vec!["z", "x", "y"], scope.local_vars);
+        assert_eq!(Some("bar".to_string()), scope.enclosing_method);
+        assert_eq!(Some("Foo".to_string()), scope.enclosing_class);
+        let pos = code.find("y = 2").unwrap();
+        let scope = res.scope_at(Loc(pos, pos));
+        assert_eq!(ScopeKind::Method, scope.kind);
+        assert_eq!(vec!["x", "y"], scope.local_vars);
+        let scope = res.scope_at(Loc(0, 0));
+        assert_eq!(ScopeKind::Eval, scope.kind);
+        assert_eq!(vec!["a"], scope.local_vars);
+        assert_eq!(None, scope.enclosing_class);
+    }
+
+    #[test]
+    fn has_main_guard() {
+        assert!(parse_ok("def main; end\nif __FILE__ == $0\n  main\nend").has_main_guard());
+        assert!(parse_ok("main if $PROGRAM_NAME == __FILE__").has_main_guard());
+        assert!(parse_ok("if __FILE__.equal?($0) then main end").has_main_guard());
+        assert!(parse_ok("if $0.eql?(__FILE__) then main end").has_main_guard());
+        assert!(!parse_ok("if __FILE__ == \"a.rb\" then main end").has_main_guard());
+        assert!(!parse_ok("def f\n  main if __FILE__ == $0\nend").has_main_guard());
+    }
+
+    #[test]
+    fn prune() {
+        let res = parse_ok("class A\n  def f; end\n  X = 1\nend\ndef g; end");
+        let pruned = res.prune(|node| matches!(node.kind, NodeKind::MethodDef(..)));
+        assert!(pruned.find_method("f").is_none());
+        assert!(pruned.find_method("g").is_none());
+        assert_eq!(1, pruned.classes().len());
+        assert!(res.find_method("f").is_some());
+        let pruned = res.prune(|_| true);
+        assert!(pruned.node.is_empty());
+    }
+
+    #[test]
+    fn method_calls_count() {
+        let res = parse_ok("puts a.foo\nputs(b.foo.bar)\nfoo");
+        assert_eq!(
+            vec![
+                ("foo".to_string(), 3),
+                ("puts".to_string(), 2),
+                ("a".to_string(), 1),
+                ("b".to_string(), 1),
+                ("bar".to_string(), 1),
+            ],
+            res.method_calls_count().into_sorted_vec()
+        );
+    }
+
+    #[test]
+    fn collect_definitions() {
+        let res = parse_ok(
+            r#"
+            module M
+              class A
+                def foo; end
+                alias bar foo
+                alias_method :baz, :foo
+                self.alias_method "qux", :foo
+                def self.create; end
+                private_class_method :new, "create"
+                public_class_method def self.build; end
+              end
+            end
+            "#,
+        );
+        let defs: Vec<_> = res
+            .collect_definitions()
+            .into_iter()
+            .map(|def| match def {
+                Definition::Method {
+                    name,
+                    owner,
+                    is_singleton,
+                    ..
+                } => format!("def {}#{} {}", owner.unwrap(), name, is_singleton),
+                Definition::Class { name, .. } => format!("class {}", name),
+                Definition::Module { name, .. } => format!("module {}", name),
+                Definition::Alias {
+                    new_name, old_name, ..
+                } => format!("alias {} {}", new_name, old_name),
+                Definition::AliasMethod {
+                    new_name, old_name, ..
+                } => format!("alias_method {} {}", new_name, old_name),
+                Definition::ClassMethodVisibility {
+                    name, visibility, ..
+                } => format!("{:?} {}", visibility, name),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "module M",
+                "class M::A",
+                "def M::A#foo false",
+                "alias bar foo",
+                "alias_method baz foo",
+                "alias_method qux foo",
+                "def M::A#create true",
+                "Private new",
+                "Private create",
+                "Public build",
+                "def M::A#build true",
+            ],
+            defs
+        );
+    }
+
+    #[test]
+    fn constant_path_for() {
+        let res = parse_ok("[A::B::C, A, ::D::E, foo::Bar, a]");
+        let elems = match &res.node.kind {
+            NodeKind::Array(elems, _) => elems,
+            _ => panic!(),
+        };
+        let paths: Vec<_> = elems.iter().map(ParseResult::constant_path_for).collect();
+        let path = |v: &[&str]| Some(v.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        assert_eq!(
+            vec![
+                path(&["A", "B", "C"]),
+                path(&["A"]),
+                path(&["D", "E"]),
+                None,
+                None
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn instance_variable_accesses() {
+        let res = parse_ok(
+            r#"
+            @top = 1
+            class A
+              def initialize(x)
+                @a = @a.to_i + x
+                @b += 1
+                @c ||= @a
+              end
+            end
+            "#,
+        );
+        let accesses: Vec<_> = res
+            .instance_variable_accesses()
+            .into_iter()
+            .map(|access| {
+                (
+                    access.name,
+                    access.is_write,
+                    access.in_method.unwrap_or_default(),
+                )
+            })
+            .collect();
+        let access =
+            |name: &str, is_write, method: &str| (name.to_string(), is_write, method.to_string());
+        assert_eq!(
+            vec![
+                access("top", true, ""),
+                access("a", false, "initialize"),
+                access("a", true, "initialize"),
+                access("b", false, "initialize"),
+                access("b", true, "initialize"),
+                access("c", false, "initialize"),
+                access("a", false, "initialize"),
+                access("c", true, "initialize"),
+            ],
+            accesses
+        );
+    }
+
+    #[test]
+    fn mixin_graph() {
+        let res = parse_ok(
+            r#"
+            include Kernel
+            module M
+              class A
+                include Comparable, Foo::Bar
+                extend Forwardable
+                self.prepend M
+              end
+            end
+            "#,
+        );
+        let edges: Vec<_> = res
+            .mixin_graph()
+            .into_iter()
+            .map(|edge| format!("{} -{:?}-> {}", edge.from_class, edge.kind, edge.to_module))
+            .collect();
+        assert_eq!(
+            vec![
+                "Object -Include-> Kernel",
+                "M::A -Include-> Comparable",
+                "M::A -Include-> Foo::Bar",
+                "M::A -Extend-> Forwardable",
+                "M::A -Prepend-> M",
+            ],
+            edges
+        );
+    }
+
+    #[test]
+    fn struct_definitions() {
+        let res = parse_ok(
+            r#"
+            module M
+              Point = Struct.new(:x, :y, keyword_init: true)
+              class Pair < Struct.new(:a, :b)
+              end
+            end
+            Struct.new("Named", :z)
+            list = [Struct.new(:v, keyword_init: false)]
+            "#,
+        );
+        let defs: Vec<_> = res
+            .struct_definitions()
+            .into_iter()
+            .map(|def| (def.name, def.fields.join(","), def.keyword_init))
+            .collect();
+        assert_eq!(
+            vec![
+                (Some("M::Point".to_string()), "x,y".to_string(), true),
+                (Some("M::Pair".to_string()), "a,b".to_string(), false),
+                (Some("Struct::Named".to_string()), "z".to_string(), false),
+                (None, "v".to_string(), false),
+            ],
+            defs
+        );
+    }
+
+    #[test]
+    fn open_classes() {
+        let res = parse_ok(
+            r#"
+class Foo; end
+class Bar; end
+class ::Foo; end
+module M
+  class Foo; end
+  class Foo; end
+end
+class << self; end
+class << self; end
+class Foo; end
+"#,
+        );
+        let open_classes: Vec<_> = res
+            .open_classes()
+            .into_iter()
+            .map(|class| (class.name, class.first_loc.0, class.reopen_locs.len()))
+            .collect();
+        assert_eq!(
+            vec![("Foo".to_string(), 7, 2), ("M::Foo".to_string(), 65, 1)],
+            open_classes
+        );
+    }
+
+    #[test]
+    fn classes() {
+        let res = parse_ok(
+            r#"
+            class Outer < Base::Object
+              def f; end
+              class Inner
+                def g; end
+                def self.h; end
+              end
+              class << self
+                def i; end
+              end
+              module M
+                def j; end
+              end
+            end
+            class ::Top; end
+            "#,
+        );
+        let classes = res.classes();
+        let names: Vec<_> = classes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(vec!["Outer", "Outer::Inner", "Outer", "Top"], names);
+        assert_eq!(Some("Base::Object".to_string()), classes[0].superclass);
+        assert_eq!(1, classes[0].method_locs.len());
+        assert_eq!(2, classes[1].method_locs.len());
+        assert!(classes[2].is_singleton);
+        let modules = res.modules();
+        assert_eq!(1, modules.len());
+        assert_eq!("Outer::M", modules[0].name);
+        assert_eq!(1, modules[0].method_locs.len());
+    }
+
+    #[test]
+    fn known_mixins() {
+        let res = parse_ok(
+            r#"
+            class Version
+              include Comparable, Enumerable
+              include Foo
+              self.extend Forwardable
+            end
+            prepend Singleton
+            "#,
+        );
+        let mixins: Vec<_> = res
+            .known_mixins()
+            .into_iter()
+            .map(|m| (m.module_name, m.in_class, m.kind))
+            .collect();
+        let version = Some("Version".to_string());
+        assert_eq!(
+            vec![
+                (
+                    "Comparable".to_string(),
+                    version.clone(),
+                    MixinKind::Include
+                ),
+                (
+                    "Enumerable".to_string(),
+                    version.clone(),
+                    MixinKind::Include
+                ),
+                ("Forwardable".to_string(), version, MixinKind::Extend),
+                ("Singleton".to_string(), None, MixinKind::Prepend),
+            ],
+            mixins
+        );
+    }
+}
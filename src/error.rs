@@ -15,12 +15,49 @@ impl ParseErr {
             source_info,
         }
     }
+
+    /// Get the line number (1-based) and the column (0-based) of the error position.
+    pub fn line_col(&self) -> (usize, usize) {
+        (
+            self.source_info.byte_to_line(self.loc.0),
+            self.source_info.byte_to_col(self.loc.0),
+        )
+    }
+
+    /// Get the line number (1-based) and the column (0-based) of the error position.
+    ///
+    /// Return None if the error position is out of the source code.
+    pub fn loc_in_source(&self) -> Option<(usize, usize)> {
+        if self.loc.0 <= self.source_info.code.len() {
+            Some(self.line_col())
+        } else {
+            None
+        }
+    }
 }
 
+impl std::fmt::Display for ParseErr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (line, col) = self.line_col();
+        write!(
+            f,
+            "{}:{}:{}: {:?}",
+            self.source_info.file_name(),
+            line,
+            col,
+            self.kind
+        )
+    }
+}
+
+impl std::error::Error for ParseErr {}
+
 #[derive(Clone, PartialEq)]
 pub enum ParseErrKind {
     UnexpectedEOF,
     SyntaxError(String),
+    /// the size of the code exceeds the limit. (in bytes)
+    FileTooLarge(usize),
 }
 
 impl std::fmt::Debug for ParseErrKind {
@@ -28,6 +65,47 @@ impl std::fmt::Debug for ParseErrKind {
         match self {
             Self::UnexpectedEOF => write!(f, "SyntaxError (Unexpected EOF.)"),
             Self::SyntaxError(msg) => write!(f, "SyntaxError ({})", msg),
+            Self::FileTooLarge(max) => write!(f, "FileTooLarge (exceeds {} bytes.)", max),
         }
     }
 }
+
+/// A non-fatal diagnostic about the code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    pub kind: ParseWarnKind,
+    pub loc: Loc,
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub(crate) fn new(kind: ParseWarnKind, loc: Loc, message: impl Into<String>) -> Self {
+        ParseWarning {
+            kind,
+            loc,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarnKind {
+    /// `break` outside of loops and blocks.
+    BreakOutsideLoop,
+    /// `return` in a block.
+    ReturnInBlock,
+    /// `retry` outside of rescue clauses.
+    RetryOutsideRescue,
+    /// a `when` pattern which appeared in the former branch.
+    DuplicateWhen,
+    /// statements after `return`, `break`, `next` or `redo`.
+    UnreachableCode,
+    /// `rescue` clause without exception classes.
+    BareRescue,
+    /// a block parameter which has the same name as an outer local variable.
+    ShadowedVariable,
+    /// an expression which has no effect. e.g. `a = a`
+    UselessExpression,
+    /// `foo -1` or `foo *a`, which is parsed as a method call with an argument.
+    AmbiguousOperator,
+}
@@ -1,15 +1,21 @@
+mod analysis;
 mod error;
+mod lint;
 mod lvar_collector;
 mod node;
 mod parser;
 mod source_info;
 mod token;
+mod visitor;
+pub use analysis::*;
 pub use error::*;
+pub use lint::*;
 pub use lvar_collector::*;
 pub use node::*;
 pub use parser::*;
 pub use source_info::*;
-use token::*;
+pub use token::{Punct, Reserved, Token, TokenKind};
+pub use visitor::*;
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Annot<T: PartialEq + Default> {
@@ -27,8 +33,9 @@ impl<T: PartialEq + Default> Annot<T> {
     }
 }
 
+/// The content of a string literal, which may not be valid UTF-8. (e.g. `"\xff"`)
 #[derive(Debug, Clone, PartialEq)]
-enum RubyString {
+pub enum RubyString {
     Bytes(Vec<u8>),
     Utf8(String),
 }
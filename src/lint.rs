@@ -0,0 +1,674 @@
+use super::*;
+
+/// An assignment which can be written with a self-assignment operator. (e.g. `x = x + 1`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelfAssignPattern {
+    pub var_name: String,
+    /// the binary operator in the right-hand side. (e.g. `"+"` for `x = x + 1`)
+    /// None for `x = x`.
+    pub operator: Option<String>,
+    pub loc: Loc,
+}
+
+/// A reassignment of a method parameter in the method body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RebindWarning {
+    pub param_name: String,
+    pub assign_loc: Loc,
+    pub method_name: String,
+}
+
+/// Recommended threshold for `ParseResult::detect_long_parameter_lists()`.
+pub const DEFAULT_MAX_PARAMS: usize = 5;
+
+/// A method which has too many parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongParamWarning {
+    pub method_name: String,
+    /// the number of parameters excluding `&block` and `...`.
+    pub param_count: usize,
+    pub loc: Loc,
+}
+
+/// Position of a string literal which may be accidentally mutable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreezeKind {
+    /// a key of a hash literal.
+    HashKey,
+    /// the value assigned to a constant.
+    ConstantValue,
+    /// the return value of a method.
+    ReturnedLiteral,
+}
+
+/// A string literal which should be frozen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingFreezeHint {
+    pub loc: Loc,
+    pub kind: FreezeKind,
+}
+
+/// A condition which is always truthy or always falsy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UselessCondition {
+    /// location of the condition.
+    pub loc: Loc,
+    /// true if the condition is always truthy, i.e. the `then` branch of `if`, the body of
+    /// `while` or the `when` branch is always executed. Note that the body of `unless` is
+    /// never executed in this case.
+    pub always_taken: bool,
+}
+
+/// The context of a node for `ParseResult::check_syntax_only()`.
+#[derive(Debug, Clone, Copy, Default)]
+struct LintContext {
+    /// in a loop or a block.
+    breakable: bool,
+    /// the innermost scope is a block.
+    in_block: bool,
+    /// in a rescue clause.
+    in_rescue: bool,
+}
+
+impl ParseResult {
+    /// Check the AST for well-formedness and return warnings.
+    ///
+    /// This is a best-effort lint pass and never fails.
+    pub fn check_syntax_only(&self) -> Vec<ParseWarning> {
+        let mut warnings = vec![];
+        self.check_node(&self.node, LintContext::default(), &mut warnings);
+        warnings
+    }
+
+    /// Detect assignments like `x = x + 1` or `x = x || y`, which should use `+=` or `||=`.
+    ///
+    /// Only the outermost operator of the right-hand side is examined, so `x = x * 2 + 1`
+    /// is not reported.
+    pub fn detect_self_assignment_patterns(&self) -> Vec<SelfAssignPattern> {
+        fn var_name(node: &Node) -> Option<&str> {
+            match &node.kind {
+                NodeKind::LocalVar(_, name)
+                | NodeKind::InstanceVar(name)
+                | NodeKind::GlobalVar(name)
+                | NodeKind::ClassVar(name) => Some(name),
+                _ => None,
+            }
+        }
+        let mut patterns = vec![];
+        self.each_node_with_depth(|node, _| {
+            let (lhs, rhs) = match &node.kind {
+                NodeKind::MulAssign(mlhs, mrhs) if mlhs.len() == 1 && mrhs.len() == 1 => {
+                    (&mlhs[0], &mrhs[0])
+                }
+                _ => return,
+            };
+            let name = match var_name(lhs) {
+                Some(name) => name,
+                None => return,
+            };
+            let operator = match &rhs.kind {
+                NodeKind::BinOp(BinOp::Cmp(_) | BinOp::Match, ..) => return,
+                NodeKind::BinOp(op, operand, _) if var_name(operand) == Some(name) => {
+                    Some(op.to_str().to_string())
+                }
+                _ if var_name(rhs) == Some(name) => None,
+                _ => return,
+            };
+            patterns.push(SelfAssignPattern {
+                var_name: name.to_string(),
+                operator,
+                loc: node.loc,
+            });
+        });
+        patterns
+    }
+
+    /// Find string literals which may be meant to be concatenated.
+    ///
+    /// Reports consecutive statements which are both string literals (e.g. a line continuation
+    /// `\` is missing), and `"a" + "b"` where both operands are literals, which can be written as
+    /// a single literal. The location of the second literal is returned.
+    pub fn string_concat_opportunities(&self) -> Vec<Loc> {
+        fn is_str(node: &Node) -> bool {
+            matches!(node.kind, NodeKind::String(_) | NodeKind::Bytes(_))
+        }
+        let mut locs = vec![];
+        self.each_node_with_depth(|node, _| match &node.kind {
+            NodeKind::CompStmt(nodes) => {
+                for pair in nodes.windows(2) {
+                    if is_str(&pair[0]) && is_str(&pair[1]) {
+                        locs.push(pair[1].loc);
+                    }
+                }
+            }
+            NodeKind::BinOp(BinOp::Add, lhs, rhs) if is_str(lhs) && is_str(rhs) => {
+                locs.push(rhs.loc)
+            }
+            _ => {}
+        });
+        locs
+    }
+
+    /// Detect reassignments of method parameters in method bodies, e.g. `def f(x); x = x + 1; end`.
+    ///
+    /// Assignments in blocks are also reported unless the block shadows the parameter.
+    pub fn detect_rebinds(&self) -> Vec<RebindWarning> {
+        fn param_names(params: &[FormalParam]) -> Vec<&str> {
+            let mut names = vec![];
+            for param in params {
+                match &param.kind {
+                    ParamKind::Param(name)
+                    | ParamKind::Post(name)
+                    | ParamKind::Optional(name, _)
+                    | ParamKind::Rest(Some(name))
+                    | ParamKind::Keyword(name, _)
+                    | ParamKind::KWRest(name)
+                    | ParamKind::Block(name) => names.push(name.as_str()),
+                    ParamKind::Destruct(params) => {
+                        names.extend(params.iter().map(|(name, _)| name.as_str()))
+                    }
+                    ParamKind::Rest(None) | ParamKind::Delegate => {}
+                }
+            }
+            names
+        }
+        /// *depth* is the number of blocks between the method and *node*.
+        fn visit(
+            node: &Node,
+            method: &str,
+            params: &[&str],
+            depth: usize,
+            warnings: &mut Vec<RebindWarning>,
+        ) {
+            let targets: Vec<&Node> = match &node.kind {
+                NodeKind::MulAssign(mlhs, _) => mlhs.iter().collect(),
+                NodeKind::AssignOp(_, lhs, _) => vec![lhs],
+                NodeKind::MethodDef(..)
+                | NodeKind::SingletonMethodDef(..)
+                | NodeKind::ClassDef { .. }
+                | NodeKind::SingletonClassDef { .. } => return,
+                _ => vec![],
+            };
+            for target in targets {
+                if let NodeKind::LocalVar(outer, name) = &target.kind {
+                    if *outer == depth && params.contains(&name.as_str()) {
+                        warnings.push(RebindWarning {
+                            param_name: name.clone(),
+                            assign_loc: node.loc,
+                            method_name: method.to_string(),
+                        });
+                    }
+                }
+            }
+            let depth = match node.kind {
+                NodeKind::Lambda(_) => depth + 1,
+                _ => depth,
+            };
+            for child in node.children() {
+                visit(child, method, params, depth, warnings);
+            }
+        }
+        let mut warnings = vec![];
+        self.each_node_with_depth(|node, _| {
+            if let NodeKind::MethodDef(name, info) | NodeKind::SingletonMethodDef(_, name, info) =
+                &node.kind
+            {
+                let params = param_names(&info.params);
+                visit(&info.body, name, &params, 0, &mut warnings);
+            }
+        });
+        warnings
+    }
+
+    /// Detect methods whose parameter count (excluding `&block` and `...`) exceeds *threshold*.
+    ///
+    /// `DEFAULT_MAX_PARAMS` is the recommended threshold.
+    pub fn detect_long_parameter_lists(&self, threshold: usize) -> Vec<LongParamWarning> {
+        let mut warnings = vec![];
+        self.each_node_with_depth(|node, _| {
+            if let NodeKind::MethodDef(name, info) | NodeKind::SingletonMethodDef(_, name, info) =
+                &node.kind
+            {
+                let param_count = info
+                    .params
+                    .iter()
+                    .filter(|param| {
+                        !matches!(param.kind, ParamKind::Block(_) | ParamKind::Delegate)
+                    })
+                    .count();
+                if param_count > threshold {
+                    warnings.push(LongParamWarning {
+                        method_name: name.clone(),
+                        param_count,
+                        loc: node.loc,
+                    });
+                }
+            }
+        });
+        warnings
+    }
+
+    /// Find string literals in positions where mutability is risky.
+    ///
+    /// Nothing is reported if `# frozen_string_literal: true` exists.
+    pub fn detect_missing_freeze(&self) -> Vec<MissingFreezeHint> {
+        fn is_str(node: &Node) -> bool {
+            matches!(node.kind, NodeKind::String(_))
+        }
+        /// The last statement of the method body.
+        fn last_stmt(node: &Node) -> &Node {
+            match &node.kind {
+                NodeKind::Begin {
+                    body,
+                    rescue,
+                    else_: None,
+                    ensure: None,
+                } if rescue.is_empty() => last_stmt(body),
+                NodeKind::CompStmt(nodes) if !nodes.is_empty() => last_stmt(nodes.last().unwrap()),
+                _ => node,
+            }
+        }
+        let mut hints = vec![];
+        if self.frozen() {
+            return hints;
+        }
+        let mut push = |node: &Node, kind| {
+            if is_str(node) {
+                hints.push(MissingFreezeHint {
+                    loc: node.loc,
+                    kind,
+                })
+            }
+        };
+        self.each_node_with_depth(|node, _| match &node.kind {
+            NodeKind::Hash(pairs, _) => {
+                for (key, _) in pairs {
+                    push(key, FreezeKind::HashKey);
+                }
+            }
+            NodeKind::MulAssign(mlhs, mrhs) if mlhs.len() == 1 && mrhs.len() == 1 => {
+                if matches!(mlhs[0].kind, NodeKind::Const { .. }) {
+                    push(&mrhs[0], FreezeKind::ConstantValue);
+                }
+            }
+            NodeKind::Return(val) => push(val, FreezeKind::ReturnedLiteral),
+            NodeKind::MethodDef(_, info) | NodeKind::SingletonMethodDef(_, _, info) => {
+                push(last_stmt(&info.body), FreezeKind::ReturnedLiteral)
+            }
+            _ => {}
+        });
+        hints
+    }
+
+    /// Detect conditions of `if`, `unless`, `while`, `until` and `case`/`when` which are
+    /// determined by literals.
+    pub fn detect_useless_conditions(&self) -> Vec<UselessCondition> {
+        /// Get the truthiness of a literal.
+        fn truthiness(node: &Node) -> Option<bool> {
+            match &node.kind {
+                NodeKind::Nil | NodeKind::Bool(false) => Some(false),
+                NodeKind::Bool(true)
+                | NodeKind::Integer(_)
+                | NodeKind::Bignum(_)
+                | NodeKind::Float(_)
+                | NodeKind::String(_)
+                | NodeKind::Symbol(_) => Some(true),
+                _ => None,
+            }
+        }
+        let mut conditions = vec![];
+        self.each_node_with_depth(|node, _| match &node.kind {
+            NodeKind::If { cond, .. } => {
+                if let Some(always_taken) = truthiness(cond) {
+                    conditions.push(UselessCondition {
+                        loc: cond.loc,
+                        always_taken,
+                    });
+                }
+            }
+            NodeKind::While { cond, cond_op, .. } => {
+                if let Some(truthy) = truthiness(cond) {
+                    conditions.push(UselessCondition {
+                        loc: cond.loc,
+                        // `until` runs while the condition is falsy.
+                        always_taken: truthy == *cond_op,
+                    });
+                }
+            }
+            NodeKind::Case {
+                cond: Some(subject),
+                when_,
+                ..
+            } if truthiness(subject).is_some() => {
+                for branch in when_ {
+                    for when in &branch.when {
+                        if truthiness(when).is_some()
+                            && std::mem::discriminant(&when.kind)
+                                == std::mem::discriminant(&subject.kind)
+                        {
+                            conditions.push(UselessCondition {
+                                loc: when.loc,
+                                always_taken: when.kind == subject.kind,
+                            });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        });
+        conditions
+    }
+
+    fn check_node(&self, node: &Node, ctx: LintContext, warnings: &mut Vec<ParseWarning>) {
+        let mut block = None;
+        let mut rescue_bodies = vec![];
+        let ctx = match &node.kind {
+            NodeKind::Break(_) if !ctx.breakable => {
+                warnings.push(ParseWarning::new(
+                    ParseWarnKind::BreakOutsideLoop,
+                    node.loc,
+                    "Invalid break",
+                ));
+                ctx
+            }
+            NodeKind::Return(_) if ctx.in_block => {
+                warnings.push(ParseWarning::new(
+                    ParseWarnKind::ReturnInBlock,
+                    node.loc,
+                    "return in a block",
+                ));
+                ctx
+            }
+            NodeKind::Retry if !ctx.in_rescue => {
+                warnings.push(ParseWarning::new(
+                    ParseWarnKind::RetryOutsideRescue,
+                    node.loc,
+                    "Invalid retry",
+                ));
+                ctx
+            }
+            NodeKind::CompStmt(nodes) => {
+                if let Some(i) = nodes.iter().position(|node| {
+                    matches!(
+                        node.kind,
+                        NodeKind::Return(_)
+                            | NodeKind::Break(_)
+                            | NodeKind::Next(_)
+                            | NodeKind::Redo
+                    )
+                }) {
+                    if let Some(node) = nodes.get(i + 1) {
+                        warnings.push(ParseWarning::new(
+                            ParseWarnKind::UnreachableCode,
+                            node.loc,
+                            "statement not reached",
+                        ));
+                    }
+                }
+                ctx
+            }
+            NodeKind::Case { when_, .. } => {
+                let mut patterns = vec![];
+                for branch in when_ {
+                    for when in &branch.when {
+                        let pattern = self.source_text_for(when);
+                        if patterns.contains(&pattern) {
+                            warnings.push(ParseWarning::new(
+                                ParseWarnKind::DuplicateWhen,
+                                when.loc,
+                                format!("duplicated `when' clause: {}", pattern),
+                            ));
+                        } else {
+                            patterns.push(pattern);
+                        }
+                    }
+                }
+                ctx
+            }
+            NodeKind::Begin { rescue, .. } => {
+                for entry in rescue {
+                    if entry.exception_list.is_empty() {
+                        warnings.push(ParseWarning::new(
+                            ParseWarnKind::BareRescue,
+                            entry.body.loc,
+                            "rescue without exception classes",
+                        ));
+                    }
+                    rescue_bodies.push(entry.body.as_ref());
+                }
+                ctx
+            }
+            NodeKind::While { .. } | NodeKind::For { .. } => LintContext {
+                breakable: true,
+                ..ctx
+            },
+            NodeKind::Lambda(_) => LintContext {
+                breakable: true,
+                ..ctx
+            },
+            NodeKind::MethodDef(..)
+            | NodeKind::SingletonMethodDef(..)
+            | NodeKind::ClassDef { .. }
+            | NodeKind::SingletonClassDef { .. } => LintContext::default(),
+            NodeKind::FuncCall { arglist, .. }
+            | NodeKind::MethodCall { arglist, .. }
+            | NodeKind::Yield(arglist)
+            | NodeKind::Super(Some(arglist)) => {
+                block = arglist.block.as_deref();
+                ctx
+            }
+            NodeKind::Catch { body, .. } => {
+                block = Some(body);
+                ctx
+            }
+            _ => ctx,
+        };
+        for child in node.children() {
+            let ctx = if let Some(block) = block.filter(|block| std::ptr::eq(*block, child)) {
+                // `-> { .. }` is a lambda, `{ .. }` passed to a method is a block.
+                let in_block = matches!(block.kind, NodeKind::Lambda(_));
+                LintContext { in_block, ..ctx }
+            } else if rescue_bodies.iter().any(|body| std::ptr::eq(*body, child)) {
+                LintContext {
+                    in_rescue: true,
+                    ..ctx
+                }
+            } else if matches!(child.kind, NodeKind::Lambda(_)) {
+                LintContext {
+                    in_block: false,
+                    ..ctx
+                }
+            } else {
+                ctx
+            };
+            self.check_node(child, ctx, warnings);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::test::parse_ok;
+
+    fn warnings(code: &str) -> Vec<ParseWarnKind> {
+        parse_ok(code)
+            .check_syntax_only()
+            .into_iter()
+            .map(|warning| warning.kind)
+            .collect()
+    }
+
+    #[test]
+    fn detect_self_assignment_patterns() {
+        let res = parse_ok("x = 1\nx = x + 1\n@a = @a || b\nx = 2 * x\nx = x * 2 + 1\nx = x == 1\n$g = $g\ny, x = x - 1, 2");
+        let patterns: Vec<_> = res
+            .detect_self_assignment_patterns()
+            .into_iter()
+            .map(|p| (p.var_name, p.operator))
+            .collect();
+        assert_eq!(
+            vec![
+                ("x".to_string(), Some("+".to_string())),
+                ("@a".to_string(), Some("||".to_string())),
+                ("$g".to_string(), None),
+            ],
+            patterns
+        );
+    }
+
+    #[test]
+    fn string_concat_opportunities() {
+        let code = "x = \"foo\" \"bar\"\nputs \"a\" + 'b'\ny = x + \"c\"\n\"d\"\n\"e\"\n";
+        let res = parse_ok(code);
+        let texts: Vec<_> = res
+            .string_concat_opportunities()
+            .into_iter()
+            .map(|loc| &code[loc.0..=loc.1])
+            .collect();
+        assert_eq!(vec!["\"e\"", "'b'"], texts);
+    }
+
+    #[test]
+    fn detect_rebinds() {
+        let code = r#"
+def f(x, y = 1, *r, k:, &b)
+  x = x + 1
+  y += 1
+  z = 1
+  [1].each { |x| x = 2; y = 3 }
+  def g(x)
+    x = 1
+  end
+end
+def h(a); b = a; end
+"#;
+        let res = parse_ok(code);
+        let rebinds: Vec<_> = res
+            .detect_rebinds()
+            .into_iter()
+            .map(|w| (w.method_name, w.param_name))
+            .collect();
+        let rebind = |m: &str, p: &str| (m.to_string(), p.to_string());
+        assert_eq!(
+            vec![
+                rebind("f", "x"),
+                rebind("f", "y"),
+                rebind("f", "y"),
+                rebind("g", "x")
+            ],
+            rebinds
+        );
+    }
+
+    #[test]
+    fn detect_long_parameter_lists() {
+        let code = r#"
+def f(a, b, c, d, e, &blk); end
+def self.g(a, b = 1, *c, d, e:, **f); end
+def h(a, b, c, d, e, ...); end
+"#;
+        let res = parse_ok(code);
+        let warnings: Vec<_> = res
+            .detect_long_parameter_lists(DEFAULT_MAX_PARAMS)
+            .into_iter()
+            .map(|w| (w.method_name, w.param_count))
+            .collect();
+        assert_eq!(vec![("g".to_string(), 6)], warnings);
+        assert_eq!(3, res.detect_long_parameter_lists(4).len());
+    }
+
+    #[test]
+    fn detect_missing_freeze() {
+        let code = r##"A = "a"
+B = "b".freeze
+h = { "k" => 1, k: "v" }
+def f
+  return "r" if x
+  "s"
+end
+def g
+  "#{x}"
+end
+"##;
+        let res = parse_ok(code);
+        let hints: Vec<_> = res
+            .detect_missing_freeze()
+            .into_iter()
+            .map(|hint| (&code[hint.loc.0..=hint.loc.1], hint.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                ("\"a\"", FreezeKind::ConstantValue),
+                ("\"k\"", FreezeKind::HashKey),
+                ("\"s\"", FreezeKind::ReturnedLiteral),
+                ("\"r\"", FreezeKind::ReturnedLiteral),
+            ],
+            hints
+        );
+        let code = format!("# frozen_string_literal: true\n{}", code);
+        let res = parse_ok(&code);
+        assert!(res.detect_missing_freeze().is_empty());
+    }
+
+    #[test]
+    fn detect_useless_conditions() {
+        let code = r#"
+if true then a end
+b unless nil
+while false do c end
+until 1 do d end
+if x then e end
+case 1
+when 2 then f
+when 1 then g
+when 1.0, x then h
+end
+"#;
+        let res = parse_ok(code);
+        let conditions: Vec<_> = res
+            .detect_useless_conditions()
+            .into_iter()
+            .map(|c| (&code[c.loc.0..=c.loc.1], c.always_taken))
+            .collect();
+        assert_eq!(
+            vec![
+                ("true", true),
+                ("nil", false),
+                ("false", false),
+                ("1", false),
+                ("2", false),
+                ("1", true),
+            ],
+            conditions
+        );
+    }
+
+    #[test]
+    fn check_syntax_only() {
+        assert!(warnings("while true do break end").is_empty());
+        assert!(warnings("[].each { break }").is_empty());
+        assert!(warnings("f { -> { return } }").is_empty());
+        assert!(warnings("begin; rescue Error; retry; end").is_empty());
+        assert_eq!(
+            vec![ParseWarnKind::BreakOutsideLoop],
+            warnings("def f; break; end")
+        );
+        assert_eq!(
+            vec![ParseWarnKind::ReturnInBlock],
+            warnings("def f; g { return 1 }; end")
+        );
+        assert_eq!(
+            vec![ParseWarnKind::DuplicateWhen],
+            warnings("case x\nwhen 1, 2 then a\nwhen 3, 1 then b\nend")
+        );
+        assert_eq!(
+            vec![ParseWarnKind::UnreachableCode],
+            warnings("def f\n  return 1\n  g\nend")
+        );
+        assert_eq!(
+            vec![ParseWarnKind::BareRescue],
+            warnings("begin\n  f\nrescue\n  retry\nend")
+        );
+    }
+}
@@ -43,10 +43,14 @@ impl From<u32> for LvarId {
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct LvarCollector {
     pub kw: Vec<LvarId>,
+    /// positional (required, optional, rest and post) parameters.
+    positional: Vec<LvarId>,
     pub table: LvarTable,
     kwrest: Option<LvarId>,
     block: Option<LvarId>,
     pub delegate_param: Option<LvarId>,
+    /// block-local variables. (`|a; x, y|`)
+    pub block_locals: Vec<LvarId>,
     pub numbered_param: Option<crate::Loc>,
     pub prohibit_numbered_param: Option<crate::Loc>,
 }
@@ -57,10 +61,12 @@ impl LvarCollector {
         table.push(id);
         Self {
             kw: vec![],
+            positional: vec![],
             table,
             kwrest: None,
             block: None,
             delegate_param: None,
+            block_locals: vec![],
             numbered_param: None,
             prohibit_numbered_param: None,
         }
@@ -72,10 +78,12 @@ impl LvarCollector {
     pub fn new() -> Self {
         LvarCollector {
             kw: vec![],
+            positional: vec![],
             table: LvarTable::new(),
             kwrest: None,
             block: None,
             delegate_param: None,
+            block_locals: vec![],
             numbered_param: None,
             prohibit_numbered_param: None,
         }
@@ -129,6 +137,37 @@ impl LvarCollector {
         Some(lvar)
     }
 
+    /// Register *lvar* as a positional parameter.
+    pub(crate) fn add_positional_param(&mut self, lvar: LvarId) {
+        self.positional.push(lvar);
+    }
+
+    fn name_str(&self, id: LvarId) -> &str {
+        &self.table.0[id.as_usize()]
+    }
+
+    /// Get names of the positional (required, optional, rest and post) parameters in order.
+    ///
+    /// Destructuring parameters like `(a, b)` are not included.
+    pub fn param_names(&self) -> impl Iterator<Item = &str> {
+        self.positional.iter().map(move |id| self.name_str(*id))
+    }
+
+    /// Get names of the keyword parameters in order.
+    pub fn kw_param_names(&self) -> impl Iterator<Item = &str> {
+        self.kw.iter().map(move |id| self.name_str(*id))
+    }
+
+    /// Get the name of the block parameter.
+    pub fn block_param_name(&self) -> Option<&str> {
+        self.block.map(|id| self.name_str(id))
+    }
+
+    /// Get names of the block-local variables in order.
+    pub fn block_local_names(&self) -> impl Iterator<Item = &str> {
+        self.block_locals.iter().map(move |id| self.name_str(*id))
+    }
+
     fn get_name_id(&self, id: LvarId) -> Option<String> {
         self.table.get(id.into())
     }
@@ -165,6 +204,31 @@ impl LvarCollector {
     pub fn block(&self) -> &Option<LvarId> {
         &self.block
     }
+
+    /// Rename the local variable *old* to *new*.
+    ///
+    /// Return false if *old* does not exist or *new* already exists.
+    /// `LvarId`s are not changed, so keyword, kwrest and block parameters remain valid.
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        if self.table.get_lvarid(new).is_some() {
+            return false;
+        }
+        match self.table.get_lvarid(old) {
+            Some(id) => {
+                self.table.0[id.as_usize()] = new.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Release the excess capacity of the tables. Useful after the parse is complete.
+    pub fn shrink_to_fit(&mut self) {
+        self.kw.shrink_to_fit();
+        self.positional.shrink_to_fit();
+        self.block_locals.shrink_to_fit();
+        self.table.shrink_to_fit();
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -186,4 +250,103 @@ impl LvarTable {
     fn get(&self, i: usize) -> Option<String> {
         self.0.get(i).cloned()
     }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Release the excess capacity of the table.
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::test::{parse_err, parse_ok};
+
+    #[test]
+    fn lvar_table_shrink() {
+        let mut lvar = LvarCollector::new();
+        lvar.table.0.reserve(1024);
+        for name in ["a", "b", "c"] {
+            lvar.insert(name);
+        }
+        assert!(lvar.table.capacity() >= 1024);
+        lvar.shrink_to_fit();
+        assert!(lvar.table.capacity() < 1024);
+        assert_eq!(vec!["a", "b", "c"], *lvar.table());
+    }
+
+    #[test]
+    fn param_names() {
+        let res = parse_ok("def f(a, b = 1, *c, d, (e, f), g:, h: 2, **i, &j); x = 1; end");
+        let lvar = match res.node.kind {
+            crate::NodeKind::MethodDef(_, info) => info.lvar,
+            _ => panic!(),
+        };
+        assert_eq!(
+            vec!["a", "b", "c", "d"],
+            lvar.param_names().collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["g", "h"], lvar.kw_param_names().collect::<Vec<_>>());
+        assert_eq!(Some("j"), lvar.block_param_name());
+        assert_eq!(None, LvarCollector::new().block_param_name());
+    }
+
+    #[test]
+    fn block_locals() {
+        fn block_lvar(code: &str) -> (LvarCollector, crate::Node) {
+            let res = parse_ok(code);
+            let mut nodes = match res.node.kind {
+                crate::NodeKind::CompStmt(nodes) => nodes,
+                _ => panic!(),
+            };
+            match nodes.pop().unwrap().kind {
+                crate::NodeKind::MethodCall { arglist, .. } => match arglist.block.unwrap().kind {
+                    crate::NodeKind::Lambda(info) => (info.lvar, *info.body),
+                    _ => panic!(),
+                },
+                _ => panic!(),
+            }
+        }
+        let (lvar, body) = block_lvar("x = 1; [1].each {|y; x| x = 2}");
+        assert_eq!(vec!["y"], lvar.param_names().collect::<Vec<_>>());
+        assert_eq!(vec!["x"], lvar.block_local_names().collect::<Vec<_>>());
+        match body.kind {
+            crate::NodeKind::MulAssign(lhs, _) => {
+                assert_eq!(crate::NodeKind::LocalVar(0, "x".to_string()), lhs[0].kind)
+            }
+            kind => panic!("{:?}", kind),
+        }
+        let (lvar, _) = block_lvar("a = 0; [1].each do |; b, c| end");
+        assert_eq!(0, lvar.param_names().count());
+        assert_eq!(vec!["b", "c"], lvar.block_local_names().collect::<Vec<_>>());
+        parse_err("[1].each {|y; y| }");
+    }
+
+    #[test]
+    fn rename() {
+        let mut lvar = LvarCollector::new();
+        let a = lvar.insert("a");
+        lvar.insert("b");
+        let k = lvar.insert_kwrest_param("k".to_string()).unwrap();
+        assert!(lvar.rename("a", "x"));
+        assert_eq!(Some(a), lvar.table.get_lvarid("x"));
+        assert_eq!(None, lvar.table.get_lvarid("a"));
+        assert!(!lvar.rename("a", "y"));
+        assert!(!lvar.rename("x", "b"));
+        assert!(lvar.rename("k", "opts"));
+        assert_eq!(Some(k), lvar.kwrest_param());
+        assert_eq!(Some(k), lvar.table.get_lvarid("opts"));
+    }
 }
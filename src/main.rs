@@ -2,7 +2,6 @@ extern crate clap;
 
 use clap::*;
 use std::fs::*;
-use std::io::Read;
 use std::path::Path;
 
 #[derive(Parser, Debug)]
@@ -48,19 +47,29 @@ fn main() {
         }
     };
 
-    let program = match load_file(&absolute_path) {
-        Ok(program) => program,
-        Err(err) => {
-            eprintln!("{}: {}.", file, err);
+    let reader = match File::open(&absolute_path) {
+        Ok(reader) => reader,
+        Err(ioerr) => {
+            eprintln!("{}: {}.", file, ioerr);
             return;
         }
     };
 
-    parse_and_output(program);
+    match ruruby_parse::Parser::parse_program_reader(reader, Path::new("")) {
+        Ok(res) => output(Ok(res)),
+        Err(err) => match err.downcast::<ruruby_parse::ParseErr>() {
+            Ok(err) => output(Err(*err)),
+            Err(err) => eprintln!("{}: {}.", file, err),
+        },
+    }
 }
 
 fn parse_and_output(program: String) {
-    match ruruby_parse::Parser::parse_program(program, Path::new("")) {
+    output(ruruby_parse::Parser::parse_program(program, Path::new("")))
+}
+
+fn output(res: Result<ruruby_parse::ParseResult, ruruby_parse::ParseErr>) {
+    match res {
         Ok(res) => {
             println!("{:#?}", res.node);
             println!("{}", res.source_info.get_location(&ruruby_parse::Loc(0, 0)));
@@ -69,18 +78,6 @@ fn parse_and_output(program: String) {
     };
 }
 
-fn load_file(path: &Path) -> Result<String, String> {
-    let mut file_body = String::new();
-    match OpenOptions::new().read(true).open(path) {
-        Ok(mut file) => match file.read_to_string(&mut file_body) {
-            Ok(_) => {}
-            Err(ioerr) => return Err(format!("{}", ioerr)),
-        },
-        Err(ioerr) => return Err(format!("{}", ioerr)),
-    };
-    Ok(file_body)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,5 +1,5 @@
 use super::*;
-use num::BigInt;
+use num::{BigInt, BigRational};
 
 pub type Node = Annot<NodeKind>;
 
@@ -10,24 +10,39 @@ pub enum NodeKind {
     Integer(i64),
     Bignum(BigInt),
     Float(f64),
+    Rational(BigRational),
     Imaginary(NReal),
     Bool(bool),
     String(String),
     Bytes(Vec<u8>),
+    /// `__FILE__`. The path of the source is available from `SourceInfo`.
+    FileLiteral,
+    /// `__LINE__` with its line number.
+    LineLiteral(usize),
+    /// `__method__`. The method name is resolved at runtime.
+    MethodLiteral,
+    /// `__dir__`. The directory is resolved from `SourceInfo`.
+    DirLiteral,
     InterporatedString(Vec<Node>),
     Command(Box<Node>),
     Symbol(String),
+    /// The omitted start or end of beginless / endless ranges is `Nil`.
     Range {
         start: Box<Node>,
         end: Box<Node>,
         exclude_end: bool,
         is_const: bool,
     }, // start, end, exclude_end
-    Array(Vec<Node>, bool),          // Vec<ELEM>, is_constant_expr
-    Hash(Vec<(Node, Node)>, bool),   // Vec<KEY, VALUE>, is_constant_expr
+    Array(Vec<Node>, bool), // Vec<ELEM>, is_constant_expr
+    /// Vec<KEY, VALUE>, is_constant_expr
+    ///
+    /// For the shorthand `{key:}`, VALUE is a `LocalVar` which has the same loc as KEY.
+    Hash(Vec<(Node, Node)>, bool),
     RegExp(Vec<Node>, String, bool), // Vec<STRING>, option, is_constant_expr
 
     LocalVar(usize, String),
+    /// `_1` .. `_9` in blocks.
+    NumberedParam(u8),
     Ident(String),
     InstanceVar(String),
     GlobalVar(String),
@@ -72,6 +87,62 @@ pub enum NodeKind {
         when_: Vec<CaseBranch>,
         else_: Box<Node>,
     },
+    /// `case subject in pattern .. end`
+    PatternMatch {
+        subject: Box<Node>,
+        arms: Vec<PatternArm>,
+    },
+    /// `[pat, *rest, pat]` in patterns.
+    ///
+    /// The element at *rest_index* is the binding of the rest, which is `LocalVar`
+    /// for `*rest` or `DiscardLhs` for `*`.
+    ArrayPattern {
+        elements: Vec<Node>,
+        rest_index: Option<usize>,
+    },
+    /// `pat => var` in patterns, which binds the matched value to *binding*.
+    CapturePattern {
+        pattern: Box<Node>,
+        binding: Box<Node>,
+    },
+    /// `expr => pattern`
+    RightwardAssign {
+        value: Box<Node>,
+        pattern: Box<Node>,
+    },
+    /// `^var` or `^(expr)` in patterns, which matches the value instead of binding.
+    PinOperator {
+        inner: Box<Node>,
+    },
+    /// `[*pre, pat, pat, *post]` in patterns.
+    ///
+    /// *pre* and *post* hold the binding of each rest, which is `LocalVar` or `DiscardLhs`.
+    FindPattern {
+        pre: Vec<Node>,
+        core: Vec<Node>,
+        post: Vec<Node>,
+    },
+    /// `{key: pat, key:, **rest}` in patterns.
+    ///
+    /// The value is None for `key:`, which binds the local variable *key*.
+    HashPattern {
+        pairs: Vec<(Node, Option<Node>)>,
+        /// `**rest`
+        rest: Option<Box<Node>>,
+        /// true for `**nil`, which prohibits other keys.
+        double_splat: bool,
+    },
+    /// `pat | pat` in patterns.
+    AlternativePattern {
+        patterns: Vec<Node>,
+    },
+    /// `Const(pat, ..)` or `Const[pat, ..]` in patterns.
+    ///
+    /// *pattern* is an array, find or hash pattern for the arguments.
+    ConstPattern {
+        constant: Box<Node>,
+        pattern: Box<Node>,
+    },
     Begin {
         body: Box<Node>,
         rescue: Vec<RescueEntry>, // (ex_class_list, ex_param)
@@ -82,6 +153,7 @@ pub enum NodeKind {
     Break(Box<Node>),
     Next(Box<Node>),
     Redo,
+    Retry,
     Return(Box<Node>),
     Yield(ArgList),
     MethodDef(String, BlockInfo),                     // id, params, body
@@ -108,11 +180,46 @@ pub enum NodeKind {
         arglist: ArgList,
         safe_nav: bool,
     },
+    /// `BEGIN { .. }`
+    BeginBlock {
+        body: Box<Node>,
+    },
+    /// `END { .. }`
+    ///
+    /// *body* is a block (NodeKind::Lambda).
+    EndBlock {
+        body: Box<Node>,
+    },
+    /// `throw tag [, value]`
+    Throw {
+        tag: Box<Node>,
+        value: Option<Box<Node>>,
+    },
+    /// `catch [(tag)] { .. }`
+    ///
+    /// *body* is the block (NodeKind::Lambda) passed to `catch`.
+    Catch {
+        tag: Option<Box<Node>>,
+        body: Box<Node>,
+    },
 
     Defined(Box<Node>),
     Super(Option<ArgList>),
     AliasMethod(Box<Node>, Box<Node>), // (new_method, old_method)
     DiscardLhs,
+    /// `private { .. }` (only with ParserOptions::allow_visibility_block)
+    VisibilityBlock {
+        visibility: Visibility,
+        body: Box<Node>,
+    },
+    /// `Proc.new` in a method body, which captures the block of the calling method
+    /// when *has_block* is false.
+    ProcNew {
+        has_block: bool,
+    },
+    /// A stub for a statement which failed to parse.
+    /// (only with `Parser::parse_program_collecting_errors()`)
+    Error,
 }
 
 impl std::default::Default for NodeKind {
@@ -121,12 +228,21 @@ impl std::default::Default for NodeKind {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct BlockInfo {
     pub params: Vec<FormalParam>,
     pub body: Box<Node>,
     pub lvar: LvarCollector,
     pub loc: Loc,
+    /// true for endless method definitions. (`def foo = expr`)
+    pub is_endless: bool,
 }
 
 impl BlockInfo {
@@ -136,7 +252,53 @@ impl BlockInfo {
             body: Box::new(body),
             lvar,
             loc,
+            is_endless: false,
+        }
+    }
+
+    /// Check whether the block has any formal parameters.
+    pub fn has_explicit_params(&self) -> bool {
+        !self.params.is_empty()
+    }
+
+    /// Get the number of formal parameters.
+    pub fn param_count(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Check whether the block takes no parameters at all, including numbered parameters.
+    pub fn is_thunk(&self) -> bool {
+        self.params.is_empty() && self.lvar.numbered_param.is_none()
+    }
+
+    /// Check whether the block declares block-local variables. (`|x; y|`)
+    pub fn has_block_local_vars(&self) -> bool {
+        !self.lvar.block_locals.is_empty()
+    }
+
+    /// Push default values of the parameters and the body.
+    fn push_children<'a>(&'a self, children: &mut Vec<&'a Node>) {
+        for param in &self.params {
+            match &param.kind {
+                ParamKind::Optional(_, default) | ParamKind::Keyword(_, Some(default)) => {
+                    children.push(default)
+                }
+                _ => {}
+            }
+        }
+        children.push(&self.body);
+    }
+
+    fn push_children_mut<'a>(&'a mut self, children: &mut Vec<&'a mut Node>) {
+        for param in &mut self.params {
+            match &mut param.kind {
+                ParamKind::Optional(_, default) | ParamKind::Keyword(_, Some(default)) => {
+                    children.push(default)
+                }
+                _ => {}
+            }
         }
+        children.push(&mut self.body);
     }
 }
 
@@ -185,6 +347,41 @@ impl FormalParam {
             .fold(params[0].1, |acc, elem| acc.merge(elem.1));
         FormalParam::new(ParamKind::Destruct(params), loc)
     }
+
+    /// Generate the Ruby signature of the parameter. (e.g. `"x = nil"`, `"*rest"`, `"key:"`)
+    ///
+    /// Default values other than simple literals and variables are shown as `...`.
+    pub fn to_ruby_sig(&self) -> String {
+        match &self.kind {
+            ParamKind::Param(name) | ParamKind::Post(name) => name.clone(),
+            ParamKind::Optional(name, default) => format!("{} = {}", name, default.to_ruby_expr()),
+            ParamKind::Rest(name) => format!("*{}", name.as_deref().unwrap_or_default()),
+            ParamKind::Keyword(name, None) => format!("{}:", name),
+            ParamKind::Keyword(name, Some(default)) => {
+                format!("{}: {}", name, default.to_ruby_expr())
+            }
+            ParamKind::KWRest(name) => format!("**{}", name),
+            ParamKind::Block(name) => format!("&{}", name),
+            ParamKind::Delegate => "...".to_string(),
+            ParamKind::Destruct(params) => {
+                let names: Vec<_> = params.iter().map(|(name, _)| name.as_str()).collect();
+                format!("({})", names.join(", "))
+            }
+        }
+    }
+}
+
+/// Extension trait for a list of formal parameters.
+pub trait FormalParamList {
+    /// Generate the Ruby signature of the parameter list. (e.g. `"(a, b = 1, &blk)"`)
+    fn to_ruby_sig_str(&self) -> String;
+}
+
+impl FormalParamList for [FormalParam] {
+    fn to_ruby_sig_str(&self) -> String {
+        let params: Vec<_> = self.iter().map(FormalParam::to_ruby_sig).collect();
+        format!("({})", params.join(", "))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -234,6 +431,15 @@ impl ArgList {
         }
     }
 
+    /// Check whether no argument (including a block) is given.
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+            && self.kw_args.is_empty()
+            && self.hash_splat.is_empty()
+            && self.block.is_none()
+            && !self.delegate
+    }
+
     pub fn from_args(args: Vec<Node>) -> Self {
         ArgList {
             args,
@@ -255,6 +461,20 @@ impl ArgList {
             splat: false,
         }
     }
+
+    fn push_children<'a>(&'a self, children: &mut Vec<&'a Node>) {
+        children.extend(&self.args);
+        children.extend(self.kw_args.iter().map(|(_, node)| node));
+        children.extend(&self.hash_splat);
+        children.extend(self.block.as_deref());
+    }
+
+    fn push_children_mut<'a>(&'a mut self, children: &mut Vec<&'a mut Node>) {
+        children.extend(&mut self.args);
+        children.extend(self.kw_args.iter_mut().map(|(_, node)| node));
+        children.extend(&mut self.hash_splat);
+        children.extend(self.block.as_deref_mut());
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -272,6 +492,26 @@ impl CaseBranch {
     }
 }
 
+/// An `in` clause of `case`/`in`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternArm {
+    /// None for the `else` clause, which is always the last arm.
+    pub pattern: Option<Box<Node>>,
+    /// `if` or `unless` guard. `unless` guard is negated with `UnOp::Not`.
+    pub guard: Option<Box<Node>>,
+    pub body: Box<Node>,
+}
+
+impl PatternArm {
+    pub(crate) fn new(pattern: Option<Node>, guard: Option<Node>, body: Node) -> Self {
+        PatternArm {
+            pattern: pattern.map(Box::new),
+            guard: guard.map(Box::new),
+            body: Box::new(body),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum CmpKind {
     Eq = 0,
@@ -334,6 +574,38 @@ pub enum BinOp {
     Match,
 }
 
+impl BinOp {
+    /// Get the operator string. (e.g. `"+"`, `"||"`)
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+            Self::Exp => "**",
+            Self::Shr => ">>",
+            Self::Shl => "<<",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::BitXor => "^",
+            Self::Cmp(kind) => match kind {
+                CmpKind::Eq => "==",
+                CmpKind::Ne => "!=",
+                CmpKind::Lt => "<",
+                CmpKind::Le => "<=",
+                CmpKind::Gt => ">",
+                CmpKind::Ge => ">=",
+                CmpKind::TEq => "===",
+                CmpKind::Cmp => "<=>",
+            },
+            Self::LAnd => "&&",
+            Self::LOr => "||",
+            Self::Match => "=~",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnOp {
     BitNot,
@@ -342,6 +614,124 @@ pub enum UnOp {
     Neg,
 }
 
+impl NodeKind {
+    /// Get the name of the variant.
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            NodeKind::SelfValue => "SelfValue",
+            NodeKind::Nil => "Nil",
+            NodeKind::Integer(..) => "Integer",
+            NodeKind::Bignum(..) => "Bignum",
+            NodeKind::Float(..) => "Float",
+            NodeKind::Rational(..) => "Rational",
+            NodeKind::Imaginary(..) => "Imaginary",
+            NodeKind::Bool(..) => "Bool",
+            NodeKind::String(..) => "String",
+            NodeKind::Bytes(..) => "Bytes",
+            NodeKind::FileLiteral => "FileLiteral",
+            NodeKind::LineLiteral(..) => "LineLiteral",
+            NodeKind::MethodLiteral => "MethodLiteral",
+            NodeKind::DirLiteral => "DirLiteral",
+            NodeKind::InterporatedString(..) => "InterporatedString",
+            NodeKind::Command(..) => "Command",
+            NodeKind::Symbol(..) => "Symbol",
+            NodeKind::Range { .. } => "Range",
+            NodeKind::Array(..) => "Array",
+            NodeKind::Hash(..) => "Hash",
+            NodeKind::RegExp(..) => "RegExp",
+            NodeKind::LocalVar(..) => "LocalVar",
+            NodeKind::NumberedParam(..) => "NumberedParam",
+            NodeKind::Ident(..) => "Ident",
+            NodeKind::InstanceVar(..) => "InstanceVar",
+            NodeKind::GlobalVar(..) => "GlobalVar",
+            NodeKind::SpecialVar(..) => "SpecialVar",
+            NodeKind::ClassVar(..) => "ClassVar",
+            NodeKind::Const { .. } => "Const",
+            NodeKind::BinOp(..) => "BinOp",
+            NodeKind::UnOp(..) => "UnOp",
+            NodeKind::Index { .. } => "Index",
+            NodeKind::Splat(..) => "Splat",
+            NodeKind::AssignOp(..) => "AssignOp",
+            NodeKind::MulAssign(..) => "MulAssign",
+            NodeKind::CompStmt(..) => "CompStmt",
+            NodeKind::If { .. } => "If",
+            NodeKind::For { .. } => "For",
+            NodeKind::While { .. } => "While",
+            NodeKind::Case { .. } => "Case",
+            NodeKind::PatternMatch { .. } => "PatternMatch",
+            NodeKind::ArrayPattern { .. } => "ArrayPattern",
+            NodeKind::FindPattern { .. } => "FindPattern",
+            NodeKind::PinOperator { .. } => "PinOperator",
+            NodeKind::CapturePattern { .. } => "CapturePattern",
+            NodeKind::RightwardAssign { .. } => "RightwardAssign",
+            NodeKind::HashPattern { .. } => "HashPattern",
+            NodeKind::AlternativePattern { .. } => "AlternativePattern",
+            NodeKind::ConstPattern { .. } => "ConstPattern",
+            NodeKind::Begin { .. } => "Begin",
+            NodeKind::Lambda(..) => "Lambda",
+            NodeKind::Break(..) => "Break",
+            NodeKind::Next(..) => "Next",
+            NodeKind::Redo => "Redo",
+            NodeKind::Retry => "Retry",
+            NodeKind::Return(..) => "Return",
+            NodeKind::Yield(..) => "Yield",
+            NodeKind::MethodDef(..) => "MethodDef",
+            NodeKind::SingletonMethodDef(..) => "SingletonMethodDef",
+            NodeKind::ClassDef { .. } => "ClassDef",
+            NodeKind::SingletonClassDef { .. } => "SingletonClassDef",
+            NodeKind::MethodCall { .. } => "MethodCall",
+            NodeKind::FuncCall { .. } => "FuncCall",
+            NodeKind::BeginBlock { .. } => "BeginBlock",
+            NodeKind::EndBlock { .. } => "EndBlock",
+            NodeKind::Throw { .. } => "Throw",
+            NodeKind::Catch { .. } => "Catch",
+            NodeKind::Defined(..) => "Defined",
+            NodeKind::Super(..) => "Super",
+            NodeKind::AliasMethod(..) => "AliasMethod",
+            NodeKind::DiscardLhs => "DiscardLhs",
+            NodeKind::VisibilityBlock { .. } => "VisibilityBlock",
+            NodeKind::ProcNew { .. } => "ProcNew",
+            NodeKind::Error => "Error",
+        }
+    }
+}
+
+impl NodeKind {
+    pub fn is_func_call(&self) -> bool {
+        matches!(self, NodeKind::FuncCall { .. })
+    }
+
+    pub fn is_method_call(&self) -> bool {
+        matches!(self, NodeKind::MethodCall { .. })
+    }
+
+    pub fn is_local_var(&self) -> bool {
+        matches!(self, NodeKind::LocalVar(..))
+    }
+
+    /// Get the value of a real number literal (`Integer`, `Bignum`, `Float` or `Rational`).
+    pub fn as_real(&self) -> Option<NReal> {
+        match self {
+            NodeKind::Integer(i) => Some(NReal::Integer(*i)),
+            NodeKind::Bignum(b) => Some(NReal::Bignum(b.clone())),
+            NodeKind::Float(f) => Some(NReal::Float(*f)),
+            NodeKind::Rational(r) => Some(NReal::Rational(r.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl From<NReal> for NodeKind {
+    fn from(num: NReal) -> Self {
+        match num {
+            NReal::Integer(i) => NodeKind::Integer(i),
+            NReal::Bignum(b) => NodeKind::Bignum(b),
+            NReal::Float(f) => NodeKind::Float(f),
+            NReal::Rational(r) => NodeKind::Rational(r),
+        }
+    }
+}
+
 impl Node {
     pub fn is_empty(&self) -> bool {
         match &self.kind {
@@ -350,6 +740,486 @@ impl Node {
         }
     }
 
+    /// Render simple literals and variables as Ruby code, otherwise `...`.
+    fn to_ruby_expr(&self) -> String {
+        match &self.kind {
+            NodeKind::Nil => "nil".to_string(),
+            NodeKind::SelfValue => "self".to_string(),
+            NodeKind::FileLiteral => "__FILE__".to_string(),
+            NodeKind::LineLiteral(_) => "__LINE__".to_string(),
+            NodeKind::MethodLiteral => "__method__".to_string(),
+            NodeKind::DirLiteral => "__dir__".to_string(),
+            NodeKind::Bool(b) => b.to_string(),
+            NodeKind::Integer(i) => i.to_string(),
+            NodeKind::Bignum(b) => b.to_string(),
+            NodeKind::Float(f) => format!("{:?}", f),
+            NodeKind::String(s) => format!("{:?}", s),
+            NodeKind::Symbol(s) => format!(":{}", s),
+            NodeKind::Array(elems, _) if elems.is_empty() => "[]".to_string(),
+            NodeKind::Hash(pairs, _) if pairs.is_empty() => "{}".to_string(),
+            NodeKind::LocalVar(_, name)
+            | NodeKind::Ident(name)
+            | NodeKind::InstanceVar(name)
+            | NodeKind::GlobalVar(name)
+            | NodeKind::ClassVar(name) => name.clone(),
+            NodeKind::NumberedParam(i) => format!("_{}", i),
+            NodeKind::Const {
+                toplevel,
+                parent: None,
+                prefix,
+                name,
+            } => {
+                let mut path = if *toplevel { "::" } else { "" }.to_string();
+                for p in prefix {
+                    path += p;
+                    path += "::";
+                }
+                path + name
+            }
+            NodeKind::UnOp(UnOp::Neg, node) => format!("-{}", node.to_ruby_expr()),
+            _ => "...".to_string(),
+        }
+    }
+
+    /// Iterate over `self` and all of its descendants in pre-order (parents before children).
+    pub fn walk(&self) -> NodeWalker<'_> {
+        NodeWalker { stack: vec![self] }
+    }
+
+    /// Iterate over `self` and all of its descendants in post-order (children before parents).
+    pub fn walk_post(&self) -> PostOrderNodeWalker<'_> {
+        PostOrderNodeWalker {
+            stack: vec![(self, false)],
+        }
+    }
+
+    /// Collect `self` and its descendants whose kind satisfies *pred* in pre-order.
+    pub fn find_all_by_kind<F: Fn(&NodeKind) -> bool>(&self, pred: F) -> Vec<&Node> {
+        self.walk().filter(|node| pred(&node.kind)).collect()
+    }
+
+    /// Collect all receiver-less method calls (`FuncCall`) in pre-order.
+    pub fn find_func_calls(&self) -> Vec<&Node> {
+        self.find_all_by_kind(NodeKind::is_func_call)
+    }
+
+    /// Collect all method calls with receivers (`MethodCall`) in pre-order.
+    pub fn find_method_calls(&self) -> Vec<&Node> {
+        self.find_all_by_kind(NodeKind::is_method_call)
+    }
+
+    /// Collect all local variable references (`LocalVar`) in pre-order.
+    pub fn find_local_vars(&self) -> Vec<&Node> {
+        self.find_all_by_kind(NodeKind::is_local_var)
+    }
+
+    /// Collect the direct children of `self` in source order.
+    pub fn children(&self) -> Vec<&Node> {
+        let mut children = vec![];
+        match &self.kind {
+            NodeKind::InterporatedString(nodes)
+            | NodeKind::Array(nodes, _)
+            | NodeKind::RegExp(nodes, ..)
+            | NodeKind::CompStmt(nodes)
+            | NodeKind::ArrayPattern {
+                elements: nodes, ..
+            }
+            | NodeKind::AlternativePattern { patterns: nodes } => children.extend(nodes),
+            NodeKind::Command(node)
+            | NodeKind::UnOp(_, node)
+            | NodeKind::Splat(node)
+            | NodeKind::Break(node)
+            | NodeKind::Next(node)
+            | NodeKind::Return(node)
+            | NodeKind::Defined(node)
+            | NodeKind::PinOperator { inner: node }
+            | NodeKind::VisibilityBlock { body: node, .. } => children.push(node.as_ref()),
+            NodeKind::Range { start, end, .. } => {
+                children.push(start.as_ref());
+                children.push(end.as_ref());
+            }
+            NodeKind::Hash(key_value, _) => {
+                for (k, v) in key_value {
+                    children.push(k);
+                    children.push(v);
+                }
+            }
+            NodeKind::FindPattern { pre, core, post } => {
+                children.extend(pre);
+                children.extend(core);
+                children.extend(post);
+            }
+            NodeKind::HashPattern { pairs, rest, .. } => {
+                for (k, v) in pairs {
+                    children.push(k);
+                    children.extend(v);
+                }
+                children.extend(rest.as_deref());
+            }
+            NodeKind::Const { parent, .. } => children.extend(parent.as_deref()),
+            NodeKind::BinOp(_, lhs, rhs)
+            | NodeKind::AssignOp(_, lhs, rhs)
+            | NodeKind::AliasMethod(lhs, rhs)
+            | NodeKind::CapturePattern {
+                pattern: lhs,
+                binding: rhs,
+            }
+            | NodeKind::ConstPattern {
+                constant: lhs,
+                pattern: rhs,
+            }
+            | NodeKind::RightwardAssign {
+                value: lhs,
+                pattern: rhs,
+            } => {
+                children.push(lhs.as_ref());
+                children.push(rhs.as_ref());
+            }
+            NodeKind::Index { base, index } => {
+                children.push(base.as_ref());
+                children.extend(index);
+            }
+            NodeKind::MulAssign(mlhs, mrhs) => {
+                children.extend(mlhs);
+                children.extend(mrhs);
+            }
+            NodeKind::If { cond, then_, else_ } => {
+                children.push(cond.as_ref());
+                children.push(then_.as_ref());
+                children.push(else_.as_ref());
+            }
+            NodeKind::For { iter, body, .. } => {
+                children.push(iter.as_ref());
+                body.push_children(&mut children);
+            }
+            NodeKind::While {
+                cond,
+                body,
+                postfix,
+                ..
+            } => {
+                if *postfix {
+                    children.push(body.as_ref());
+                    children.push(cond.as_ref());
+                } else {
+                    children.push(cond.as_ref());
+                    children.push(body.as_ref());
+                }
+            }
+            NodeKind::Case { cond, when_, else_ } => {
+                children.extend(cond.as_deref());
+                for branch in when_ {
+                    children.extend(&branch.when);
+                    children.push(branch.body.as_ref());
+                }
+                children.push(else_.as_ref());
+            }
+            NodeKind::PatternMatch { subject, arms } => {
+                children.push(subject.as_ref());
+                for arm in arms {
+                    children.extend(arm.pattern.as_deref());
+                    children.extend(arm.guard.as_deref());
+                    children.push(arm.body.as_ref());
+                }
+            }
+            NodeKind::Begin {
+                body,
+                rescue,
+                else_,
+                ensure,
+            } => {
+                children.push(body.as_ref());
+                for entry in rescue {
+                    children.extend(&entry.exception_list);
+                    children.extend(entry.assign.as_deref());
+                    children.push(entry.body.as_ref());
+                }
+                children.extend(else_.as_deref());
+                children.extend(ensure.as_deref());
+            }
+            NodeKind::Lambda(info) | NodeKind::MethodDef(_, info) => {
+                info.push_children(&mut children)
+            }
+            NodeKind::SingletonMethodDef(singleton, _, info)
+            | NodeKind::SingletonClassDef { singleton, info } => {
+                children.push(singleton.as_ref());
+                info.push_children(&mut children);
+            }
+            NodeKind::ClassDef {
+                base,
+                superclass,
+                info,
+                ..
+            } => {
+                children.extend(base.as_deref());
+                children.extend(superclass.as_deref());
+                info.push_children(&mut children);
+            }
+            NodeKind::MethodCall {
+                receiver, arglist, ..
+            } => {
+                children.push(receiver.as_ref());
+                arglist.push_children(&mut children);
+            }
+            NodeKind::FuncCall { arglist, .. }
+            | NodeKind::Yield(arglist)
+            | NodeKind::Super(Some(arglist)) => arglist.push_children(&mut children),
+            NodeKind::BeginBlock { body } | NodeKind::EndBlock { body } => {
+                children.push(body.as_ref())
+            }
+            NodeKind::Throw { tag, value } => {
+                children.push(tag.as_ref());
+                children.extend(value.as_deref());
+            }
+            NodeKind::Catch { tag, body } => {
+                children.extend(tag.as_deref());
+                children.push(body.as_ref());
+            }
+            NodeKind::SelfValue
+            | NodeKind::Nil
+            | NodeKind::Integer(_)
+            | NodeKind::Bignum(_)
+            | NodeKind::Float(_)
+            | NodeKind::Rational(_)
+            | NodeKind::Imaginary(_)
+            | NodeKind::Bool(_)
+            | NodeKind::String(_)
+            | NodeKind::Bytes(_)
+            | NodeKind::FileLiteral
+            | NodeKind::LineLiteral(_)
+            | NodeKind::MethodLiteral
+            | NodeKind::DirLiteral
+            | NodeKind::Symbol(_)
+            | NodeKind::LocalVar(..)
+            | NodeKind::NumberedParam(_)
+            | NodeKind::Ident(_)
+            | NodeKind::InstanceVar(_)
+            | NodeKind::GlobalVar(_)
+            | NodeKind::SpecialVar(_)
+            | NodeKind::ClassVar(_)
+            | NodeKind::Redo
+            | NodeKind::Retry
+            | NodeKind::Super(None)
+            | NodeKind::DiscardLhs
+            | NodeKind::Error
+            | NodeKind::ProcNew { .. } => {}
+        }
+        children
+    }
+
+    /// Collect mutable references to the direct children of `self` in source order.
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        let mut children = vec![];
+        match &mut self.kind {
+            NodeKind::InterporatedString(nodes)
+            | NodeKind::Array(nodes, _)
+            | NodeKind::RegExp(nodes, ..)
+            | NodeKind::CompStmt(nodes)
+            | NodeKind::ArrayPattern {
+                elements: nodes, ..
+            }
+            | NodeKind::AlternativePattern { patterns: nodes } => children.extend(nodes),
+            NodeKind::Command(node)
+            | NodeKind::UnOp(_, node)
+            | NodeKind::Splat(node)
+            | NodeKind::Break(node)
+            | NodeKind::Next(node)
+            | NodeKind::Return(node)
+            | NodeKind::Defined(node)
+            | NodeKind::PinOperator { inner: node }
+            | NodeKind::VisibilityBlock { body: node, .. } => children.push(node.as_mut()),
+            NodeKind::Range { start, end, .. } => {
+                children.push(start.as_mut());
+                children.push(end.as_mut());
+            }
+            NodeKind::Hash(key_value, _) => {
+                for (k, v) in key_value.iter_mut() {
+                    children.push(k);
+                    children.push(v);
+                }
+            }
+            NodeKind::FindPattern { pre, core, post } => {
+                children.extend(pre);
+                children.extend(core);
+                children.extend(post);
+            }
+            NodeKind::HashPattern { pairs, rest, .. } => {
+                for (k, v) in pairs.iter_mut() {
+                    children.push(k);
+                    children.extend(v);
+                }
+                children.extend(rest.as_deref_mut());
+            }
+            NodeKind::Const { parent, .. } => children.extend(parent.as_deref_mut()),
+            NodeKind::BinOp(_, lhs, rhs)
+            | NodeKind::AssignOp(_, lhs, rhs)
+            | NodeKind::AliasMethod(lhs, rhs)
+            | NodeKind::CapturePattern {
+                pattern: lhs,
+                binding: rhs,
+            }
+            | NodeKind::ConstPattern {
+                constant: lhs,
+                pattern: rhs,
+            }
+            | NodeKind::RightwardAssign {
+                value: lhs,
+                pattern: rhs,
+            } => {
+                children.push(lhs.as_mut());
+                children.push(rhs.as_mut());
+            }
+            NodeKind::Index { base, index } => {
+                children.push(base.as_mut());
+                children.extend(index);
+            }
+            NodeKind::MulAssign(mlhs, mrhs) => {
+                children.extend(mlhs);
+                children.extend(mrhs);
+            }
+            NodeKind::If { cond, then_, else_ } => {
+                children.push(cond.as_mut());
+                children.push(then_.as_mut());
+                children.push(else_.as_mut());
+            }
+            NodeKind::For { iter, body, .. } => {
+                children.push(iter.as_mut());
+                body.push_children_mut(&mut children);
+            }
+            NodeKind::While {
+                cond,
+                body,
+                postfix,
+                ..
+            } => {
+                if *postfix {
+                    children.push(body.as_mut());
+                    children.push(cond.as_mut());
+                } else {
+                    children.push(cond.as_mut());
+                    children.push(body.as_mut());
+                }
+            }
+            NodeKind::Case { cond, when_, else_ } => {
+                children.extend(cond.as_deref_mut());
+                for branch in when_ {
+                    children.extend(&mut branch.when);
+                    children.push(branch.body.as_mut());
+                }
+                children.push(else_.as_mut());
+            }
+            NodeKind::PatternMatch { subject, arms } => {
+                children.push(subject.as_mut());
+                for arm in arms {
+                    children.extend(arm.pattern.as_deref_mut());
+                    children.extend(arm.guard.as_deref_mut());
+                    children.push(arm.body.as_mut());
+                }
+            }
+            NodeKind::Begin {
+                body,
+                rescue,
+                else_,
+                ensure,
+            } => {
+                children.push(body.as_mut());
+                for entry in rescue {
+                    children.extend(&mut entry.exception_list);
+                    children.extend(entry.assign.as_deref_mut());
+                    children.push(entry.body.as_mut());
+                }
+                children.extend(else_.as_deref_mut());
+                children.extend(ensure.as_deref_mut());
+            }
+            NodeKind::Lambda(info) | NodeKind::MethodDef(_, info) => {
+                info.push_children_mut(&mut children)
+            }
+            NodeKind::SingletonMethodDef(singleton, _, info)
+            | NodeKind::SingletonClassDef { singleton, info } => {
+                children.push(singleton.as_mut());
+                info.push_children_mut(&mut children);
+            }
+            NodeKind::ClassDef {
+                base,
+                superclass,
+                info,
+                ..
+            } => {
+                children.extend(base.as_deref_mut());
+                children.extend(superclass.as_deref_mut());
+                info.push_children_mut(&mut children);
+            }
+            NodeKind::MethodCall {
+                receiver, arglist, ..
+            } => {
+                children.push(receiver.as_mut());
+                arglist.push_children_mut(&mut children);
+            }
+            NodeKind::FuncCall { arglist, .. }
+            | NodeKind::Yield(arglist)
+            | NodeKind::Super(Some(arglist)) => arglist.push_children_mut(&mut children),
+            NodeKind::BeginBlock { body } | NodeKind::EndBlock { body } => {
+                children.push(body.as_mut())
+            }
+            NodeKind::Throw { tag, value } => {
+                children.push(tag.as_mut());
+                children.extend(value.as_deref_mut());
+            }
+            NodeKind::Catch { tag, body } => {
+                children.extend(tag.as_deref_mut());
+                children.push(body.as_mut());
+            }
+            NodeKind::SelfValue
+            | NodeKind::Nil
+            | NodeKind::Integer(_)
+            | NodeKind::Bignum(_)
+            | NodeKind::Float(_)
+            | NodeKind::Rational(_)
+            | NodeKind::Imaginary(_)
+            | NodeKind::Bool(_)
+            | NodeKind::String(_)
+            | NodeKind::Bytes(_)
+            | NodeKind::FileLiteral
+            | NodeKind::LineLiteral(_)
+            | NodeKind::MethodLiteral
+            | NodeKind::DirLiteral
+            | NodeKind::Symbol(_)
+            | NodeKind::LocalVar(..)
+            | NodeKind::NumberedParam(_)
+            | NodeKind::Ident(_)
+            | NodeKind::InstanceVar(_)
+            | NodeKind::GlobalVar(_)
+            | NodeKind::SpecialVar(_)
+            | NodeKind::ClassVar(_)
+            | NodeKind::Redo
+            | NodeKind::Retry
+            | NodeKind::Super(None)
+            | NodeKind::DiscardLhs
+            | NodeKind::Error
+            | NodeKind::ProcNew { .. } => {}
+        }
+        children
+    }
+
+    /// Replace the first descendant whose location is *target_loc* with *replacement*.
+    ///
+    /// Return true if the replacement was made.
+    pub fn replace_child(&mut self, target_loc: Loc, replacement: Node) -> bool {
+        fn replace(node: &mut Node, target_loc: Loc, replacement: &mut Option<Node>) -> bool {
+            for child in node.children_mut() {
+                if child.loc == target_loc {
+                    *child = replacement.take().unwrap();
+                    return true;
+                }
+                if replace(child, target_loc, replacement) {
+                    return true;
+                }
+            }
+            false
+        }
+        replace(self, target_loc, &mut Some(replacement))
+    }
+
     pub(crate) fn is_integer(&self) -> bool {
         matches!(&self.kind, NodeKind::Integer(_) | NodeKind::Bignum(_))
     }
@@ -388,6 +1258,10 @@ impl Node {
         Node::new(NodeKind::Float(num), loc)
     }
 
+    pub(crate) fn new_rational(num: BigRational, loc: Loc) -> Self {
+        Node::new(NodeKind::Rational(num), loc)
+    }
+
     pub(crate) fn new_imaginary(num: NReal, loc: Loc) -> Self {
         Node::new(NodeKind::Imaginary(num), loc)
     }
@@ -438,6 +1312,22 @@ impl Node {
         Node::new(NodeKind::SelfValue, loc)
     }
 
+    pub(crate) fn new_file_literal(loc: Loc) -> Self {
+        Node::new(NodeKind::FileLiteral, loc)
+    }
+
+    pub(crate) fn new_line_literal(line: usize, loc: Loc) -> Self {
+        Node::new(NodeKind::LineLiteral(line), loc)
+    }
+
+    pub(crate) fn new_method_literal(loc: Loc) -> Self {
+        Node::new(NodeKind::MethodLiteral, loc)
+    }
+
+    pub(crate) fn new_dir_literal(loc: Loc) -> Self {
+        Node::new(NodeKind::DirLiteral, loc)
+    }
+
     pub(crate) fn new_interporated_string(nodes: Vec<Node>, loc: Loc) -> Self {
         let nodes = nodes
             .into_iter()
@@ -463,10 +1353,28 @@ impl Node {
         Node::new(NodeKind::AliasMethod(Box::new(new), Box::new(old)), loc)
     }
 
+    pub(crate) fn new_visibility_block(visibility: Visibility, body: Node, loc: Loc) -> Self {
+        Node::new(
+            NodeKind::VisibilityBlock {
+                visibility,
+                body: Box::new(body),
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_proc_new(has_block: bool, loc: Loc) -> Self {
+        Node::new(NodeKind::ProcNew { has_block }, loc)
+    }
+
     pub(crate) fn new_discard(loc: Loc) -> Self {
         Node::new(NodeKind::DiscardLhs, loc)
     }
 
+    pub(crate) fn new_error(loc: Loc) -> Self {
+        Node::new(NodeKind::Error, loc)
+    }
+
     pub(crate) fn new_comp_stmt(mut nodes: Vec<Node>, mut loc: Loc) -> Self {
         if nodes.len() == 1 {
             return nodes.remove(0);
@@ -509,6 +1417,10 @@ impl Node {
         Node::new(NodeKind::LocalVar(outer, name), loc)
     }
 
+    pub(crate) fn new_numbered_param(i: u8, loc: Loc) -> Self {
+        Node::new(NodeKind::NumberedParam(i), loc)
+    }
+
     pub(crate) fn new_identifier(name: String, loc: Loc) -> Self {
         Node::new(NodeKind::Ident(name), loc)
     }
@@ -577,9 +1489,11 @@ impl Node {
         params: Vec<FormalParam>,
         body: Node,
         lvar: LvarCollector,
+        is_endless: bool,
         loc: Loc,
     ) -> Self {
-        let info = BlockInfo::new(params, body, lvar, loc);
+        let mut info = BlockInfo::new(params, body, lvar, loc);
+        info.is_endless = is_endless;
         Node::new(NodeKind::MethodDef(name, info), loc)
     }
 
@@ -589,9 +1503,11 @@ impl Node {
         params: Vec<FormalParam>,
         body: Node,
         lvar: LvarCollector,
+        is_endless: bool,
         loc: Loc,
     ) -> Self {
-        let info = BlockInfo::new(params, body, lvar, loc);
+        let mut info = BlockInfo::new(params, body, lvar, loc);
+        info.is_endless = is_endless;
         Node::new(
             NodeKind::SingletonMethodDef(Box::new(singleton), name, info),
             loc,
@@ -672,7 +1588,29 @@ impl Node {
         )
     }
 
+    /// Create a function call node.
+    ///
+    /// `throw tag [, value]` and `catch [(tag)] { .. }` are converted to
+    /// NodeKind::Throw and NodeKind::Catch.
     pub(crate) fn new_fcall(method: String, arglist: ArgList, safe_nav: bool, loc: Loc) -> Self {
+        let simple = arglist.kw_args.is_empty()
+            && arglist.hash_splat.is_empty()
+            && !arglist.delegate
+            && !arglist.splat;
+        match method.as_str() {
+            "throw" if simple && arglist.block.is_none() && matches!(arglist.args.len(), 1 | 2) => {
+                let mut args = arglist.args.into_iter();
+                let tag = Box::new(args.next().unwrap());
+                let value = args.next().map(Box::new);
+                return Node::new(NodeKind::Throw { tag, value }, loc);
+            }
+            "catch" if simple && arglist.block.is_some() && arglist.args.len() <= 1 => {
+                let tag = arglist.args.into_iter().next().map(Box::new);
+                let body = arglist.block.unwrap();
+                return Node::new(NodeKind::Catch { tag, body }, loc);
+            }
+            _ => {}
+        }
         Node::new(
             NodeKind::FuncCall {
                 method,
@@ -695,6 +1633,24 @@ impl Node {
         )
     }
 
+    pub(crate) fn new_begin_block(body: Node, loc: Loc) -> Self {
+        Node::new(
+            NodeKind::BeginBlock {
+                body: Box::new(body),
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_end_block(body: Node, loc: Loc) -> Self {
+        Node::new(
+            NodeKind::EndBlock {
+                body: Box::new(body),
+            },
+            loc,
+        )
+    }
+
     pub(crate) fn new_if(cond: Node, then_: Node, else_: Node, loc: Loc) -> Self {
         let loc = loc.merge(then_.loc()).merge(else_.loc());
         Node::new(
@@ -750,6 +1706,105 @@ impl Node {
         )
     }
 
+    pub(crate) fn new_pattern_match(subject: Node, arms: Vec<PatternArm>, loc: Loc) -> Self {
+        Node::new(
+            NodeKind::PatternMatch {
+                subject: Box::new(subject),
+                arms,
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_array_pattern(
+        elements: Vec<Node>,
+        rest_index: Option<usize>,
+        loc: Loc,
+    ) -> Self {
+        Node::new(
+            NodeKind::ArrayPattern {
+                elements,
+                rest_index,
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_capture_pattern(pattern: Node, binding: Node) -> Self {
+        let loc = pattern.loc().merge(binding.loc());
+        Node::new(
+            NodeKind::CapturePattern {
+                pattern: Box::new(pattern),
+                binding: Box::new(binding),
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_rightward_assign(value: Node, pattern: Node) -> Self {
+        let loc = value.loc().merge(pattern.loc());
+        Node::new(
+            NodeKind::RightwardAssign {
+                value: Box::new(value),
+                pattern: Box::new(pattern),
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_pin_operator(inner: Node, loc: Loc) -> Self {
+        let loc = loc.merge(inner.loc());
+        Node::new(
+            NodeKind::PinOperator {
+                inner: Box::new(inner),
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_find_pattern(pre: Node, core: Vec<Node>, post: Node, loc: Loc) -> Self {
+        Node::new(
+            NodeKind::FindPattern {
+                pre: vec![pre],
+                core,
+                post: vec![post],
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_hash_pattern(
+        pairs: Vec<(Node, Option<Node>)>,
+        rest: Option<Node>,
+        double_splat: bool,
+        loc: Loc,
+    ) -> Self {
+        Node::new(
+            NodeKind::HashPattern {
+                pairs,
+                rest: rest.map(Box::new),
+                double_splat,
+            },
+            loc,
+        )
+    }
+
+    pub(crate) fn new_alternative_pattern(patterns: Vec<Node>) -> Self {
+        let loc = patterns[0].loc().merge(patterns[patterns.len() - 1].loc());
+        Node::new(NodeKind::AlternativePattern { patterns }, loc)
+    }
+
+    pub(crate) fn new_const_pattern(constant: Node, pattern: Node) -> Self {
+        let loc = constant.loc().merge(pattern.loc());
+        Node::new(
+            NodeKind::ConstPattern {
+                constant: Box::new(constant),
+                pattern: Box::new(pattern),
+            },
+            loc,
+        )
+    }
+
     pub(crate) fn new_begin(
         body: Node,
         rescue: Vec<RescueEntry>, //Vec<(Vec<Node>, Box<Node>)>,
@@ -794,6 +1849,10 @@ impl Node {
         Node::new(NodeKind::Redo, loc)
     }
 
+    pub(crate) fn new_retry(loc: Loc) -> Self {
+        Node::new(NodeKind::Retry, loc)
+    }
+
     pub(crate) fn new_return(val: Node, loc: Loc) -> Self {
         Node::new(NodeKind::Return(Box::new(val)), loc)
     }
@@ -855,3 +1914,46 @@ impl Node {
         }
     }
 }
+
+/// Pre-order iterator over a tree of nodes. See `Node::walk()`.
+#[derive(Debug, Clone)]
+pub struct NodeWalker<'a> {
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for NodeWalker<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children().into_iter().rev());
+        Some(node)
+    }
+}
+
+/// Post-order iterator over a tree of nodes. See `Node::walk_post()`.
+#[derive(Debug, Clone)]
+pub struct PostOrderNodeWalker<'a> {
+    /// (node, whether the children of node are already pushed)
+    stack: Vec<(&'a Node, bool)>,
+}
+
+impl<'a> Iterator for PostOrderNodeWalker<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(node);
+            }
+            self.stack.push((node, true));
+            self.stack.extend(
+                node.children()
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, false)),
+            );
+        }
+    }
+}
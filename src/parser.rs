@@ -1,5 +1,5 @@
 use super::*;
-use num::BigInt;
+use num::{BigInt, BigRational, ToPrimitive};
 use std::path::PathBuf;
 
 mod define;
@@ -7,7 +7,9 @@ mod expression;
 mod flow_control;
 mod lexer;
 mod literals;
+mod patterns;
 pub(crate) use lexer::*;
+pub use lexer::{Lexer, LexerErr, LexerMode};
 
 /// $&
 pub const SPECIAL_LASTMATCH: u32 = 0;
@@ -17,6 +19,8 @@ pub const SPECIAL_POSTMATCH: u32 = 1;
 pub const SPECIAL_LOADPATH: u32 = 10;
 /// $LOADED_FEATURES
 pub const SPECIAL_LOADEDFEATURES: u32 = 11;
+/// DATA
+pub const SPECIAL_DATA: u32 = 20;
 
 pub trait LocalsContext: Sized {
     fn find_lvar(&self, id: &str) -> Option<usize>;
@@ -31,6 +35,16 @@ impl LocalsContext for DummyContext {
     }
 }
 
+/// Options to customize the behavior of the parser.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ParserOptions {
+    /// parse `private { .. }` and `protected { .. }` as NodeKind::VisibilityBlock.
+    pub allow_visibility_block: bool,
+    /// the maximum size of the code in bytes for `Parser::parse_program_reader()`.
+    /// No limit if None.
+    pub max_file_size: Option<usize>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Parser<'a, OuterContext: LocalsContext> {
     lexer: Lexer<'a>,
@@ -40,6 +54,8 @@ pub struct Parser<'a, OuterContext: LocalsContext> {
     scope: Vec<LvarScope>,
     /// loop stack.
     loop_stack: Vec<LoopKind>,
+    /// nesting depth of rescue clauses in the current method or class body.
+    rescue_depth: usize,
     extern_context: Option<&'a OuterContext>,
     /// this flag suppress accesory assignment. e.g. x=3
     suppress_acc_assign: bool,
@@ -49,13 +65,102 @@ pub struct Parser<'a, OuterContext: LocalsContext> {
     suppress_do_block: bool,
     /// defined? mode: allow invalid break/next.
     defined_mode: bool,
+    options: ParserOptions,
+    /// here documents found in the code.
+    heredocs: Vec<HeredocInfo>,
+    /// syntax errors recovered so far. None if the parser does not recover from errors.
+    errors: Option<Vec<LexerErr>>,
+    warnings: Vec<ParseWarning>,
 }
 
 impl<'a> Parser<'a, DummyContext> {
     pub fn parse_program(code: String, path: impl Into<PathBuf>) -> Result<ParseResult, ParseErr> {
+        Self::parse_program_with_options(code, path, ParserOptions::default())
+    }
+
+    pub fn parse_program_with_options(
+        code: String,
+        path: impl Into<PathBuf>,
+        options: ParserOptions,
+    ) -> Result<ParseResult, ParseErr> {
         let path = path.into();
         let parse_ctx = LvarScope::new_eval(None);
-        parse(code, path, None::<&DummyContext>, parse_ctx)
+        parse(code, path, None::<&DummyContext>, parse_ctx, options)
+    }
+
+    /// Read the code from *reader* and parse it.
+    pub fn parse_program_reader<R: std::io::Read>(
+        reader: R,
+        path: impl Into<PathBuf>,
+    ) -> Result<ParseResult, Box<dyn std::error::Error>> {
+        Self::parse_program_reader_with_options(reader, path, ParserOptions::default())
+    }
+
+    /// Read the code from *reader* and parse it.
+    ///
+    /// Return `ParseErrKind::FileTooLarge` if the code exceeds `ParserOptions::max_file_size`.
+    pub fn parse_program_reader_with_options<R: std::io::Read>(
+        reader: R,
+        path: impl Into<PathBuf>,
+        options: ParserOptions,
+    ) -> Result<ParseResult, Box<dyn std::error::Error>> {
+        use std::io::Read;
+        let path = path.into();
+        let mut buf = vec![];
+        match options.max_file_size {
+            Some(max) => {
+                reader.take(max as u64 + 1).read_to_end(&mut buf)?;
+                if buf.len() > max {
+                    return Err(Box::new(ParseErr {
+                        kind: ParseErrKind::FileTooLarge(max),
+                        loc: Loc(0, 0),
+                        source_info: SourceInfoRef::new(SourceInfo::new(path, "")),
+                    }));
+                }
+            }
+            None => {
+                let mut reader = reader;
+                reader.read_to_end(&mut buf)?;
+            }
+        }
+        let code = String::from_utf8(buf)?;
+        Ok(Self::parse_program_with_options(code, path, options)?)
+    }
+
+    /// Parse the code, and collect all syntax errors instead of stopping at the first one.
+    ///
+    /// On a syntax error, the parser skips to the end of the statement (a line terminator,
+    /// `end` or EOF) and continues to parse. An `end` or `}` at which the error occurred is
+    /// left to close the enclosing construct, and stray `)` and `]` are skipped.
+    pub fn parse_program_collecting_errors(
+        code: String,
+        path: impl Into<PathBuf>,
+    ) -> Result<ParseResult, Vec<ParseErr>> {
+        let path = path.into();
+        let mut parser = Parser::<DummyContext>::init(
+            &code,
+            path.clone(),
+            None,
+            LvarScope::new_eval(None),
+            ParserOptions::default(),
+        );
+        parser.errors = Some(vec![]);
+        let res = parser.parse_program_recovering();
+        let mut errors = parser.errors.take().unwrap();
+        match res {
+            Ok(program) if errors.is_empty() => {
+                return program
+                    .into_parse_result(code, path)
+                    .map_err(|err| vec![err])
+            }
+            Ok(_) => {}
+            Err(err) => errors.push(err),
+        }
+        let source_info = SourceInfoRef::new(SourceInfo::new(path, code));
+        Err(errors
+            .into_iter()
+            .map(|err| ParseErr::from_lexer_err(err, source_info.clone()))
+            .collect())
     }
 }
 
@@ -66,7 +171,13 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         extern_context: Option<&'a OuterContext>,
     ) -> Result<ParseResult, ParseErr> {
         let parse_ctx = LvarScope::new_eval(None);
-        parse(code, path, extern_context, parse_ctx)
+        parse(
+            code,
+            path,
+            extern_context,
+            parse_ctx,
+            ParserOptions::default(),
+        )
     }
 
     pub fn parse_program_binding(
@@ -76,7 +187,13 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         extern_context: Option<&OuterContext>,
     ) -> Result<ParseResult, ParseErr> {
         let parse_ctx = LvarScope::new_block(context);
-        parse(code, path, extern_context, parse_ctx)
+        parse(
+            code,
+            path,
+            extern_context,
+            parse_ctx,
+            ParserOptions::default(),
+        )
     }
 }
 
@@ -86,24 +203,219 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         path: PathBuf,
         extern_context: Option<&'a OuterContext>,
         scope: LvarScope,
-    ) -> Result<(Node, LvarCollector, Token), LexerErr> {
+        options: ParserOptions,
+    ) -> Result<ParsedProgram, LexerErr> {
+        let mut parser = Self::init(code, path, extern_context, scope, options);
+        let node = parser.parse_comp_stmt()?;
+        let tok = parser.peek()?;
+        Ok(parser.finish(node, tok))
+    }
+
+    /// Pop the toplevel scope, and gather the results of parsing for *node*.
+    fn finish(&mut self, node: Node, tok: Token) -> ParsedProgram {
+        let lvar = self.scope.pop().unwrap().lvar;
+        ParsedProgram {
+            node,
+            lvar,
+            tok,
+            heredocs: std::mem::take(&mut self.heredocs),
+            warnings: std::mem::take(&mut self.warnings),
+            data_pos: self.lexer.data_pos(),
+        }
+    }
+
+    fn init(
+        code: &'a str,
+        path: PathBuf,
+        extern_context: Option<&'a OuterContext>,
+        scope: LvarScope,
+        options: ParserOptions,
+    ) -> Self {
         let lexer = Lexer::new(code);
-        let mut parser = Parser {
+        Parser {
             lexer,
             path,
             prev_loc: Loc(0, 0),
             scope: vec![scope],
             loop_stack: vec![LoopKind::Top],
+            rescue_depth: 0,
             extern_context,
             suppress_acc_assign: false,
             suppress_mul_assign: false,
             suppress_do_block: false,
             defined_mode: false,
-        };
-        let node = parser.parse_comp_stmt()?;
-        let lvar = parser.scope.pop().unwrap().lvar;
-        let tok = parser.peek()?;
-        Ok((node, lvar, tok))
+            options,
+            heredocs: vec![],
+            errors: None,
+            warnings: vec![],
+        }
+    }
+
+    /// Parse the whole program, recovering from syntax errors.
+    fn parse_program_recovering(&mut self) -> Result<ParsedProgram, LexerErr> {
+        let mut node = self.parse_comp_stmt()?;
+        loop {
+            let tok = self.peek()?;
+            if tok.is_eof() {
+                return Ok(self.finish(node, tok));
+            }
+            // stray `end` or `}` etc.
+            self.record_error(error_unexpected(tok.loc(), "Expected end-of-input."));
+            self.get()?;
+            let rest = self.parse_comp_stmt()?;
+            if let (NodeKind::CompStmt(nodes), NodeKind::CompStmt(rest)) =
+                (&mut node.kind, rest.kind)
+            {
+                nodes.extend(rest);
+            }
+        }
+    }
+
+    /// Emit a warning. The same warning at the same position is emitted only once,
+    /// since the parser may parse some code twice on backtracking.
+    fn warn(&mut self, kind: ParseWarnKind, loc: Loc, msg: impl Into<String>) {
+        if !self
+            .warnings
+            .iter()
+            .any(|warning| warning.kind == kind && warning.loc == loc)
+        {
+            self.warnings.push(ParseWarning::new(kind, loc, msg));
+        }
+    }
+
+    /// Warn `a = a`.
+    fn check_self_assign(&mut self, lhs: &Node, mrhs: &[Node]) {
+        if let (NodeKind::LocalVar(_, lhs_name), [rhs]) = (&lhs.kind, mrhs) {
+            if matches!(&rhs.kind, NodeKind::LocalVar(_, name) if name == lhs_name) {
+                self.warn(
+                    ParseWarnKind::UselessExpression,
+                    lhs.loc().merge(rhs.loc()),
+                    format!("assignment of `{}' to itself", lhs_name),
+                );
+            }
+        }
+    }
+
+    fn record_error(&mut self, err: LexerErr) {
+        self.errors.as_mut().unwrap().push(err);
+    }
+
+    /// Skip tokens until a line terminator, `end` or EOF.
+    fn skip_to_stmt_end(&mut self) -> Result<(), LexerErr> {
+        loop {
+            let tok = self.peek_no_term()?;
+            if tok.is_term() || tok.kind == TokenKind::Reserved(Reserved::End) {
+                return Ok(());
+            }
+            self.get()?;
+        }
+    }
+
+    fn save_recovery_state(&self) -> RecoveryState {
+        RecoveryState {
+            lexer_state: self.save_state(),
+            scope_len: self.scope.len(),
+            loop_stack_len: self.loop_stack.len(),
+            rescue_depth: self.rescue_depth,
+            suppress_acc_assign: self.suppress_acc_assign,
+            suppress_mul_assign: self.suppress_mul_assign,
+            suppress_do_block: self.suppress_do_block,
+            defined_mode: self.defined_mode,
+        }
+    }
+
+    /// Record *err* and skip to the end of the statement which began at *start*, and return
+    /// `NodeKind::Error` for the statement.
+    ///
+    /// Return *err* if the parser does not recover from errors or reached EOF.
+    fn recover_from_error(
+        &mut self,
+        err: LexerErr,
+        start: Loc,
+        state: RecoveryState,
+    ) -> Result<Node, LexerErr> {
+        if self.errors.is_none() || err.0 == ParseErrKind::UnexpectedEOF {
+            return Err(err);
+        }
+        let err_pos = err.1 .0;
+        self.record_error(err);
+        self.scope.truncate(state.scope_len);
+        self.loop_stack.truncate(state.loop_stack_len);
+        self.rescue_depth = state.rescue_depth;
+        self.suppress_acc_assign = state.suppress_acc_assign;
+        self.suppress_mul_assign = state.suppress_mul_assign;
+        self.suppress_do_block = state.suppress_do_block;
+        self.defined_mode = state.defined_mode;
+        if !self.rewind_to_closing_token(err_pos, state.lexer_state.1) {
+            self.skip_to_stmt_end()?;
+        }
+        let end = std::cmp::max(start.1, self.prev_loc().1);
+        Ok(Node::new_error(Loc(start.0, end)))
+    }
+
+    /// If the token at *err_pos* is `end` or `}`, move back to it so that the enclosing
+    /// construct can be closed by the token, and return true.
+    ///
+    /// *stmt_pos* is the position where the failed statement began.
+    fn rewind_to_closing_token(&mut self, err_pos: usize, stmt_pos: usize) -> bool {
+        if err_pos < stmt_pos {
+            return false;
+        }
+        let save = self.save_state();
+        self.restore_state((err_pos, err_pos));
+        self.lexer.flush();
+        match self.peek_no_term() {
+            Ok(tok)
+                if tok.loc.0 == err_pos
+                    && matches!(
+                        tok.kind,
+                        TokenKind::Reserved(Reserved::End) | TokenKind::Punct(Punct::RBrace)
+                    )
+                    && !self.has_unclosed_construct(stmt_pos, err_pos) =>
+            {
+                true
+            }
+            _ => {
+                self.restore_state(save);
+                self.lexer.flush();
+                false
+            }
+        }
+    }
+
+    /// Examine if the code in *start*..*end* has a construct which is not closed by `end`
+    /// or `}`. (e.g. `def f(; end`) Then the closing token belongs to the failed statement.
+    fn has_unclosed_construct(&self, start: usize, end: usize) -> bool {
+        let mut lexer = self.lexer.new_with_range(start, end);
+        let mut depth = 0;
+        let mut stmt_head = true;
+        while let Ok(tok) = lexer.get_token() {
+            match tok.kind {
+                TokenKind::Eof => break,
+                TokenKind::Reserved(
+                    Reserved::Def
+                    | Reserved::Class
+                    | Reserved::Module
+                    | Reserved::Case
+                    | Reserved::Begin
+                    | Reserved::For
+                    | Reserved::Do,
+                )
+                | TokenKind::Punct(Punct::LBrace) => depth += 1,
+                // modifier `if` etc. do not need `end`.
+                TokenKind::Reserved(
+                    Reserved::If | Reserved::Unless | Reserved::While | Reserved::Until,
+                ) if stmt_head => depth += 1,
+                TokenKind::Reserved(Reserved::End) | TokenKind::Punct(Punct::RBrace) => depth -= 1,
+                _ => {}
+            }
+            stmt_head = tok.is_line_term()
+                || matches!(
+                    tok.kind,
+                    TokenKind::Punct(Punct::Assign | Punct::LParen | Punct::Comma)
+                );
+        }
+        depth > 0
     }
 
     fn save_state(&self) -> (usize, usize) {
@@ -129,6 +441,12 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         false
     }
 
+    /// Check whether the parser is at the top level of the program. (not in any class, method,
+    /// block or loop)
+    fn is_top_level(&self) -> bool {
+        self.scope.len() == 1 && self.loop_stack.len() == 1
+    }
+
     /// Check whether parameter delegation exists or not in the method def of current context.
     /// If not, return ParseErr.
     fn check_delegate(&self) -> Result<(), LexerErr> {
@@ -166,6 +484,14 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
     /// Add the `id` as a new parameter in the current context.
     /// If a parameter with the same name already exists, return error.
     fn new_param(&mut self, name: String, loc: Loc) -> Result<LvarId, LexerErr> {
+        if self.scope.last().unwrap().kind == ScopeKind::Block && self.is_local_var(&name).is_some()
+        {
+            self.warn(
+                ParseWarnKind::ShadowedVariable,
+                loc,
+                format!("shadowing outer local variable - {}", name),
+            );
+        }
         match self.scope_mut().lvar.insert_new(name) {
             Some(lvar) => Ok(lvar),
             None => Err(error_unexpected(loc, "Duplicated argument name.")),
@@ -176,6 +502,22 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         self.scope_mut().lvar.kw.push(lvar);
     }
 
+    fn add_positional_param(&mut self, lvar: LvarId) {
+        self.scope_mut().lvar.add_positional_param(lvar);
+    }
+
+    /// Add the `id` as a new block-local variable in the current context.
+    /// If a parameter with the same name already exists, return error.
+    fn new_block_local(&mut self, name: String, loc: Loc) -> Result<(), LexerErr> {
+        // block-local variables shadow outer variables intentionally.
+        let lvar = match self.scope_mut().lvar.insert_new(name) {
+            Some(lvar) => lvar,
+            None => return Err(error_unexpected(loc, "Duplicated argument name.")),
+        };
+        self.scope_mut().lvar.block_locals.push(lvar);
+        Ok(())
+    }
+
     /// Add the `id` as a new parameter in the current context.
     /// If a parameter with the same name already exists, return error.
     fn new_kwrest_param(&mut self, name: String, loc: Loc) -> Result<(), LexerErr> {
@@ -478,6 +820,11 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
 
         self.loop_stack.pop().unwrap();
         let lvar = self.scope.pop().unwrap().lvar;
+        if ordinary_param {
+            if let Some(loc) = lvar.numbered_param {
+                return Err(error_ordinary_param_defined(loc));
+            }
+        }
         let loc = loc.merge(self.prev_loc());
         let node = Node::new_lambda(params, body, lvar, loc);
         self.suppress_mul_assign = old_suppress_mul_flag;
@@ -487,9 +834,6 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
     /// Parse operator which can be defined as a method.
     /// Return IdentId of the operator.
     fn parse_op_definable(&mut self, punct: &Punct) -> Result<&'static str, LexerErr> {
-        // TODO: must support
-        // ^
-        // **  ~  ` !  !~
         match punct {
             Punct::Plus => {
                 if self.consume_char('@') {
@@ -506,12 +850,19 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 }
             }
             Punct::Mul => Ok("*"),
+            Punct::DMul => Ok("**"),
             Punct::Div => Ok("/"),
             Punct::Rem => Ok("%"),
             Punct::Shl => Ok("<<"),
             Punct::Shr => Ok(">>"),
             Punct::BitAnd => Ok("&"),
             Punct::BitOr => Ok("|"),
+            Punct::BitXor => Ok("^"),
+            Punct::BitNot => {
+                // `~@` is the same as `~`.
+                self.consume_char('@');
+                Ok("~")
+            }
 
             Punct::Cmp => Ok("<=>"),
             Punct::Eq => Ok("=="),
@@ -522,6 +873,8 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             Punct::Ge => Ok(">="),
             Punct::TEq => Ok("==="),
             Punct::Match => Ok("=~"),
+            Punct::Not => Ok("!"),
+            Punct::Unmatch => Ok("!~"),
             Punct::LBracket => {
                 if self.consume_punct_no_term(Punct::RBracket)? {
                     if self.consume_punct_no_term(Punct::Assign)? {
@@ -580,6 +933,9 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 if self.consume_punct(term)? {
                     return Ok(args);
                 }
+                if term == Punct::BitOr && self.peek_punct_no_term(Punct::Semi) {
+                    break;
+                }
             }
             let mut loc = self.loc();
             if self.consume_punct(Punct::LParen)? {
@@ -616,7 +972,8 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 match self.consume_ident()? {
                     Some(name) => {
                         args.push(FormalParam::rest(name.clone(), loc));
-                        self.new_param(name, self.prev_loc())?;
+                        let lvar = self.new_param(name, self.prev_loc())?;
+                        self.add_positional_param(lvar);
                     }
                     None => args.push(FormalParam::rest_discard(loc)),
                 }
@@ -656,7 +1013,8 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                         }
                     };
                     args.push(FormalParam::optional(name.clone(), default, loc));
-                    self.new_param(name, loc)?;
+                    let lvar = self.new_param(name, loc)?;
+                    self.add_positional_param(lvar);
                 } else if self.consume_punct_no_term(Punct::Colon)? {
                     // Keyword param
                     let next = self.peek_no_term()?.kind;
@@ -690,11 +1048,13 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                     match state {
                         Kind::Required => {
                             args.push(FormalParam::req_param(name.clone(), loc));
-                            self.new_param(name, loc)?;
+                            let lvar = self.new_param(name, loc)?;
+                            self.add_positional_param(lvar);
                         }
                         Kind::PostReq | Kind::Optional | Kind::Rest => {
                             args.push(FormalParam::post(name.clone(), loc));
-                            self.new_param(name, loc)?;
+                            let lvar = self.new_param(name, loc)?;
+                            self.add_positional_param(lvar);
                             state = Kind::PostReq;
                         }
                         _ => {
@@ -719,12 +1079,29 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 }
             }
         }
+        if terminator == Some(Punct::BitOr) && self.peek_punct_no_term(Punct::Semi) {
+            self.get_no_skip_line_term()?;
+            self.parse_block_locals()?;
+        }
         if let Some(term) = terminator {
             self.expect_punct(term)?;
         }
         Ok(args)
     }
 
+    /// Parse block-local variables.
+    /// `|' [BLOCK_PARAMS] `;' ident [`,' ident]* `|'
+    fn parse_block_locals(&mut self) -> Result<(), LexerErr> {
+        loop {
+            let loc = self.loc();
+            let name = self.expect_ident()?;
+            self.new_block_local(name, loc)?;
+            if !self.consume_punct(Punct::Comma)? {
+                return Ok(());
+            }
+        }
+    }
+
     fn parse_destruct_param(&mut self) -> Result<FormalParam, LexerErr> {
         let loc = self.loc();
         let name = self.expect_ident()?;
@@ -762,6 +1139,13 @@ fn error_nested_numbered_param(loc: Loc) -> LexerErr {
     )
 }
 
+fn error_ordinary_param_defined(loc: Loc) -> LexerErr {
+    LexerErr(
+        ParseErrKind::SyntaxError("ordinary parameter is defined".to_string()),
+        loc,
+    )
+}
+
 fn error_eof(loc: Loc) -> LexerErr {
     LexerErr(ParseErrKind::UnexpectedEOF, loc)
 }
@@ -771,26 +1155,110 @@ fn parse(
     path: PathBuf,
     extern_context: Option<&impl LocalsContext>,
     parse_context: LvarScope,
+    options: ParserOptions,
 ) -> Result<ParseResult, ParseErr> {
-    match Parser::new(&code, path.clone(), extern_context, parse_context) {
-        Ok((node, lvar_collector, tok)) => {
-            let source_info = SourceInfoRef::new(SourceInfo::new(path, code));
-            if tok.is_eof() {
-                let result = ParseResult {
-                    node,
-                    lvar_collector,
-                    source_info,
-                };
-                Ok(result)
-            } else {
-                let err = error_unexpected(tok.loc(), "Expected end-of-input.");
-                Err(ParseErr::from_lexer_err(err, source_info))
-            }
-        }
+    match Parser::new(&code, path.clone(), extern_context, parse_context, options) {
+        Ok(program) => program.into_parse_result(code, path),
         Err(err) => {
             let source_info = SourceInfoRef::new(SourceInfo::new(path, code));
+            Err(ParseErr::from_lexer_err(err, source_info))
+        }
+    }
+}
+
+/// The result of `Parser::new()`.
+#[derive(Debug)]
+struct ParsedProgram {
+    node: Node,
+    lvar: LvarCollector,
+    /// the token after the program, which must be EOF.
+    tok: Token,
+    heredocs: Vec<HeredocInfo>,
+    warnings: Vec<ParseWarning>,
+    /// the start of the data section after `__END__`.
+    data_pos: Option<usize>,
+}
+
+impl ParsedProgram {
+    /// Build `ParseResult` of *code*, adding what is read from the code itself.
+    /// (the shebang line, magic comments and the data section)
+    ///
+    /// Return an error if the program is followed by something other than EOF.
+    fn into_parse_result(self, code: String, path: PathBuf) -> Result<ParseResult, ParseErr> {
+        let ParsedProgram {
+            mut node,
+            lvar,
+            tok,
+            heredocs,
+            warnings,
+            data_pos,
+        } = self;
+        if data_pos.is_some() {
+            resolve_data_const(&mut node);
+        }
+        let data_section = data_pos.map(|pos| code[pos..].to_string());
+        let shebang = shebang_line(&code).map(|line| line.to_string());
+        let magic = read_magic_comments(&code);
+        let source_info = SourceInfoRef::new(SourceInfo::new(path, code));
+        if !tok.is_eof() {
+            let err = error_unexpected(tok.loc(), "Expected end-of-input.");
             return Err(ParseErr::from_lexer_err(err, source_info));
         }
+        Ok(ParseResult {
+            node,
+            lvar_collector: lvar,
+            source_info,
+            heredocs,
+            warnings,
+            shebang,
+            frozen_string_literal: magic.frozen_string_literal,
+            encoding: magic.encoding,
+            data_section,
+        })
+    }
+}
+
+/// Replace the references to the constant `DATA` with `SpecialVar(SPECIAL_DATA)`, which
+/// reads the data section after `__END__`.
+///
+/// Assignments to `DATA` and `DATA` as the parent of a scoped constant (`DATA::X`) remain
+/// `NodeKind::Const`.
+fn resolve_data_const(node: &mut Node) {
+    match &mut node.kind {
+        NodeKind::Const {
+            toplevel: false,
+            parent: None,
+            prefix,
+            name,
+        } if prefix.is_empty() && name == "DATA" => {
+            node.kind = NodeKind::SpecialVar(SPECIAL_DATA);
+        }
+        NodeKind::Const { .. } => {}
+        // `DATA ||= 1` is expanded to `DATA || DATA = 1`.
+        NodeKind::BinOp(BinOp::LOr | BinOp::LAnd, lhs, rhs) if is_op_assign_expansion(lhs, rhs) => {
+            lhs.children_mut().into_iter().for_each(resolve_data_const);
+            resolve_data_const(rhs);
+        }
+        NodeKind::AssignOp(_, lhs, rhs) => {
+            lhs.children_mut().into_iter().for_each(resolve_data_const);
+            resolve_data_const(rhs);
+        }
+        NodeKind::MulAssign(mlhs, mrhs) => {
+            mlhs.iter_mut()
+                .flat_map(|lhs| lhs.children_mut())
+                .for_each(resolve_data_const);
+            mrhs.iter_mut().for_each(resolve_data_const);
+        }
+        _ => node.children_mut().into_iter().for_each(resolve_data_const),
+    }
+}
+
+/// Examine whether `lhs || rhs` (or `&&`) is the expansion of `lhs ||= value`, where *rhs*
+/// is the assignment `lhs = value`.
+fn is_op_assign_expansion(lhs: &Node, rhs: &Node) -> bool {
+    match &rhs.kind {
+        NodeKind::MulAssign(mlhs, _) => mlhs.len() == 1 && mlhs[0] == *lhs,
+        _ => false,
     }
 }
 
@@ -800,6 +1268,31 @@ pub struct ParseResult {
     pub lvar_collector: LvarCollector,
     pub source_info: SourceInfoRef,
     //pub id_store: IdentifierTable,
+    pub(crate) heredocs: Vec<HeredocInfo>,
+    /// warnings found while parsing.
+    pub warnings: Vec<ParseWarning>,
+    /// the shebang line (`#!/usr/bin/env ruby`) without the newline.
+    pub shebang: Option<String>,
+    /// the value of `# frozen_string_literal:` magic comment.
+    pub frozen_string_literal: Option<bool>,
+    /// the value of `# encoding:` magic comment.
+    pub encoding: Option<String>,
+    /// the text after the `__END__` line, which can be read via `DATA`.
+    pub data_section: Option<String>,
+}
+
+/// The state of the parser to be restored on error recovery.
+#[derive(Debug, Clone, Copy)]
+struct RecoveryState {
+    /// the lexer state at the start of the statement.
+    lexer_state: (usize, usize),
+    scope_len: usize,
+    loop_stack_len: usize,
+    rescue_depth: usize,
+    suppress_acc_assign: bool,
+    suppress_mul_assign: bool,
+    suppress_do_block: bool,
+    defined_mode: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -810,8 +1303,9 @@ enum LoopKind {
     For,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum ScopeKind {
+/// Kind of a local variable scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
     Eval,
     Class,
     Method,
@@ -895,10 +1389,71 @@ pub enum NReal {
     Integer(i64),
     Bignum(BigInt),
     Float(f64),
+    Rational(BigRational),
 }
 
+impl NReal {
+    fn from_bigint(b: BigInt) -> Self {
+        match b.to_i64() {
+            Some(i) => NReal::Integer(i),
+            None => NReal::Bignum(b),
+        }
+    }
+
+    fn to_bigint(&self) -> BigInt {
+        match self {
+            NReal::Integer(i) => BigInt::from(*i),
+            NReal::Bignum(b) => b.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn to_rational(&self) -> BigRational {
+        match self {
+            NReal::Rational(r) => r.clone(),
+            _ => BigRational::from_integer(self.to_bigint()),
+        }
+    }
+
+    /// Convert to f64.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            NReal::Integer(i) => *i as f64,
+            NReal::Bignum(b) => b.to_f64().unwrap_or(f64::NAN),
+            NReal::Float(f) => *f,
+            NReal::Rational(r) => r.to_f64().unwrap_or(f64::NAN),
+        }
+    }
+}
+
+/// Arithmetic with Ruby's coercion: Float wins over Rational, and Rational over Integer.
+/// Integers overflowing i64 become Bignum.
+macro_rules! nreal_binop {
+    ($trait:ident, $method:ident, $op:tt) => {
+        impl std::ops::$trait for NReal {
+            type Output = NReal;
+
+            fn $method(self, rhs: NReal) -> NReal {
+                match (&self, &rhs) {
+                    (NReal::Float(_), _) | (_, NReal::Float(_)) => {
+                        NReal::Float(self.to_f64() $op rhs.to_f64())
+                    }
+                    (NReal::Rational(_), _) | (_, NReal::Rational(_)) => {
+                        NReal::Rational(self.to_rational() $op rhs.to_rational())
+                    }
+                    _ => NReal::from_bigint(self.to_bigint() $op rhs.to_bigint()),
+                }
+            }
+        }
+    };
+}
+
+nreal_binop!(Add, add, +);
+nreal_binop!(Sub, sub, -);
+nreal_binop!(Mul, mul, *);
+
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
 
     fn parse_test(code: &str) {
@@ -907,9 +1462,10 @@ mod test {
             std::path::PathBuf::new(),
             None,
             LvarScope::new_eval(None),
+            ParserOptions::default(),
         )
         .unwrap()
-        .0;
+        .node;
         dbg!(node);
     }
 
@@ -919,18 +1475,28 @@ mod test {
             std::path::PathBuf::new(),
             None,
             LvarScope::new_eval(None),
+            ParserOptions::default(),
         )
         .unwrap()
-        .0;
+        .node;
         assert_eq!(node, expected);
     }
 
+    pub(crate) fn parse_ok(code: &str) -> ParseResult {
+        Parser::parse_program(code.to_string(), PathBuf::new()).unwrap()
+    }
+
+    pub(crate) fn parse_err(code: &str) -> ParseErr {
+        Parser::parse_program(code.to_string(), PathBuf::new()).unwrap_err()
+    }
+
     fn parse_test_err(code: &str) {
         Parser::<DummyContext>::new(
             code,
             std::path::PathBuf::new(),
             None,
             LvarScope::new_eval(None),
+            ParserOptions::default(),
         )
         .unwrap_err();
     }
@@ -979,6 +1545,1423 @@ mod test {
         );
     }
 
+    #[test]
+    fn visibility_block() {
+        let code = "private { def foo; end }";
+        let options = ParserOptions {
+            allow_visibility_block: true,
+            ..Default::default()
+        };
+        let res =
+            Parser::parse_program_with_options(code.to_string(), PathBuf::new(), options).unwrap();
+        match res.node.kind {
+            NodeKind::VisibilityBlock { visibility, body } => {
+                assert_eq!(Visibility::Private, visibility);
+                assert!(matches!(body.kind, NodeKind::MethodDef(..)));
+            }
+            _ => panic!(),
+        }
+        let res = parse_ok(code);
+        assert!(matches!(res.node.kind, NodeKind::FuncCall { .. }));
+    }
+
+    #[test]
+    fn error_line_col() {
+        let err = parse_err("a = 1\nb = (2\n");
+        assert_eq!(Some((3, 0)), err.loc_in_source());
+        let err = parse_err("x = 1\n  y = ]");
+        assert_eq!((2, 6), err.line_col());
+    }
+
+    #[test]
+    fn replace_child() {
+        let mut node = parse_ok("a = 1 + 2").node;
+        assert!(node.replace_child(Loc(8, 8), Node::new_integer(5, Loc(8, 8))));
+        assert!(!node.replace_child(Loc(9, 9), Node::new_nil(Loc(9, 9))));
+        match &node.kind {
+            NodeKind::MulAssign(_, mrhs) => match &mrhs[0].kind {
+                NodeKind::BinOp(BinOp::Add, _, rhs) => assert_eq!(NodeKind::Integer(5), rhs.kind),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn block_params() {
+        fn block_info(code: &str) -> BlockInfo {
+            let node = parse_ok(code).node;
+            match node.kind {
+                NodeKind::FuncCall { arglist, .. } => match arglist.block.unwrap().kind {
+                    NodeKind::Lambda(info) => info,
+                    _ => panic!(),
+                },
+                _ => panic!(),
+            }
+        }
+        let info = block_info("f { puts 1 }");
+        assert!(!info.has_explicit_params());
+        assert_eq!(0, info.param_count());
+        assert!(info.is_thunk());
+        let info = block_info("f { |a, b = 1, *c| a }");
+        assert!(info.has_explicit_params());
+        assert_eq!(3, info.param_count());
+        assert!(!info.is_thunk());
+        let info = block_info("f { _1 }");
+        assert!(!info.has_explicit_params());
+        assert!(!info.is_thunk());
+        assert!(!info.has_block_local_vars());
+        let info = block_info("f {|x; y| }");
+        assert!(info.has_block_local_vars());
+        assert_eq!(1, info.param_count());
+        assert!(!block_info("f {|x| }").has_block_local_vars());
+    }
+
+    #[test]
+    fn numbered_param() {
+        fn block_body(code: &str) -> Node {
+            let node = parse_ok(code).node;
+            match node.kind {
+                NodeKind::MethodCall { arglist, .. } | NodeKind::FuncCall { arglist, .. } => {
+                    match arglist.block.unwrap().kind {
+                        NodeKind::Lambda(info) => *info.body,
+                        kind => panic!("{:?}", kind),
+                    }
+                }
+                kind => panic!("{:?}", kind),
+            }
+        }
+        match block_body("[1,2,3].map { _1 * 2 }").kind {
+            NodeKind::BinOp(_, lhs, _) => assert_eq!(NodeKind::NumberedParam(1), lhs.kind),
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(NodeKind::NumberedParam(9), block_body("f { _9 }").kind);
+        // `_1` outside blocks is an ordinary identifier.
+        assert_eq!(NodeKind::Ident("_1".to_string()), parse_ok("_1").node.kind);
+        parse_test_err("f { |x| _1 }");
+        parse_test_err("->(x) { _1 }");
+        parse_test("->() { _1 }");
+        parse_test_err("f { _1 = 3 }");
+        parse_test_err("f { _1 += 3 }");
+        parse_test_err("f { a, _2 = 3, 4 }");
+    }
+
+    #[test]
+    fn endless_def() {
+        let node = parse_ok("def double(x) = x * 2").node;
+        match node.kind {
+            NodeKind::MethodDef(name, info) => {
+                assert_eq!("double", name);
+                assert!(info.is_endless);
+                assert_eq!(1, info.param_count());
+                assert!(matches!(info.body.kind, NodeKind::BinOp(BinOp::Mul, ..)));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        let node = parse_ok("def self.greet = \"hello\"").node;
+        match node.kind {
+            NodeKind::SingletonMethodDef(_, name, info) => {
+                assert_eq!("greet", name);
+                assert!(info.is_endless);
+                assert_eq!(NodeKind::String("hello".to_string()), info.body.kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        match parse_ok("def foo; 1; end").node.kind {
+            NodeKind::MethodDef(_, info) => assert!(!info.is_endless),
+            kind => panic!("{:?}", kind),
+        }
+        parse_test("def ==(other) = true");
+        parse_test("def foo=(v); end");
+        parse_test_err("def foo=(v) = 1");
+        // endless methods can not have rescue or ensure clauses.
+        parse_err("def foo = 1\nensure\n  2\nend");
+        parse_err("def foo = 1\nrescue\n  2\nend");
+        // a rescue modifier belongs to the body.
+        match parse_ok("def f(x) = x rescue 2").node.kind {
+            NodeKind::MethodDef(_, info) => match info.body.kind {
+                NodeKind::Begin { body, rescue, .. } => {
+                    assert!(matches!(body.kind, NodeKind::LocalVar(..)));
+                    assert_eq!(NodeKind::Integer(2), rescue[0].body.kind);
+                }
+                kind => panic!("{:?}", kind),
+            },
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn hash_shorthand() {
+        let res = parse_ok("name = \"Alice\"; {name:, age: 30}");
+        let pairs = match res.node.kind {
+            NodeKind::CompStmt(mut nodes) => match nodes.pop().unwrap().kind {
+                NodeKind::Hash(pairs, _) => pairs,
+                kind => panic!("{:?}", kind),
+            },
+            kind => panic!("{:?}", kind),
+        };
+        assert_eq!(NodeKind::Symbol("name".to_string()), pairs[0].0.kind);
+        assert_eq!(NodeKind::LocalVar(0, "name".to_string()), pairs[0].1.kind);
+        assert_eq!(pairs[0].0.loc, pairs[0].1.loc);
+        assert_eq!(NodeKind::Integer(30), pairs[1].1.kind);
+        assert_ne!(pairs[1].0.loc, pairs[1].1.loc);
+        parse_test("a = 1; {a:\n}");
+        parse_test_err("{unknown:}");
+    }
+
+    #[test]
+    fn file_literal() {
+        let res = Parser::parse_program("__FILE__".to_string(), "test.rb").unwrap();
+        assert_eq!(NodeKind::FileLiteral, res.node.kind);
+        assert_eq!(PathBuf::from("test.rb"), res.source_info.path);
+        let res = Parser::parse_program("\"#{__FILE__}\"".to_string(), "test.rb").unwrap();
+        match res.node.kind {
+            NodeKind::InterporatedString(nodes) => {
+                assert!(nodes.iter().any(|n| n.kind == NodeKind::FileLiteral))
+            }
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn line_literal() {
+        let res = parse_ok("a = 1\nb = 2\n__LINE__");
+        match res.node.kind {
+            NodeKind::CompStmt(nodes) => assert_eq!(NodeKind::LineLiteral(3), nodes[2].kind),
+            kind => panic!("{:?}", kind),
+        }
+        let res = parse_ok("\n\"line: #{__LINE__}\"");
+        match res.node.kind {
+            NodeKind::InterporatedString(nodes) => {
+                assert!(nodes.iter().any(|n| n.kind == NodeKind::LineLiteral(2)))
+            }
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn method_literal() {
+        match parse_ok("def foo; __method__; end").node.kind {
+            NodeKind::MethodDef(_, info) => {
+                fn contains(node: &Node) -> bool {
+                    node.kind == NodeKind::MethodLiteral
+                        || node.children().into_iter().any(contains)
+                }
+                assert!(contains(&info.body));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(NodeKind::MethodLiteral, parse_ok("__method__").node.kind);
+        parse_err("__method__ = 1");
+        parse_err("__FILE__ = 1");
+        parse_err("a, __LINE__ = 1, 2");
+    }
+
+    #[test]
+    fn dir_literal() {
+        let res = Parser::parse_program("__dir__".to_string(), "lib/foo/bar.rb").unwrap();
+        assert_eq!(NodeKind::DirLiteral, res.node.kind);
+        assert_eq!("lib/foo", res.source_info.dir_name());
+        let res = Parser::parse_program("__dir__()".to_string(), "bar.rb").unwrap();
+        assert!(matches!(res.node.kind, NodeKind::FuncCall { .. }));
+        assert_eq!("", res.source_info.dir_name());
+        // assignment to `__dir__` makes a local variable.
+        let res = Parser::parse_program("__dir__ = 1; __dir__".to_string(), "bar.rb").unwrap();
+        match res.node.kind {
+            NodeKind::CompStmt(nodes) => {
+                assert_eq!(NodeKind::LocalVar(0, "__dir__".to_string()), nodes[1].kind)
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["__dir__"], res.lvar_collector.table().clone());
+    }
+
+    #[test]
+    fn lambda_literal() {
+        fn lambda(code: &str) -> BlockInfo {
+            fn find(node: Node) -> Option<BlockInfo> {
+                match node.kind {
+                    NodeKind::Lambda(info) => Some(info),
+                    _ => node.children().into_iter().find_map(|n| find(n.clone())),
+                }
+            }
+            let res = parse_ok(code);
+            find(res.node).unwrap()
+        }
+        let info = lambda("f = ->(x) { x + 1 }");
+        assert_eq!(vec!["x"], info.lvar.param_names().collect::<Vec<_>>());
+        let info = lambda("f = -> { 42 }");
+        assert!(!info.has_explicit_params());
+        assert_eq!(NodeKind::Integer(42), info.body.kind);
+        let info = lambda("f = ->(x, y=1) { x + y }");
+        assert_eq!(vec!["x", "y"], info.lvar.param_names().collect::<Vec<_>>());
+        let info = lambda("f = -> x do x * 2 end");
+        assert_eq!(1, info.param_count());
+        let info = lambda("a = 1; f = -> do a end");
+        assert_eq!(NodeKind::LocalVar(1, "a".to_string()), info.body.kind);
+    }
+
+    #[test]
+    fn safe_navigation() {
+        fn call(code: &str) -> (Node, String, ArgList, bool) {
+            let res = parse_ok(code);
+            match res.node.kind {
+                NodeKind::MethodCall {
+                    receiver,
+                    method,
+                    arglist,
+                    safe_nav,
+                } => (*receiver, method, arglist, safe_nav),
+                kind => panic!("{:?}", kind),
+            }
+        }
+        let (receiver, method, _, safe_nav) = call("x&.length");
+        assert!(matches!(receiver.kind, NodeKind::FuncCall { method, .. } if method == "x"));
+        assert_eq!(("length", true), (method.as_str(), safe_nav));
+        let (_, method, arglist, safe_nav) = call("x&.map { |y| y }");
+        assert_eq!(("map", true), (method.as_str(), safe_nav));
+        assert!(arglist.block.is_some());
+        let (receiver, method, _, safe_nav) = call("a&.b&.c");
+        assert_eq!(("c", true), (method.as_str(), safe_nav));
+        assert!(matches!(
+            receiver.kind,
+            NodeKind::MethodCall { safe_nav: true, .. }
+        ));
+        let (_, method, _, safe_nav) = call("a\n  &.b");
+        assert_eq!(("b", true), (method.as_str(), safe_nav));
+        let (_, _, _, safe_nav) = call("a.b");
+        assert!(!safe_nav);
+    }
+
+    #[test]
+    fn endless_range() {
+        fn range(code: &str) -> (NodeKind, NodeKind, bool) {
+            let res = parse_ok(code);
+            match res.node.kind {
+                NodeKind::Range {
+                    start,
+                    end,
+                    exclude_end,
+                    ..
+                } => (start.kind, end.kind, exclude_end),
+                kind => panic!("{:?}", kind),
+            }
+        }
+        assert_eq!((NodeKind::Integer(1), NodeKind::Nil, false), range("(1..)"));
+        assert_eq!((NodeKind::Nil, NodeKind::Integer(5), false), range("(..5)"));
+        assert_eq!(
+            (NodeKind::Integer(1), NodeKind::Integer(5), false),
+            range("(1..5)")
+        );
+        assert_eq!((NodeKind::Nil, NodeKind::Nil, true), range("(...)"));
+        assert_eq!((NodeKind::Integer(1), NodeKind::Nil, true), range("1...\n"));
+        parse_test("a[1..]; a[..-1]; f(1.., 2); case x; when 1.. then end");
+    }
+
+    fn array_elements(code: &str) -> Vec<NodeKind> {
+        let res = parse_ok(code);
+        let node = match res.node.kind {
+            NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap(),
+            _ => res.node,
+        };
+        match node.kind {
+            NodeKind::Array(elems, _) => elems.into_iter().map(|n| n.kind).collect(),
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn word_array() {
+        let s = |s: &str| NodeKind::String(s.to_string());
+        assert_eq!(
+            vec![s("hello"), s("world")],
+            array_elements("%w[hello world]")
+        );
+        assert_eq!(vec![s("a"), s("b"), s("c")], array_elements("%w(a b c)"));
+        assert_eq!(vec![s("a"), s("b")], array_elements("%w{a b}"));
+        assert_eq!(vec![s("a"), s("b")], array_elements("%w<a\n b>"));
+        assert_eq!(vec![s("a b"), s("c\t")], array_elements("%W[a\\ b c\\t]"));
+        let elems = array_elements("greeting = 1; %W[#{greeting} world x#{1 + 2}y]");
+        assert!(matches!(&elems[0], NodeKind::InterporatedString(nodes)
+            if nodes.iter().any(|n| matches!(n.kind, NodeKind::LocalVar(0, _)))));
+        assert_eq!(s("world"), elems[1]);
+        assert!(matches!(&elems[2], NodeKind::InterporatedString(nodes) if nodes.len() == 3));
+        // locations of the words.
+        let res = parse_ok("%W[x #{1}]");
+        match res.node.kind {
+            NodeKind::Array(elems, _) => assert_eq!(
+                vec![Loc(3, 3), Loc(5, 8)],
+                elems.iter().map(|n| n.loc).collect::<Vec<_>>()
+            ),
+            kind => panic!("{:?}", kind),
+        }
+        let res = parse_ok("%I[a#{1}b c]");
+        match res.node.kind {
+            NodeKind::Array(elems, _) => {
+                assert_eq!(
+                    vec![Loc(3, 8), Loc(10, 10)],
+                    elems.iter().map(|n| n.loc).collect::<Vec<_>>()
+                );
+                match &elems[0].kind {
+                    NodeKind::MethodCall { receiver, .. } => match &receiver.kind {
+                        NodeKind::InterporatedString(nodes) => {
+                            assert_eq!(NodeKind::String("a".to_string()), nodes[0].kind);
+                            // includes `#` as in "a#{1}b".
+                            assert_eq!(Loc(3, 4), nodes[0].loc);
+                        }
+                        kind => panic!("{:?}", kind),
+                    },
+                    kind => panic!("{:?}", kind),
+                }
+            }
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn symbol_array() {
+        assert_eq!(
+            array_elements("[:foo, :bar, :baz]"),
+            array_elements("%i[foo bar baz]")
+        );
+        assert_eq!(
+            array_elements("[:foo, :bar]"),
+            array_elements("%I(foo bar)")
+        );
+        let elems = array_elements("name = 1; %I[#{name} x]");
+        match &elems[0] {
+            NodeKind::MethodCall {
+                receiver, method, ..
+            } => {
+                assert_eq!("to_sym", method);
+                assert!(matches!(receiver.kind, NodeKind::InterporatedString(_)));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(NodeKind::Symbol("x".to_string()), elems[1]);
+    }
+
+    #[test]
+    fn percent_regexp() {
+        fn regexp(code: &str) -> (Vec<NodeKind>, String) {
+            let res = parse_ok(code);
+            let node = match res.node.kind {
+                NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap(),
+                _ => res.node,
+            };
+            match node.kind {
+                NodeKind::RegExp(nodes, op, _) => (nodes.into_iter().map(|n| n.kind).collect(), op),
+                kind => panic!("{:?}", kind),
+            }
+        }
+        let s = |s: &str| NodeKind::String(s.to_string());
+        assert_eq!((vec![s("foo/bar")], "m".to_string()), regexp("%r{foo/bar}"));
+        assert_eq!((vec![s("^\\d+$")], "mi".to_string()), regexp("%r(^\\d+$)i"));
+        assert_eq!((vec![s("a{2}[b]")], "m".to_string()), regexp("%r{a{2}[b]}"));
+        let (nodes, _) = regexp("pattern = 1; %r{#{pattern}a{2}}x");
+        assert!(nodes.iter().any(|n| matches!(n, NodeKind::LocalVar(0, _))));
+        assert_eq!(&s("a{2}"), nodes.last().unwrap());
+        assert_eq!((vec![s("a")], "m".to_string()), regexp("/a/"));
+    }
+
+    #[test]
+    fn percent_command() {
+        fn command(code: &str) -> NodeKind {
+            let res = parse_ok(code);
+            let node = match res.node.kind {
+                NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap(),
+                _ => res.node,
+            };
+            match node.kind {
+                NodeKind::Command(content) => content.kind,
+                kind => panic!("{:?}", kind),
+            }
+        }
+        assert_eq!(command("`echo hello`"), command("%x{echo hello}"));
+        assert_eq!(
+            NodeKind::String("echo `date`".to_string()),
+            command("%x(echo `date`)")
+        );
+        match command("dir = 1; %x(ls #{dir})") {
+            NodeKind::InterporatedString(nodes) => {
+                assert_eq!(NodeKind::String("ls ".to_string()), nodes[0].kind);
+                assert_eq!(NodeKind::LocalVar(0, "dir".to_string()), nodes[1].kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn percent_string() {
+        fn string(code: &str) -> NodeKind {
+            let res = parse_ok(code);
+            match res.node.kind {
+                NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap().kind,
+                kind => kind,
+            }
+        }
+        let s = |s: &str| NodeKind::String(s.to_string());
+        assert_eq!(s("it's easy"), string("%q{it's easy}"));
+        assert_eq!(s("no #{interp} here"), string("%q{no #{interp} here}"));
+        assert_eq!(s("a {b} c"), string("%q{a {b} c}"));
+        assert_eq!(s("a } \\ \\n"), string("%q{a \\} \\\\ \\n}"));
+        assert_eq!(s("a (b) \n"), string("%Q(a (b) \\n)"));
+        match string("name = 1; %Q{Hello, #{name}!}") {
+            NodeKind::InterporatedString(nodes) => assert_eq!(
+                vec![
+                    s("Hello, "),
+                    NodeKind::LocalVar(0, "name".to_string()),
+                    s("!")
+                ],
+                nodes.into_iter().map(|n| n.kind).collect::<Vec<_>>()
+            ),
+            kind => panic!("{:?}", kind),
+        }
+        match string("%Q{a #{1} {b} \\} c}") {
+            NodeKind::InterporatedString(nodes) => assert_eq!(s(" {b} } c"), nodes[2].kind),
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn walk() {
+        let res = parse_ok("puts 'a'\nif x\n  if y\n    foo(\"b\") if z\n  end\nend\nbar(1 + 2)");
+        let node = &res.node;
+        let func_calls = node
+            .walk()
+            .filter(|n| matches!(n.kind, NodeKind::FuncCall { .. }))
+            .count();
+        assert_eq!(3, func_calls);
+        let strings: Vec<_> = node
+            .walk()
+            .filter_map(|n| match &n.kind {
+                NodeKind::String(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec!["a", "b"], strings);
+        let is_if = |n: &Node| matches!(n.kind, NodeKind::If { .. });
+        let if_depth = node
+            .walk()
+            .filter(|n| is_if(n))
+            .map(|n| n.walk().filter(|n| is_if(n)).count())
+            .max();
+        assert_eq!(Some(3), if_depth);
+        // In post-order, children precede their parent and the root comes last.
+        let post: Vec<_> = node.walk_post().collect();
+        assert_eq!(node.walk().count(), post.len());
+        assert!(std::ptr::eq(node, *post.last().unwrap()));
+        let kinds: Vec<_> = post
+            .iter()
+            .rev()
+            .skip(1)
+            .take(4)
+            .map(|n| n.kind.kind_name())
+            .collect();
+        assert_eq!(vec!["FuncCall", "BinOp", "Integer", "Integer"], kinds);
+    }
+
+    #[test]
+    fn find_all_by_kind() {
+        let res = parse_ok("a = 1\n[1].each do |x|\n  foo(x) { bar(a) }\nend\nb = a.to_s");
+        let node = &res.node;
+        let names: Vec<_> = node
+            .find_func_calls()
+            .into_iter()
+            .map(|n| match &n.kind {
+                NodeKind::FuncCall { method, .. } => method.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(vec!["foo", "bar"], names);
+        assert_eq!(2, node.find_method_calls().len());
+        // `a` and `b` of the assignments are also `LocalVar`.
+        assert_eq!(5, node.find_local_vars().len());
+        assert_eq!(
+            2,
+            node.find_all_by_kind(|k| matches!(k, NodeKind::Lambda(_)))
+                .len()
+        );
+        let empty = parse_ok("");
+        assert!(empty.node.find_func_calls().is_empty());
+    }
+
+    #[test]
+    fn collecting_errors() {
+        let collect = |code: &str| {
+            Parser::parse_program_collecting_errors(code.to_string(), PathBuf::new())
+                .unwrap_err()
+                .into_iter()
+                .map(|err| err.line_col().0)
+                .collect::<Vec<_>>()
+        };
+        let code = "a = 1\nb = (1 + ]\nc = 2\ndef f(x)\n  x.foo(\"a\" :)\n  x\nend\nd = 3";
+        parse_err(code);
+        assert_eq!(vec![2, 5], collect(code));
+        assert_eq!(vec![1, 3], collect("x = )\ny = 1\nend\nz = 2"));
+        assert_eq!(
+            vec![2, 3],
+            collect("class A\n  1 +* 2\n  def f(; end\nend\n")
+        );
+        // `end` which caused the error closes the enclosing construct.
+        assert_eq!(vec![3], collect("def f\n  1 +\nend\nx = 1"));
+        assert_eq!(vec![1], collect("f { 1 + }\nx = 1"));
+        // stray `)` and `]` are skipped.
+        assert_eq!(vec![2, 4], collect("if x\n  )\nelse\n  ]\nend"));
+        // UnexpectedEOF is not recoverable.
+        assert_eq!(vec![1, 2], collect("foo(]\nbar("));
+        let res =
+            Parser::parse_program_collecting_errors("a = 1\nb = a".to_string(), PathBuf::new())
+                .unwrap();
+        assert_eq!(vec!["a", "b"], res.lvar_collector.table().clone());
+    }
+
+    #[test]
+    fn parse_warnings() {
+        fn warnings(code: &str) -> Vec<ParseWarnKind> {
+            parse_ok(code)
+                .warnings
+                .into_iter()
+                .map(|w| w.kind)
+                .collect()
+        }
+        let res = parse_ok("a = a");
+        assert_eq!(
+            vec![ParseWarning::new(
+                ParseWarnKind::UselessExpression,
+                Loc(0, 4),
+                "assignment of `a' to itself"
+            )],
+            res.warnings
+        );
+        assert_eq!(
+            vec![ParseWarnKind::UselessExpression],
+            warnings("b = 1\nb = b")
+        );
+        assert!(warnings("a = b = 1; a = b").is_empty());
+        assert_eq!(
+            vec![ParseWarnKind::ShadowedVariable],
+            warnings("x = 1; [1].each {|x| x }")
+        );
+        assert!(warnings("[1].each {|x| }; x = 1").is_empty());
+        assert!(warnings("x = 1; def f(x); end").is_empty());
+        assert!(warnings("x = 1; [1].each {|y; x| }").is_empty());
+        assert_eq!(vec![ParseWarnKind::AmbiguousOperator], warnings("puts -1"));
+        assert_eq!(vec![ParseWarnKind::AmbiguousOperator], warnings("foo *a"));
+        assert!(warnings("puts - 1; puts(-1); x = 1; x -1").is_empty());
+    }
+
+    #[test]
+    fn shebang() {
+        let res = parse_ok("#!/usr/bin/ruby\nputs 42");
+        assert_eq!(Some("#!/usr/bin/ruby".to_string()), res.shebang);
+        assert_eq!(
+            Some("#!/usr/bin/env ruby -w".to_string()),
+            parse_ok("#!/usr/bin/env ruby -w\r\n").shebang
+        );
+        assert_eq!(None, parse_ok("\n#!/usr/bin/ruby\n1").shebang);
+    }
+
+    #[test]
+    fn magic_comments() {
+        let res = parse_ok("# frozen_string_literal: true\n'a'");
+        assert_eq!(Some(true), res.frozen_string_literal);
+        assert_eq!(None, res.encoding);
+        let res = parse_ok("'a'");
+        assert_eq!(None, res.frozen_string_literal);
+        assert_eq!(None, res.encoding);
+        let res = parse_ok("#!/usr/bin/ruby\n#  Frozen-String-Literal :FALSE\n'a'");
+        assert_eq!(Some(false), res.frozen_string_literal);
+        let res = parse_ok("# encoding: UTF-8\n# frozen_string_literal: true\n1");
+        assert_eq!(Some("UTF-8".to_string()), res.encoding);
+        assert_eq!(Some(true), res.frozen_string_literal);
+        let res = parse_ok("# -*- coding: euc-jp; frozen_string_literal: true -*-\n1");
+        assert_eq!(Some("euc-jp".to_string()), res.encoding);
+        assert_eq!(Some(true), res.frozen_string_literal);
+        // encoding is effective only in the first two lines.
+        assert_eq!(None, parse_ok("# a\n# b\n# encoding: UTF-8\n1").encoding);
+        // magic comments after code are ignored.
+        assert_eq!(
+            None,
+            parse_ok("1\n# frozen_string_literal: true\n").frozen_string_literal
+        );
+    }
+
+    #[test]
+    fn data_section() {
+        let res = parse_ok("DATA.read\n__END__\nfoo\n  bar\n");
+        assert_eq!(Some("foo\n  bar\n".to_string()), res.data_section);
+        match res.node.kind {
+            NodeKind::MethodCall { receiver, .. } => {
+                assert_eq!(NodeKind::SpecialVar(SPECIAL_DATA), receiver.kind)
+            }
+            kind => panic!("{:?}", kind),
+        }
+        // the code after `__END__` is not parsed.
+        assert_eq!(
+            Some("def (\n".to_string()),
+            parse_ok("1\n  __END__\r\ndef (\n").data_section
+        );
+        assert_eq!(Some(String::new()), parse_ok("1\n__END__").data_section);
+        // `DATA` is an ordinary constant without `__END__`.
+        let res = parse_ok("DATA = 1\nDATA");
+        assert_eq!(None, res.data_section);
+        assert!(res
+            .node
+            .walk()
+            .all(|node| node.kind != NodeKind::SpecialVar(SPECIAL_DATA)));
+        // assignments and scoped constants are not the data section.
+        let res = parse_ok("DATA ||= 1\nDATA::X\nDATA\n__END__\n");
+        assert!(parse_ok("DATA += 1\n__END__\n")
+            .node
+            .walk()
+            .all(|node| node.kind != NodeKind::SpecialVar(SPECIAL_DATA)));
+        match res.node.kind {
+            NodeKind::CompStmt(nodes) => {
+                assert!(nodes[0]
+                    .walk()
+                    .all(|node| node.kind != NodeKind::SpecialVar(SPECIAL_DATA)));
+                match &nodes[1].kind {
+                    NodeKind::Const { prefix, name, .. } => {
+                        assert_eq!((vec!["DATA".to_string()], "X"), (prefix.clone(), &name[..]))
+                    }
+                    kind => panic!("{:?}", kind),
+                }
+                assert_eq!(NodeKind::SpecialVar(SPECIAL_DATA), nodes[2].kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        // `__END__` not on a line by itself, or in a string.
+        assert_eq!(None, parse_ok("1; __END__\n").data_section);
+        assert_eq!(
+            None,
+            parse_ok("a = \"\n__END__\n\"\nb = <<X\n__END__\nX\n").data_section
+        );
+    }
+
+    #[test]
+    fn singleton_class() {
+        let res = parse_ok("class << self; def foo; end; end");
+        match res.node.kind {
+            NodeKind::SingletonClassDef { singleton, info } => {
+                assert_eq!(NodeKind::SelfValue, singleton.kind);
+                match info.body.kind {
+                    NodeKind::Begin { body, .. } => {
+                        assert!(
+                            matches!(body.kind, NodeKind::MethodDef(ref name, _) if name == "foo")
+                        )
+                    }
+                    kind => panic!("{:?}", kind),
+                }
+            }
+            kind => panic!("{:?}", kind),
+        }
+        parse_ok("def f; class << self; end; end");
+        parse_err("def f; class A; end; end");
+    }
+
+    #[test]
+    fn rational_imaginary() {
+        fn literal(code: &str) -> NodeKind {
+            let res = parse_ok(code);
+            res.node.kind
+        }
+        let rational = |n: i64, d: i64| NodeKind::Rational(BigRational::new(n.into(), d.into()));
+        assert_eq!(rational(1, 1), literal("1r"));
+        assert_eq!(rational(3, 2), literal("1.5r"));
+        assert_eq!(rational(1, 8), literal("0.125r"));
+        assert_eq!(NodeKind::Imaginary(NReal::Integer(2)), literal("2i"));
+        assert_eq!(
+            NodeKind::Imaginary(NReal::Rational(BigRational::new(3.into(), 1.into()))),
+            literal("3ri")
+        );
+        match literal("1+2i") {
+            NodeKind::BinOp(BinOp::Add, lhs, rhs) => {
+                assert_eq!(NodeKind::Integer(1), lhs.kind);
+                assert_eq!(NodeKind::Imaginary(NReal::Integer(2)), rhs.kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        match literal("3/4r") {
+            NodeKind::BinOp(BinOp::Div, lhs, rhs) => {
+                assert_eq!(NodeKind::Integer(3), lhs.kind);
+                assert_eq!(rational(4, 1), rhs.kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        parse_err("1e3r");
+    }
+
+    #[test]
+    fn squiggly_heredoc() {
+        fn heredoc(code: &str) -> NodeKind {
+            let res = parse_ok(code);
+            match res.node.kind {
+                NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap().kind,
+                kind => kind,
+            }
+        }
+        let s = |s: &str| NodeKind::String(s.to_string());
+        assert_eq!(
+            s("  a\nb\n\n    c\n"),
+            heredoc("<<~EOS\n    a\n  b\n\n      c\n  EOS\n")
+        );
+        assert_eq!(s("a\n\nb\n"), heredoc("<<~'EOS'\n  a\n \n  b\nEOS\n"));
+        assert_eq!(
+            s("foo\nbar\n"),
+            heredoc("<<~EOS\n\tfoo\n        bar\nEOS\n")
+        );
+        assert_eq!(
+            s("\tfoo\nbar\n"),
+            heredoc("<<~EOS\n  \tfoo\n    bar\nEOS\n")
+        );
+        assert_eq!(s("\ta\n"), heredoc("<<~EOS\n  \\ta\nEOS\n"));
+        match heredoc("<<~EOS\n  Hello, #{@name}!\n    x\nEOS\n") {
+            NodeKind::InterporatedString(nodes) => assert_eq!(
+                vec![
+                    s("Hello, "),
+                    NodeKind::InstanceVar("@name".to_string()),
+                    s("!\n  x\n")
+                ],
+                nodes.into_iter().map(|n| n.kind).collect::<Vec<_>>()
+            ),
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(s("  a\n"), heredoc("<<-EOS\n  a\n  EOS\n"));
+    }
+
+    #[test]
+    fn begin_end_block() {
+        let res = parse_ok("BEGIN { puts \"start\" }\nx = 1\nEND { puts \"finish\" }");
+        let nodes = match res.node.kind {
+            NodeKind::CompStmt(nodes) => nodes,
+            kind => panic!("{:?}", kind),
+        };
+        assert!(matches!(nodes[0].kind, NodeKind::BeginBlock { .. }));
+        match &nodes[2].kind {
+            NodeKind::EndBlock { body } => assert!(matches!(body.kind, NodeKind::Lambda(_))),
+            kind => panic!("{:?}", kind),
+        }
+        parse_err("def f; BEGIN { }; end");
+        parse_err("[].each { END { } }");
+        parse_err("class C; BEGIN { }; end");
+        parse_err("BEGIN");
+    }
+
+    #[test]
+    fn throw_catch() {
+        fn parse_kind(code: &str) -> NodeKind {
+            parse_ok(code).node.kind
+        }
+        match parse_kind("throw :done") {
+            NodeKind::Throw { tag, value: None } => {
+                assert_eq!(NodeKind::Symbol("done".to_string()), tag.kind)
+            }
+            kind => panic!("{:?}", kind),
+        }
+        match parse_kind("catch(:done) { throw :done, 42 }") {
+            NodeKind::Catch {
+                tag: Some(tag),
+                body,
+            } => {
+                assert_eq!(NodeKind::Symbol("done".to_string()), tag.kind);
+                let body = match body.kind {
+                    NodeKind::Lambda(info) => info.body,
+                    kind => panic!("{:?}", kind),
+                };
+                match body.kind {
+                    NodeKind::Throw {
+                        value: Some(value), ..
+                    } => assert_eq!(NodeKind::Integer(42), value.kind),
+                    kind => panic!("{:?}", kind),
+                }
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert!(matches!(
+            parse_kind("catch do end"),
+            NodeKind::Catch { tag: None, .. }
+        ));
+        assert!(matches!(parse_kind("throw"), NodeKind::Ident(_)));
+        assert!(matches!(parse_kind("catch(:a)"), NodeKind::FuncCall { .. }));
+    }
+
+    #[test]
+    fn retry() {
+        let res = parse_ok("begin; f; rescue; retry; end");
+        match res.node.kind {
+            NodeKind::Begin { rescue, .. } => {
+                assert_eq!(NodeKind::Retry, rescue[0].body.kind)
+            }
+            kind => panic!("{:?}", kind),
+        }
+        parse_ok("def f\n  g\nrescue => e\n  [1].each { retry if e }\nend");
+        parse_ok("f rescue retry");
+        parse_err("retry");
+        parse_err("def f; retry; end");
+        parse_err("begin; rescue; def f; retry; end; end");
+        parse_err("begin; retry; rescue; end");
+    }
+
+    #[test]
+    fn until() {
+        fn while_node(code: &str) -> (Node, bool) {
+            let res = parse_ok(code);
+            let node = match res.node.kind {
+                NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap(),
+                _ => res.node,
+            };
+            match node.kind {
+                NodeKind::While { cond, cond_op, .. } => (*cond, cond_op),
+                kind => panic!("{:?}", kind),
+            }
+        }
+        let (cond, cond_op) = while_node("x = 0; until x > 10 do x += 1 end");
+        assert!(!cond_op);
+        assert!(matches!(
+            cond.kind,
+            NodeKind::BinOp(BinOp::Cmp(CmpKind::Gt), ..)
+        ));
+        let (cond, cond_op) = while_node("until done\n  step\nend");
+        assert!(!cond_op);
+        assert_eq!(NodeKind::Ident("done".to_string()), cond.kind);
+        let (cond, cond_op) = while_node("loop_body until done");
+        assert!(!cond_op);
+        assert_eq!(NodeKind::Ident("done".to_string()), cond.kind);
+        let (_, cond_op) = while_node("loop_body while running");
+        assert!(cond_op);
+    }
+
+    #[test]
+    fn proc_new() {
+        parse_node(
+            "Proc.new",
+            Node::new_mcall(
+                Node::new_const("Proc".to_string(), false, None, vec![], Loc(0, 3)),
+                "new".to_string(),
+                ArgList::default(),
+                false,
+                Loc(5, 8),
+            ),
+        );
+        let node = parse_ok("def f; Proc.new; end").node;
+        let body = match node.kind {
+            NodeKind::MethodDef(_, info) => info.body,
+            _ => panic!(),
+        };
+        match body.kind {
+            NodeKind::Begin { body, .. } => {
+                assert_eq!(Node::new_proc_new(false, Loc(7, 15)), *body)
+            }
+            _ => panic!(),
+        }
+        let res = parse_ok("def f; Proc.new {}; end");
+        assert!(!res.format_ast(0).contains("ProcNew"));
+    }
+
+    #[test]
+    fn defined() {
+        for operand in [
+            "a",
+            "@a",
+            "@@a",
+            "$a",
+            "A::B",
+            "a.b(1)",
+            "super",
+            "yield",
+            "1",
+            "[1, 2]",
+            "defined?(a)",
+        ] {
+            parse_test(&format!("defined?({})", operand));
+            parse_test(&format!("defined? {}", operand));
+        }
+        let node = parse_ok("defined?(x = 1); x").node;
+        match node.kind {
+            NodeKind::CompStmt(nodes) => {
+                match &nodes[0].kind {
+                    NodeKind::Defined(node) => {
+                        assert!(matches!(node.kind, NodeKind::MulAssign(..)))
+                    }
+                    _ => panic!(),
+                }
+                assert!(matches!(nodes[1].kind, NodeKind::LocalVar(..)));
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn to_ruby_sig() {
+        let node = parse_ok(
+            "def f(a, (b, c), d = nil, e = [], *r, g, h:, i: :sym, j: A::B, **kw, &blk); end",
+        )
+        .node;
+        let params = match node.kind {
+            NodeKind::MethodDef(_, info) => info.params,
+            _ => panic!(),
+        };
+        assert_eq!(
+            "(a, (b, c), d = nil, e = [], *r, g, h:, i: :sym, j: A::B, **kw, &blk)",
+            params.to_ruby_sig_str()
+        );
+        for (code, sig) in [
+            ("def f(x = 1 + 2, *, y: -1.5); end", "(x = ..., *, y: -1.5)"),
+            ("def f(...); end", "(...)"),
+            ("def f; end", "()"),
+        ] {
+            let node = parse_ok(code).node;
+            match node.kind {
+                NodeKind::MethodDef(_, info) => assert_eq!(sig, info.params.to_ruby_sig_str()),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_program_reader() {
+        let res = Parser::parse_program_reader("a = 1".as_bytes(), "a.rb").unwrap();
+        assert!(matches!(res.node.kind, NodeKind::MulAssign(..)));
+        let options = ParserOptions {
+            max_file_size: Some(5),
+            ..Default::default()
+        };
+        assert!(
+            Parser::parse_program_reader_with_options("a = 1".as_bytes(), "", options.clone())
+                .is_ok()
+        );
+        let err = Parser::parse_program_reader_with_options("a = 10".as_bytes(), "", options)
+            .unwrap_err()
+            .downcast::<ParseErr>()
+            .unwrap();
+        assert_eq!(ParseErrKind::FileTooLarge(5), err.kind);
+        let err = Parser::parse_program_reader("a = (".as_bytes(), "a.rb").unwrap_err();
+        assert!(err.downcast_ref::<ParseErr>().is_some());
+        assert!(Parser::parse_program_reader(&[0xffu8, 0xfe][..], "").is_err());
+    }
+
+    #[test]
+    fn op_definable() {
+        fn method_name(code: &str) -> String {
+            match parse_ok(code).node.kind {
+                NodeKind::MethodDef(name, _) => name,
+                kind => panic!("{:?}", kind),
+            }
+        }
+        assert_eq!("^", method_name("def ^(other); end"));
+        assert_eq!("**", method_name("def **(exp); end"));
+        assert_eq!("**", method_name("def **exp; end"));
+        assert_eq!("~", method_name("def ~; self.complement; end"));
+        assert_eq!("~", method_name("def ~@; end"));
+        assert_eq!("+@", method_name("def +@; self; end"));
+        assert_eq!("-@", method_name("def -@; self; end"));
+        assert_eq!("+", method_name("def +(other); end"));
+        assert_eq!("-", method_name("def -(other); end"));
+        assert_eq!("`", method_name("def `(cmd); super; end"));
+        assert_eq!("`", method_name("def\n  `(cmd)\nend"));
+        parse_test("def f; `ls`; end");
+        assert_eq!("!", method_name("def !(other); end"));
+        assert_eq!("!", method_name("def !; end"));
+        assert_eq!("!~", method_name("def !~(other); end"));
+        assert_eq!("!=", method_name("def !=(other); end"));
+        let res = parse_ok("class Matrix; def **(exp); end; end");
+        assert_eq!(1, res.find_all_methods("**").len());
+    }
+
+    /// Get the arms of the last `case`/`in` in *code*.
+    fn pattern_arms(code: &str) -> (Vec<PatternArm>, LvarCollector) {
+        let res = parse_ok(code);
+        let node = match res.node.kind {
+            NodeKind::CompStmt(mut nodes) => nodes.pop().unwrap(),
+            _ => res.node,
+        };
+        match node.kind {
+            NodeKind::PatternMatch { arms, .. } => (arms, res.lvar_collector),
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn pattern_match() {
+        let (arms, lvar) = pattern_arms(
+            r#"
+            case [1, 2]
+            in [a, b] if a < b then :array
+            in {name: String, age: 18..} then :hash
+            in Foo::Bar
+            in nil
+              :nil
+            else
+              :else
+            end
+            "#,
+        );
+        assert_eq!(5, arms.len());
+        let pattern = arms[0].pattern.as_deref().unwrap();
+        match &pattern.kind {
+            NodeKind::ArrayPattern { elements, .. } => assert_eq!(
+                vec![
+                    NodeKind::LocalVar(0, "a".to_string()),
+                    NodeKind::LocalVar(0, "b".to_string())
+                ],
+                elements
+                    .iter()
+                    .map(|node| node.kind.clone())
+                    .collect::<Vec<_>>()
+            ),
+            kind => panic!("{:?}", kind),
+        }
+        assert!(arms[0].guard.is_some());
+        assert_eq!(vec!["a", "b"], lvar.table().clone());
+        match &arms[1].pattern.as_deref().unwrap().kind {
+            NodeKind::HashPattern { pairs, .. } => {
+                assert_eq!(NodeKind::Symbol("name".to_string()), pairs[0].0.kind);
+                assert!(matches!(
+                    pairs[1].1.as_ref().unwrap().kind,
+                    NodeKind::Range { .. }
+                ));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert!(matches!(
+            arms[2].pattern.as_deref().unwrap().kind,
+            NodeKind::Const { .. }
+        ));
+        assert_eq!(NodeKind::Nil, arms[3].pattern.as_deref().unwrap().kind);
+        assert!(arms[4].pattern.is_none());
+
+        let (arms, _) = pattern_arms("case x\nin 1.. unless y\n  1\nend");
+        assert_eq!(1, arms.len());
+        assert!(matches!(
+            arms[0].guard.as_deref().unwrap().kind,
+            NodeKind::UnOp(UnOp::Not, _)
+        ));
+        parse_test("case x in [] then 1 end");
+        parse_test_err("case\nin 1 then 2\nend");
+    }
+
+    #[test]
+    fn array_pattern() {
+        fn array_pattern(pattern: &str) -> (Vec<NodeKind>, Option<usize>) {
+            let (arms, _) = pattern_arms(&format!("case x\nin {}\nend", pattern));
+            match arms[0].pattern.as_deref().unwrap().kind.clone() {
+                NodeKind::ArrayPattern {
+                    elements,
+                    rest_index,
+                } => (
+                    elements.into_iter().map(|node| node.kind).collect(),
+                    rest_index,
+                ),
+                kind => panic!("{:?}", kind),
+            }
+        }
+        assert_eq!((vec![], None), array_pattern("[]"));
+        assert_eq!((vec![NodeKind::DiscardLhs], Some(0)), array_pattern("[*]"));
+        let (elements, rest_index) = array_pattern("[Integer, *rest, String]");
+        assert_eq!(Some(1), rest_index);
+        assert!(matches!(&elements[0], NodeKind::Const { name, .. } if name == "Integer"));
+        assert_eq!(NodeKind::LocalVar(0, "rest".to_string()), elements[1]);
+        assert!(matches!(&elements[2], NodeKind::Const { name, .. } if name == "String"));
+        assert_eq!(
+            (
+                vec![
+                    NodeKind::LocalVar(0, "a".to_string()),
+                    NodeKind::ArrayPattern {
+                        elements: vec![],
+                        rest_index: None
+                    },
+                    NodeKind::LocalVar(0, "c".to_string()),
+                ],
+                Some(2)
+            ),
+            array_pattern("a, [], *c")
+        );
+        assert_eq!((vec![NodeKind::DiscardLhs], Some(0)), array_pattern("*"));
+        parse_test_err("case x\nin [*a, *b]\nend");
+    }
+
+    #[test]
+    fn find_pattern() {
+        let (arms, lvar) = pattern_arms("case x\nin [*, String, Integer, *post]\nend");
+        match arms[0].pattern.as_deref().unwrap().kind.clone() {
+            NodeKind::FindPattern { pre, core, post } => {
+                assert_eq!(
+                    vec![NodeKind::DiscardLhs],
+                    pre.into_iter().map(|node| node.kind).collect::<Vec<_>>()
+                );
+                assert_eq!(2, core.len());
+                assert_eq!(
+                    vec![NodeKind::LocalVar(0, "post".to_string())],
+                    post.into_iter().map(|node| node.kind).collect::<Vec<_>>()
+                );
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["post"], lvar.table().clone());
+        let (arms, _) = pattern_arms("case x\nin *pre, 1, *\nend");
+        assert!(matches!(
+            arms[0].pattern.as_deref().unwrap().kind,
+            NodeKind::FindPattern { .. }
+        ));
+        // rests must be the first and the last elements.
+        parse_test_err("case x\nin [*, String, *, Integer, *]\nend");
+        parse_test_err("case x\nin [*, *]\nend");
+        parse_test_err("case x\nin [1, *a, *b]\nend");
+    }
+
+    #[test]
+    fn pin_operator() {
+        let (arms, lvar) = pattern_arms("expected = 1\ncase val in ^expected then :ok end");
+        assert_eq!(
+            NodeKind::PinOperator {
+                inner: Box::new(Node::new_lvar("expected".to_string(), 0, Loc(26, 33)))
+            },
+            arms[0].pattern.as_deref().unwrap().kind
+        );
+        assert_eq!(vec!["expected"], lvar.table().clone());
+        let (arms, _) =
+            pattern_arms("expected = 1\ncase val\nin [^expected, ^@a, ^(1 + 2)] then :ok\nend");
+        let elements = match arms[0].pattern.as_deref().unwrap().kind.clone() {
+            NodeKind::ArrayPattern { elements, .. } => elements,
+            kind => panic!("{:?}", kind),
+        };
+        let inner: Vec<_> = elements
+            .into_iter()
+            .map(|node| match node.kind {
+                NodeKind::PinOperator { inner } => inner.kind.kind_name(),
+                kind => panic!("{:?}", kind),
+            })
+            .collect();
+        assert_eq!(vec!["LocalVar", "InstanceVar", "BinOp"], inner);
+        parse_test_err("case val\nin ^unknown then :ok\nend");
+    }
+
+    #[test]
+    fn rightward_assign() {
+        fn rightward_assign(code: &str) -> (Node, Node, Vec<String>) {
+            let res = parse_ok(code);
+            match res.node.kind {
+                NodeKind::RightwardAssign { value, pattern } => {
+                    (*value, *pattern, res.lvar_collector.table().clone())
+                }
+                kind => panic!("{:?}", kind),
+            }
+        }
+        let (value, pattern, lvars) = rightward_assign("1 => Integer");
+        assert_eq!(NodeKind::Integer(1), value.kind);
+        assert!(matches!(pattern.kind, NodeKind::Const { .. }));
+        assert!(lvars.is_empty());
+        let (value, pattern, lvars) = rightward_assign("{a: 1} => {a:}");
+        assert!(matches!(value.kind, NodeKind::Hash(..)));
+        assert!(matches!(pattern.kind, NodeKind::HashPattern { .. }));
+        assert_eq!(vec!["a"], lvars);
+        let (value, pattern, lvars) = rightward_assign("(1..5).sum => total");
+        assert!(matches!(value.kind, NodeKind::MethodCall { .. }));
+        assert_eq!(NodeKind::LocalVar(0, "total".to_string()), pattern.kind);
+        assert_eq!(vec!["total"], lvars);
+        let (_, pattern, lvars) = rightward_assign("42 => Integer => n");
+        match pattern.kind {
+            NodeKind::CapturePattern { pattern, binding } => {
+                assert!(matches!(pattern.kind, NodeKind::Const { .. }));
+                assert_eq!(NodeKind::LocalVar(0, "n".to_string()), binding.kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["n"], lvars);
+        // `=>` in arguments and hash literals is not rightward assignment.
+        for code in ["f(1 => 2)", "f 1 => 2", "{1 => 2}"] {
+            let node = parse_ok(code).node;
+            assert!(matches!(
+                node.kind,
+                NodeKind::FuncCall { .. } | NodeKind::Hash(..)
+            ));
+        }
+        // `return a => 1` returns a hash.
+        for code in ["return a => 1, 2 => b", "break a => 1", "next a => 1"] {
+            match parse_ok(code).node.kind {
+                NodeKind::Return(val) | NodeKind::Break(val) | NodeKind::Next(val) => {
+                    assert!(matches!(val.kind, NodeKind::Hash(..)), "{:?}", val)
+                }
+                kind => panic!("{:?}", kind),
+            }
+        }
+        match parse_ok("return 1, a => 2").node.kind {
+            NodeKind::Return(val) => match val.kind {
+                NodeKind::Array(elems, _) => {
+                    assert_eq!(NodeKind::Integer(1), elems[0].kind);
+                    assert!(matches!(elems[1].kind, NodeKind::Hash(..)));
+                }
+                kind => panic!("{:?}", kind),
+            },
+            kind => panic!("{:?}", kind),
+        }
+        parse_test_err("return a => 1, b");
+        // a braced hash literal may be followed by other values.
+        fn break_values(node: &Node) -> Option<&Vec<Node>> {
+            node.walk().find_map(|node| match &node.kind {
+                NodeKind::Return(val) | NodeKind::Break(val) => match &val.kind {
+                    NodeKind::Array(elems, _) => Some(elems),
+                    _ => None,
+                },
+                _ => None,
+            })
+        }
+        for code in [
+            "def f; return {a: 1}, 2; end",
+            "[1].each { break {b: 2}, 3 }",
+        ] {
+            let node = parse_ok(code).node;
+            let elems = break_values(&node).unwrap();
+            assert_eq!(2, elems.len());
+            assert!(matches!(elems[0].kind, NodeKind::Hash(..)));
+            assert!(matches!(elems[1].kind, NodeKind::Integer(_)));
+        }
+    }
+
+    #[test]
+    fn hash_pattern() {
+        fn hash_pattern(pattern: &str) -> (NodeKind, Vec<String>) {
+            let (arms, lvar) = pattern_arms(&format!("case x\nin {}\nend", pattern));
+            (
+                arms[0].pattern.as_deref().unwrap().kind.clone(),
+                lvar.table().clone(),
+            )
+        }
+        let (kind, lvars) = hash_pattern("{name:, age: (18..)}");
+        match kind {
+            NodeKind::HashPattern {
+                pairs,
+                rest: None,
+                double_splat: false,
+            } => {
+                assert_eq!(2, pairs.len());
+                assert_eq!(NodeKind::Symbol("name".to_string()), pairs[0].0.kind);
+                assert!(pairs[0].1.is_none());
+                assert!(matches!(
+                    pairs[1].1.as_ref().unwrap().kind,
+                    NodeKind::Range { .. }
+                ));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["name"], lvars);
+        let (kind, lvars) = hash_pattern("{name: String, **nil}");
+        assert!(matches!(
+            kind,
+            NodeKind::HashPattern {
+                rest: None,
+                double_splat: true,
+                ..
+            }
+        ));
+        assert!(lvars.is_empty());
+        let (kind, lvars) = hash_pattern("id:, **opts");
+        match kind {
+            NodeKind::HashPattern { pairs, rest, .. } => {
+                assert_eq!(1, pairs.len());
+                assert_eq!(
+                    NodeKind::LocalVar(0, "opts".to_string()),
+                    rest.unwrap().kind
+                );
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["id", "opts"], lvars);
+        assert!(matches!(
+            hash_pattern("{}").0,
+            NodeKind::HashPattern { ref pairs, .. } if pairs.is_empty()
+        ));
+        parse_test_err("case x\nin {Name:}\nend");
+    }
+
+    #[test]
+    fn alternative_pattern() {
+        fn first_pattern(pattern: &str) -> Node {
+            let (mut arms, _) = pattern_arms(&format!("case x\nin {} then 1\nend", pattern));
+            *arms.remove(0).pattern.unwrap()
+        }
+        match first_pattern("1 | 2").kind {
+            NodeKind::AlternativePattern { patterns } => {
+                assert_eq!(NodeKind::Integer(1), patterns[0].kind);
+                assert_eq!(NodeKind::Integer(2), patterns[1].kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        // `=>` captures the whole alternatives.
+        match first_pattern("Integer | Float => n").kind {
+            NodeKind::CapturePattern { pattern, binding } => {
+                match pattern.kind {
+                    NodeKind::AlternativePattern { patterns } => assert_eq!(2, patterns.len()),
+                    kind => panic!("{:?}", kind),
+                }
+                assert_eq!(NodeKind::LocalVar(0, "n".to_string()), binding.kind);
+            }
+            kind => panic!("{:?}", kind),
+        }
+        match first_pattern("..0 | 10.. | [_, *_]").kind {
+            NodeKind::AlternativePattern { patterns } => assert_eq!(3, patterns.len()),
+            kind => panic!("{:?}", kind),
+        }
+        // variables can not be bound in alternatives, except ones beginning with `_`.
+        parse_test_err("case x; in a | 1 then end");
+        parse_test_err("case x; in [1, a] | 2 then end");
+        parse_test_err("case x; in {a:} | 2 then end");
+        parse_test_err("case x; in 1 | {b: 1, **rest} then end");
+        first_pattern("[_a, 1] | _");
+        parse_test("y = 1; case x; in ^y | 2 then end");
+    }
+
+    #[test]
+    fn const_pattern() {
+        fn first_pattern(pattern: &str) -> (Node, Vec<String>) {
+            let (mut arms, lvar) = pattern_arms(&format!("case x\nin {} then 1\nend", pattern));
+            (*arms.remove(0).pattern.unwrap(), lvar.table().clone())
+        }
+        let (pattern, lvars) = first_pattern("Foo[a, b]");
+        match pattern.kind {
+            NodeKind::ConstPattern { constant, pattern } => {
+                assert!(matches!(constant.kind, NodeKind::Const { .. }));
+                assert!(matches!(pattern.kind, NodeKind::ArrayPattern { .. }));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["a", "b"], lvars);
+        let (pattern, lvars) = first_pattern("Foo::Bar(x:)");
+        match pattern.kind {
+            NodeKind::ConstPattern { constant, pattern } => {
+                match constant.kind {
+                    NodeKind::Const { name, prefix, .. } => {
+                        assert_eq!("Bar", name);
+                        assert_eq!(vec!["Foo"], prefix);
+                    }
+                    kind => panic!("{:?}", kind),
+                }
+                assert!(matches!(pattern.kind, NodeKind::HashPattern { .. }));
+            }
+            kind => panic!("{:?}", kind),
+        }
+        assert_eq!(vec!["x"], lvars);
+        let (pattern, _) = first_pattern("Foo(*, 1, *)");
+        match pattern.kind {
+            NodeKind::ConstPattern { pattern, .. } => {
+                assert!(matches!(pattern.kind, NodeKind::FindPattern { .. }))
+            }
+            kind => panic!("{:?}", kind),
+        }
+        let (pattern, _) = first_pattern("Foo[]");
+        match pattern.kind {
+            NodeKind::ConstPattern { pattern, .. } => match pattern.kind {
+                NodeKind::ArrayPattern { elements, .. } => assert!(elements.is_empty()),
+                kind => panic!("{:?}", kind),
+            },
+            kind => panic!("{:?}", kind),
+        }
+        let (pattern, _) = first_pattern("Point(x: 0, y: _) | Point[0, _]");
+        assert!(matches!(pattern.kind, NodeKind::AlternativePattern { .. }));
+        parse_test_err("case x; in Foo(1 then end");
+    }
+
     #[test]
     fn method_call() {
         parse_test("a()");
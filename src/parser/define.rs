@@ -11,68 +11,101 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         // 変数参照 : 定数識別子 | 大域変数識別子 | クラス変数識別子 | インスタンス変数識別子 | 局所変数識別子 | 擬似変数
         // メソッド定義名 : メソッド名 ｜ ( 定数識別子 | 局所変数識別子 ) "="
         let def_loc = self.prev_loc();
-        let tok = self.get()?;
-        let loc = tok.loc;
-        let (singleton, name) = match tok.kind {
-            TokenKind::GlobalVar(name) => {
-                self.consume_punct_no_term(Punct::Dot)?;
-                (
-                    Some(Node::new_global_var(name, loc)),
-                    self.read_method_name(true)?.0,
-                )
-            }
-            TokenKind::InstanceVar(name) => {
-                self.consume_punct_no_term(Punct::Dot)?;
-                (
-                    Some(Node::new_instance_var(name, loc)),
-                    self.read_method_name(true)?.0,
-                )
-            }
-            TokenKind::Reserved(r) => (None, self.read_method_ext(r.to_str().to_string())?),
-            TokenKind::Ident(s) => {
-                if s.as_str() == "self" {
+        let (singleton, name) = if let Some(loc) = self.lexer.consume_backtick_method_name() {
+            self.prev_loc = loc;
+            (None, "`".to_string())
+        } else {
+            let tok = self.get()?;
+            let loc = tok.loc;
+            match tok.kind {
+                TokenKind::GlobalVar(name) => {
                     self.consume_punct_no_term(Punct::Dot)?;
-                    (Some(Node::new_self(loc)), self.read_method_name(true)?.0)
-                } else if self.consume_punct_no_term(Punct::Dot)?
-                    || self.consume_punct_no_term(Punct::Scope)?
-                {
                     (
-                        Some(Node::new_lvar(s, 0, loc)),
+                        Some(Node::new_global_var(name, loc)),
                         self.read_method_name(true)?.0,
                     )
-                } else {
-                    (None, self.read_method_ext(s)?)
                 }
-            }
-            TokenKind::NumberedParam(i, _) => return Err(error_numbered_param(loc, i)),
-            TokenKind::Const(s) => {
-                if self.consume_punct_no_term(Punct::Dot)?
-                    || self.consume_punct_no_term(Punct::Scope)?
-                {
+                TokenKind::InstanceVar(name) => {
+                    self.consume_punct_no_term(Punct::Dot)?;
                     (
-                        Some(Node::new_const(s, false, None, vec![], loc)),
+                        Some(Node::new_instance_var(name, loc)),
                         self.read_method_name(true)?.0,
                     )
-                } else {
-                    (None, self.read_method_ext(s)?)
                 }
+                TokenKind::Reserved(r) => (None, self.read_method_ext(r.to_str().to_string())?),
+                TokenKind::Ident(s) => {
+                    if s.as_str() == "self" {
+                        self.consume_punct_no_term(Punct::Dot)?;
+                        (Some(Node::new_self(loc)), self.read_method_name(true)?.0)
+                    } else if self.consume_punct_no_term(Punct::Dot)?
+                        || self.consume_punct_no_term(Punct::Scope)?
+                    {
+                        (
+                            Some(Node::new_lvar(s, 0, loc)),
+                            self.read_method_name(true)?.0,
+                        )
+                    } else {
+                        (None, self.read_method_ext(s)?)
+                    }
+                }
+                TokenKind::NumberedParam(i, _) => return Err(error_numbered_param(loc, i)),
+                TokenKind::Const(s) => {
+                    if self.consume_punct_no_term(Punct::Dot)?
+                        || self.consume_punct_no_term(Punct::Scope)?
+                    {
+                        (
+                            Some(Node::new_const(s, false, None, vec![], loc)),
+                            self.read_method_name(true)?.0,
+                        )
+                    } else {
+                        (None, self.read_method_ext(s)?)
+                    }
+                }
+                TokenKind::Punct(p) => (None, self.parse_op_definable(&p)?.to_string()),
+                _ => return Err(error_unexpected(loc, "Invalid method name.")),
             }
-            TokenKind::Punct(p) => (None, self.parse_op_definable(&p)?.to_string()),
-            _ => return Err(error_unexpected(loc, "Invalid method name.")),
         };
 
         self.scope.push(LvarScope::new_method());
         self.loop_stack.push(LoopKind::Top);
-        let args = self.parse_def_params()?;
-        let body = self.parse_begin()?;
+        let old_rescue_depth = std::mem::take(&mut self.rescue_depth);
+        let (args, is_endless) = if self.consume_punct_no_term(Punct::Assign)? {
+            (vec![], true)
+        } else {
+            let args = self.parse_def_params()?;
+            (args, self.consume_punct_no_term(Punct::Assign)?)
+        };
+        let body = if is_endless {
+            // def foo(..) = ARG
+            if is_setter_name(&name) {
+                return Err(error_unexpected(
+                    self.prev_loc(),
+                    "setter method cannot be defined in an endless method definition",
+                ));
+            }
+            let body = self.parse_arg()?;
+            // def foo(..) = ARG rescue ARG
+            if self.consume_reserved_no_skip_line_term(Reserved::Rescue)? {
+                self.rescue_depth += 1;
+                let rescue = self.parse_arg();
+                self.rescue_depth -= 1;
+                let rescue = rescue?;
+                Node::new_begin(body, vec![RescueEntry::new_postfix(rescue)], None, None)
+            } else {
+                body
+            }
+        } else {
+            self.parse_begin()?
+        };
         self.loop_stack.pop().unwrap();
+        self.rescue_depth = old_rescue_depth;
         let lvar = self.scope.pop().unwrap().lvar;
 
         let decl = match singleton {
-            Some(singleton) => {
-                Node::new_singleton_method_decl(singleton, name, args, body, lvar, def_loc)
-            }
-            None => Node::new_method_decl(name, args, body, lvar, def_loc),
+            Some(singleton) => Node::new_singleton_method_decl(
+                singleton, name, args, body, lvar, is_endless, def_loc,
+            ),
+            None => Node::new_method_decl(name, args, body, lvar, is_endless, def_loc),
         };
         Ok(decl)
     }
@@ -125,8 +158,10 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
 
         self.scope.push(LvarScope::new_class(None));
         self.loop_stack.push(LoopKind::Top);
+        let old_rescue_depth = std::mem::take(&mut self.rescue_depth);
         let body = self.parse_begin()?;
         self.loop_stack.pop().unwrap();
+        self.rescue_depth = old_rescue_depth;
         let lvar = self.scope.pop().unwrap().lvar;
 
         Ok(Node::new_class_decl(
@@ -145,13 +180,31 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
 
         self.scope.push(LvarScope::new_class(None));
         self.loop_stack.push(LoopKind::Top);
+        let old_rescue_depth = std::mem::take(&mut self.rescue_depth);
         let body = self.parse_begin()?;
         self.loop_stack.pop().unwrap();
+        self.rescue_depth = old_rescue_depth;
         let lvar = self.scope.pop().unwrap().lvar;
 
         Ok(Node::new_singleton_class_decl(singleton, body, lvar, loc))
     }
 
+    /// Parse `private { .. }` or `protected { .. }`.
+    pub(super) fn parse_visibility_block(
+        &mut self,
+        visibility: Visibility,
+        loc: Loc,
+    ) -> Result<Node, LexerErr> {
+        self.expect_punct(Punct::LBrace)?;
+        let body = self.parse_comp_stmt()?;
+        self.expect_punct(Punct::RBrace)?;
+        Ok(Node::new_visibility_block(
+            visibility,
+            body,
+            loc.merge(self.prev_loc()),
+        ))
+    }
+
     pub(crate) fn alias_name(&mut self) -> Result<Node, LexerErr> {
         if self.consume_punct_no_term(Punct::Colon)? {
             self.parse_symbol()
@@ -223,3 +276,9 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         }
     }
 }
+
+/// Examine whether *name* is a setter method name like `foo=` or `[]=`.
+fn is_setter_name(name: &str) -> bool {
+    name.ends_with('=')
+        && (name == "[]=" || name.starts_with(|c: char| c == '_' || c.is_alphabetic()))
+}
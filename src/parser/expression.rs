@@ -8,15 +8,34 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         let mut nodes = vec![];
 
         loop {
-            if self.peek()?.check_stmt_end() {
+            let tok = self.peek()?;
+            if self.errors.is_some()
+                && matches!(tok.kind, TokenKind::Punct(Punct::RParen | Punct::RBracket))
+            {
+                // stray `)` or `]`. (comp_stmt in parentheses does not recover from errors)
+                self.record_error(error_unexpected(tok.loc(), "Unexpected token."));
+                self.get()?;
+                continue;
+            }
+            if tok.check_stmt_end() {
                 let node = Node::new_comp_stmt(nodes, loc);
                 return Ok(node);
             }
 
-            let node = self.parse_stmt()?;
+            let state = self.save_recovery_state();
+            let node = match self.parse_stmt() {
+                Ok(node) => node,
+                Err(err) => self.recover_from_error(err, tok.loc(), state)?,
+            };
             nodes.push(node);
             if !self.consume_term()? {
-                break;
+                let tok = self.peek()?;
+                if self.errors.is_none() || tok.check_stmt_end() {
+                    break;
+                }
+                // garbage after the statement.
+                self.record_error(error_unexpected(tok.loc(), "Unexpected token."));
+                self.skip_to_stmt_end()?;
             }
         }
         let node = Node::new_comp_stmt(nodes, loc);
@@ -43,7 +62,16 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         // | LHS [no term] = MRHS
         // | * LHS [no term] = (UNPARENTHESIZED-METHOD-CALL | ARG)
         // | MLHS [no term] = MRHS
+        // | ARG [no term] => PATTERN
         let mut node = self.parse_expr()?;
+        // `return a => 1` was already parsed as returning a hash.
+        if !matches!(
+            node.kind,
+            NodeKind::Return(_) | NodeKind::Break(_) | NodeKind::Next(_)
+        ) && self.consume_punct_no_term(Punct::FatArrow)?
+        {
+            node = self.parse_rightward_assign(node)?;
+        }
         loop {
             if self.consume_reserved_no_skip_line_term(Reserved::If)? {
                 // STMT : STMT if EXPR
@@ -69,7 +97,11 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 node = Node::new_while_postfix(cond, node, false, loc);
             } else if self.consume_reserved_no_skip_line_term(Reserved::Rescue)? {
                 // STMT : STMT rescue EXPR
-                let rescue = self.parse_expr()?;
+                let old_rescue_depth = self.rescue_depth;
+                self.rescue_depth += 1;
+                let rescue = self.parse_expr();
+                self.rescue_depth = old_rescue_depth;
+                let rescue = rescue?;
                 node = Node::new_begin(node, vec![RescueEntry::new_postfix(rescue)], None, None);
             } else {
                 break;
@@ -198,10 +230,11 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
     pub(super) fn parse_arg(&mut self) -> Result<Node, LexerErr> {
         let next = self.peek()?;
         if self.lexer.has_trailing_space(&next) && self.consume_reserved(Reserved::Defined)? {
+            let old = self.defined_mode;
             self.defined_mode = true;
-            let node = self.parse_arg()?;
-            self.defined_mode = false;
-            return Ok(Node::new_defined(node));
+            let node = self.parse_arg();
+            self.defined_mode = old;
+            return Ok(Node::new_defined(node?));
         }
         self.parse_arg_assign()
     }
@@ -214,6 +247,7 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         if self.consume_punct_no_term(Punct::Assign)? {
             let lhs = self.check_lhs(lhs)?;
             let mrhs = self.parse_mul_assign_rhs(None)?;
+            self.check_self_assign(&lhs, &mrhs);
             Ok(Node::new_mul_assign(vec![lhs], mrhs))
         } else if let Some(op) = self.consume_assign_op_no_term()? {
             // <lhs> <assign_op> <arg>
@@ -241,21 +275,45 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
     }
 
     fn parse_arg_range(&mut self) -> Result<Node, LexerErr> {
-        let lhs = self.parse_arg_logical_or()?;
+        // beginless range: `..5`
+        let lhs = match self.peek_no_term()?.kind {
+            TokenKind::Punct(Punct::Range2) | TokenKind::Punct(Punct::Range3) => {
+                Node::new_nil(self.loc())
+            }
+            _ => self.parse_arg_logical_or()?,
+        };
         if self.is_line_term()? {
             return Ok(lhs);
         }
-        if self.consume_punct(Punct::Range2)? {
-            let rhs = self.parse_arg_logical_or()?;
-            let loc = lhs.loc().merge(rhs.loc());
-            Ok(Node::new_range(lhs, rhs, false, loc))
+        let exclude_end = if self.consume_punct(Punct::Range2)? {
+            false
         } else if self.consume_punct(Punct::Range3)? {
-            let rhs = self.parse_arg_logical_or()?;
-            let loc = lhs.loc().merge(rhs.loc());
-            Ok(Node::new_range(lhs, rhs, true, loc))
+            true
         } else {
-            Ok(lhs)
-        }
+            return Ok(lhs);
+        };
+        // endless range: `1..`
+        let rhs = if self.is_range_end()? {
+            Node::new_nil(self.prev_loc())
+        } else {
+            self.parse_arg_logical_or()?
+        };
+        let loc = lhs.loc().merge(rhs.loc());
+        Ok(Node::new_range(lhs, rhs, exclude_end, loc))
+    }
+
+    /// Examine the next token, and return true if the end of the range is omitted.
+    fn is_range_end(&mut self) -> Result<bool, LexerErr> {
+        let tok = self.peek_no_term()?;
+        Ok(tok.is_term()
+            || tok.check_stmt_end()
+            || matches!(
+                tok.kind,
+                TokenKind::Punct(Punct::Comma)
+                    | TokenKind::Punct(Punct::FatArrow)
+                    | TokenKind::Reserved(Reserved::Then)
+                    | TokenKind::Reserved(Reserved::Do)
+            ))
     }
 
     fn parse_arg_logical_or(&mut self) -> Result<Node, LexerErr> {
@@ -443,6 +501,7 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             if self.consume_punct_no_term(Punct::Assign)? {
                 let lhs = self.check_lhs(lhs)?;
                 let mrhs = self.parse_mul_assign_rhs_if_allowed()?;
+                self.check_self_assign(&lhs, &mrhs);
                 return Ok(Node::new_mul_assign(vec![lhs], mrhs));
             } else if let Some(op) = self.consume_assign_op_no_term()? {
                 return self.parse_assign_op(lhs, op);
@@ -511,7 +570,7 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         loop {
             node = if self.consume_punct(Punct::Dot)? {
                 self.parse_primary_method(node, false)?
-            } else if self.consume_punct_no_term(Punct::SafeNav)? {
+            } else if self.consume_punct(Punct::SafeNav)? {
                 self.parse_primary_method(node, true)?
             } else if self.consume_punct_no_term(Punct::Scope)? {
                 if let TokenKind::Const(_) = self.peek()?.kind {
@@ -621,8 +680,22 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             }
         };
 
+        // `Proc.new` without a block in a method body captures the block of the method.
+        if id == "new" && arglist.is_empty() && self.is_method_context() {
+            if let NodeKind::Const {
+                toplevel: false,
+                parent: None,
+                prefix,
+                name,
+            } = &receiver.kind
+            {
+                if prefix.is_empty() && name == "Proc" {
+                    return Ok(Node::new_proc_new(false, receiver.loc.merge(loc)));
+                }
+            }
+        }
         let node = match receiver.kind {
-            NodeKind::Ident(id) => Node::new_fcall_noarg(id, false, loc),
+            NodeKind::Ident(id) => Node::new_fcall_noarg(id, false, receiver.loc),
             _ => receiver,
         };
         Ok(Node::new_mcall(node, id, arglist, safe_nav, loc))
@@ -640,11 +713,16 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                     "self" => return Ok(Node::new_self(loc)),
                     "__LINE__" => {
                         let line = self.lexer.get_line(loc.0);
-                        return Ok(Node::new_integer(line as i64, loc));
+                        return Ok(Node::new_line_literal(line, loc));
                     }
-                    "__FILE__" => {
-                        let file = self.path.to_string_lossy().to_string();
-                        return Ok(Node::new_string(file.into(), loc));
+                    "__FILE__" => return Ok(Node::new_file_literal(loc)),
+                    "__method__" => return Ok(Node::new_method_literal(loc)),
+                    // `__dir__()` is parsed as a method call, and `__dir__` can be a local
+                    // variable since it is a method.
+                    "__dir__"
+                        if !self.lexer.trailing_lparen() && self.is_local_var(&name).is_none() =>
+                    {
+                        return Ok(Node::new_dir_literal(loc))
                     }
                     _ => {}
                 };
@@ -662,6 +740,20 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                         match tok.kind {
                             // Multiple assignment
                             TokenKind::Punct(Punct::Comma) => return Ok(node),
+                            // private { .. } (with ParserOptions::allow_visibility_block)
+                            TokenKind::Punct(Punct::LBrace)
+                                if self.options.allow_visibility_block =>
+                            {
+                                let visibility = match name.as_str() {
+                                    "private" => Some(Visibility::Private),
+                                    "protected" => Some(Visibility::Protected),
+                                    _ => None,
+                                };
+                                if let Some(visibility) = visibility {
+                                    return self.parse_visibility_block(visibility, loc);
+                                }
+                                return self.parse_function_args(node);
+                            }
                             // Method call with block and no args
                             TokenKind::Punct(Punct::LBrace) | TokenKind::Reserved(Reserved::Do) => {
                                 return self.parse_function_args(node)
@@ -701,6 +793,8 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
 
                 if !suppress_unparen_call && self.is_command() {
                     Ok(self.parse_command(name, loc)?)
+                } else if i != 0 && self.scope.last().unwrap().kind == ScopeKind::Block {
+                    Ok(Node::new_numbered_param(i, loc))
                 } else {
                     Ok(node)
                 }
@@ -722,6 +816,7 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             TokenKind::IntegerLit(num) => Ok(Node::new_integer(num, loc)),
             TokenKind::BignumLit(num) => Ok(Node::new_bignum(num, loc)),
             TokenKind::FloatLit(num) => Ok(Node::new_float(num, loc)),
+            TokenKind::RationalLit(num) => Ok(Node::new_rational(num, loc)),
             TokenKind::ImaginaryLit(num) => Ok(Node::new_imaginary(num, loc)),
             TokenKind::StringLit(s) => self.parse_string_literal(s),
             TokenKind::CommandLit(s) => {
@@ -736,19 +831,27 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 Ok(Node::new_command(content))
             }
             TokenKind::Punct(punct) => match punct {
-                Punct::Minus => match self.get()?.kind {
-                    TokenKind::IntegerLit(num) => match num.checked_neg() {
-                        Some(i) => Ok(Node::new_integer(i, loc)),
-                        None => Ok(Node::new_bignum(-BigInt::from(num), loc)),
-                    },
-                    TokenKind::BignumLit(num) => Ok(Node::new_bignum(-num, loc)),
-                    TokenKind::FloatLit(num) => Ok(Node::new_float(-num, loc)),
-                    _ => unreachable!(),
-                },
+                Punct::Minus => {
+                    let tok = self.get()?;
+                    let loc = loc.merge(tok.loc());
+                    match tok.kind {
+                        TokenKind::IntegerLit(num) => match num.checked_neg() {
+                            Some(i) => Ok(Node::new_integer(i, loc)),
+                            None => Ok(Node::new_bignum(-BigInt::from(num), loc)),
+                        },
+                        TokenKind::BignumLit(num) => Ok(Node::new_bignum(-num, loc)),
+                        TokenKind::FloatLit(num) => Ok(Node::new_float(-num, loc)),
+                        _ => unreachable!(),
+                    }
+                }
                 Punct::LParen => {
                     let old = self.suppress_mul_assign;
                     self.suppress_mul_assign = false;
-                    let node = self.parse_comp_stmt()?;
+                    // an error in parentheses is recovered by the enclosing statement.
+                    let errors = self.errors.take();
+                    let node = self.parse_comp_stmt();
+                    self.errors = errors;
+                    let node = node?;
                     self.expect_punct(Punct::RParen)?;
                     self.suppress_mul_assign = old;
                     Ok(node)
@@ -786,18 +889,18 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 Reserved::Case => self.parse_case(),
                 Reserved::Def => self.parse_def(),
                 Reserved::Class => {
+                    let loc = self.prev_loc();
+                    // `class << obj` is permitted in method bodies.
+                    if self.consume_punct(Punct::Shl)? {
+                        return self.parse_singleton_class(loc);
+                    }
                     if self.is_method_context() {
                         return Err(error_unexpected(
                             loc,
                             "SyntaxError: class definition in method body.",
                         ));
                     }
-                    let loc = self.prev_loc();
-                    if self.consume_punct(Punct::Shl)? {
-                        self.parse_singleton_class(loc)
-                    } else {
-                        self.parse_class(false)
-                    }
+                    self.parse_class(false)
                 }
                 Reserved::Module => {
                     if self.is_method_context() {
@@ -812,12 +915,17 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 Reserved::Break => self.parse_break(),
                 Reserved::Next => self.parse_next(),
                 Reserved::Redo => self.parse_redo(),
+                Reserved::Retry => self.parse_retry(),
                 Reserved::Begin => self.parse_begin(),
+                Reserved::BEGIN => self.parse_begin_block(loc),
+                Reserved::END => self.parse_end_block(loc),
                 Reserved::Defined => {
                     if self.consume_punct_no_term(Punct::LParen)? {
+                        let old = self.defined_mode;
                         self.defined_mode = true;
-                        let node = self.parse_expr()?;
-                        self.defined_mode = false;
+                        let node = self.parse_expr();
+                        self.defined_mode = old;
+                        let node = node?;
                         self.expect_punct(Punct::RParen)?;
                         Ok(Node::new_defined(node))
                     } else {
@@ -977,7 +1085,11 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                 }
                 self.parse_then()?;
             };
-            let rescue_body = self.parse_comp_stmt()?;
+            let old_rescue_depth = self.rescue_depth;
+            self.rescue_depth += 1;
+            let rescue_body = self.parse_comp_stmt();
+            self.rescue_depth = old_rescue_depth;
+            let rescue_body = rescue_body?;
             rescue.push(RescueEntry::new(exception, assign, rescue_body));
         }
         let else_ = if self.consume_reserved(Reserved::Else)? {
@@ -994,6 +1106,38 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         Ok(Node::new_begin(body, rescue, else_, ensure))
     }
 
+    /// Parse `BEGIN { COMPSTMT }`.
+    fn parse_begin_block(&mut self, loc: Loc) -> Result<Node, LexerErr> {
+        if !self.is_top_level() {
+            return Err(error_unexpected(loc, "BEGIN is permitted only at toplevel"));
+        }
+        self.expect_punct(Punct::LBrace)?;
+        let body = self.parse_comp_stmt()?;
+        self.expect_punct(Punct::RBrace)?;
+        let loc = loc.merge(self.prev_loc());
+        Ok(Node::new_begin_block(body, loc))
+    }
+
+    /// Parse `END { COMPSTMT }`.
+    ///
+    /// The body is parsed as a block, like `at_exit { .. }`.
+    fn parse_end_block(&mut self, loc: Loc) -> Result<Node, LexerErr> {
+        if !self.is_top_level() {
+            return Err(error_unexpected(loc, "END is permitted only at toplevel"));
+        }
+        self.expect_punct(Punct::LBrace)?;
+        let block_loc = self.prev_loc();
+        self.scope.push(LvarScope::new_block(None));
+        self.loop_stack.push(LoopKind::Block);
+        let body = self.parse_comp_stmt()?;
+        self.expect_punct(Punct::RBrace)?;
+        self.loop_stack.pop().unwrap();
+        let lvar = self.scope.pop().unwrap().lvar;
+        let block = Node::new_lambda(vec![], body, lvar, block_loc.merge(self.prev_loc()));
+        let loc = loc.merge(self.prev_loc());
+        Ok(Node::new_end_block(block, loc))
+    }
+
     fn parse_command(&mut self, operation: String, loc: Loc) -> Result<Node, LexerErr> {
         // FNAME ARGS
         // FNAME ARGS DO-BLOCK
@@ -1044,6 +1188,21 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             }
             let outer = self.add_local_var_if_new(&name);
             return Ok(Node::new_lvar(name, outer, lhs.loc));
+        } else if lhs.kind == NodeKind::DirLiteral {
+            let name = "__dir__".to_string();
+            let outer = self.add_local_var_if_new(&name);
+            return Ok(Node::new_lvar(name, outer, lhs.loc));
+        } else if let NodeKind::NumberedParam(i) = lhs.kind {
+            return Err(error_numbered_param(lhs.loc, i));
+        } else if let NodeKind::FileLiteral | NodeKind::LineLiteral(_) | NodeKind::MethodLiteral =
+            lhs.kind
+        {
+            let name = match lhs.kind {
+                NodeKind::FileLiteral => "__FILE__",
+                NodeKind::LineLiteral(_) => "__LINE__",
+                _ => "__method__",
+            };
+            return Err(error_unexpected(lhs.loc, format!("Can't assign to {name}")));
         } else if let NodeKind::Const { .. } = lhs.kind {
             for c in self.scope.iter().rev() {
                 match c.kind {
@@ -1070,14 +1229,24 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                     Punct::LParen | Punct::LBracket | Punct::Scope | Punct::Arrow | Punct::Not => {
                         true
                     }
-                    Punct::Colon
-                    | Punct::Plus
-                    | Punct::Minus
-                    | Punct::Mul
-                    | Punct::Div
-                    | Punct::BitAnd
-                    | Punct::Rem
-                    | Punct::Shl => !self.lexer.has_trailing_space(&tok),
+                    Punct::Plus | Punct::Minus | Punct::Mul | Punct::BitAnd => {
+                        if self.lexer.has_trailing_space(&tok) {
+                            return false;
+                        }
+                        let msg = match p {
+                            Punct::Mul => "`*' interpreted as argument prefix".to_string(),
+                            Punct::BitAnd => "`&' interpreted as argument prefix".to_string(),
+                            _ => format!(
+                                "ambiguous first argument; put parentheses or a space even after `{}' operator",
+                                if p == Punct::Plus { '+' } else { '-' }
+                            ),
+                        };
+                        self.warn(ParseWarnKind::AmbiguousOperator, tok.loc(), msg);
+                        true
+                    }
+                    Punct::Colon | Punct::Div | Punct::Rem | Punct::Shl => {
+                        !self.lexer.has_trailing_space(&tok)
+                    }
                     _ => false,
                 },
                 TokenKind::Reserved(r) => !matches!(
@@ -1092,6 +1261,7 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                         | Reserved::Or
                         | Reserved::Then
                         | Reserved::End
+                        | Reserved::In
                 ),
                 _ => true,
             }
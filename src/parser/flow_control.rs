@@ -142,6 +142,15 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             None
         };
         self.consume_term()?;
+        if let TokenKind::Reserved(Reserved::In) = self.peek()?.kind {
+            let subject = match cond {
+                Some(cond) => cond,
+                None => return Err(error_unexpected(self.loc(), "Unexpected 'in'.")),
+            };
+            let node = self.parse_case_in(subject, loc)?;
+            self.suppress_mul_assign = old;
+            return Ok(node);
+        }
         let mut when_ = vec![];
         while self.consume_reserved(Reserved::When)? {
             let arg = self.parse_mul_assign_rhs(None)?;
@@ -197,6 +206,33 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         Ok(Node::new_redo(loc))
     }
 
+    pub(super) fn parse_retry(&mut self) -> Result<Node, LexerErr> {
+        let loc = self.prev_loc();
+        if !self.defined_mode && self.rescue_depth == 0 {
+            return Err(error_unexpected(loc, "Invalid retry"));
+        }
+        Ok(Node::new_retry(loc))
+    }
+
+    /// Parse an argument of `return`, `break` and `next`.
+    ///
+    /// `key => value, ..` is parsed as a hash without braces, which must be the last argument.
+    /// Return true as the second element if the argument is such a brace-less hash.
+    fn parse_break_arg(&mut self) -> Result<(Node, bool), LexerErr> {
+        let key = self.parse_arg()?;
+        if !self.consume_punct_no_term(Punct::FatArrow)? {
+            return Ok((key, false));
+        }
+        let loc = key.loc();
+        let mut kvp = vec![(key, self.parse_arg()?)];
+        while self.consume_punct_no_term(Punct::Comma)? {
+            let key = self.parse_arg()?;
+            self.expect_punct(Punct::FatArrow)?;
+            kvp.push((key, self.parse_arg()?));
+        }
+        Ok((Node::new_hash(kvp, loc.merge(self.prev_loc())), true))
+    }
+
     fn parse_break_sub(&mut self) -> Result<(Node, Loc), LexerErr> {
         let loc = self.prev_loc();
         let tok = self.peek_no_term()?;
@@ -209,12 +245,16 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             let val = Node::new_nil(loc);
             return Ok((val, loc));
         };
-        let val = self.parse_arg()?;
+        let (val, brace_less) = self.parse_break_arg()?;
         let ret_loc = val.loc();
-        if self.consume_punct_no_term(Punct::Comma)? {
-            let mut vec = vec![val, self.parse_arg()?];
-            while self.consume_punct_no_term(Punct::Comma)? {
-                vec.push(self.parse_arg()?);
+        if !brace_less && self.consume_punct_no_term(Punct::Comma)? {
+            let mut vec = vec![val];
+            loop {
+                let (arg, brace_less) = self.parse_break_arg()?;
+                vec.push(arg);
+                if brace_less || !self.consume_punct_no_term(Punct::Comma)? {
+                    break;
+                }
             }
             let val = Node::new_array(vec, ret_loc);
             Ok((val, loc))
@@ -2,8 +2,9 @@ use super::*;
 use num::ToPrimitive;
 use std::ops::Range;
 
+/// An error found by the lexer or the parser.
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct LexerErr(pub ParseErrKind, pub Loc);
+pub struct LexerErr(pub ParseErrKind, pub Loc);
 
 #[derive(Clone, PartialEq)]
 pub(crate) enum ParseMode {
@@ -12,8 +13,71 @@ pub(crate) enum ParseMode {
     Command,
 }
 
+/// The body of a here document.
+pub(crate) struct HeredocBody {
+    pub(crate) mode: ParseMode,
+    /// the identifier which terminates the here document.
+    pub(crate) tag: String,
+    /// true for `<<~`.
+    pub(crate) squiggly: bool,
+    /// byte position of the top of the body.
+    pub(crate) start: usize,
+    /// byte position of the end of the body. (exclusive)
+    pub(crate) end: usize,
+}
+
 use enum_iterator::all;
 
+/// Get the opening delimiter which is paired with *term*. (e.g. `{` for `}`)
+pub(crate) fn open_delimiter(term: char) -> Option<char> {
+    match term {
+        ')' => Some('('),
+        '}' => Some('{'),
+        ']' => Some('['),
+        '>' => Some('<'),
+        _ => None,
+    }
+}
+
+/// Get the width of the least indented line in the body of `<<~` here documents.
+///
+/// Lines which consist of only spaces and tabs are ignored. A tab advances to the next
+/// multiple of 8 columns.
+pub(crate) fn heredoc_indent(body: &str) -> usize {
+    body.lines()
+        .filter_map(|line| {
+            let mut width = 0;
+            for c in line.chars() {
+                match c {
+                    ' ' => width += 1,
+                    '\t' => width = (width / 8 + 1) * 8,
+                    _ => return Some(width),
+                }
+            }
+            None
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+/// Get the byte length of the indentation of *line* up to *width* columns.
+///
+/// A tab which goes beyond *width* is not removed.
+pub(crate) fn indent_len(line: &str, width: usize) -> usize {
+    let mut col = 0;
+    for (i, c) in line.char_indices() {
+        col = match c {
+            ' ' => col + 1,
+            '\t' => (col / 8 + 1) * 8,
+            _ => return i,
+        };
+        if col > width {
+            return i;
+        }
+    }
+    line.len()
+}
+
 fn check_reserved(maybe_reserved: &str) -> Option<Reserved> {
     for reserved in all::<Reserved>() {
         if maybe_reserved == reserved.to_str() {
@@ -23,14 +87,289 @@ fn check_reserved(maybe_reserved: &str) -> Option<Reserved> {
     None
 }
 
+/// Get the shebang line (`#!...`) at the top of *code*, without the newline.
+///
+/// The lexer skips the line as a comment.
+pub(crate) fn shebang_line(code: &str) -> Option<&str> {
+    if !code.starts_with("#!") {
+        return None;
+    }
+    let line = code.split('\n').next().unwrap();
+    Some(line.strip_suffix('\r').unwrap_or(line))
+}
+
+/// Magic comments in the leading comment lines of the code.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct MagicComments {
+    /// `# frozen_string_literal: true`
+    pub frozen_string_literal: Option<bool>,
+    /// `# encoding: UTF-8` (or `coding:`), which is effective only in the first two lines.
+    pub encoding: Option<String>,
+}
+
+/// Read magic comments in *code*.
+///
+/// Magic comments must appear before any code. Keys are case-insensitive and `-` in them is
+/// same as `_`, and the emacs style `# -*- key: value; key: value -*-` is also accepted.
+pub(crate) fn read_magic_comments(code: &str) -> MagicComments {
+    let mut magic = MagicComments::default();
+    for (i, line) in code.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (i == 0 && line.starts_with("#!")) {
+            continue;
+        }
+        let comment = match line.strip_prefix('#') {
+            Some(comment) => comment.trim(),
+            None => break,
+        };
+        let comment = comment
+            .trim_start_matches("-*-")
+            .trim_end_matches("-*-")
+            .trim();
+        for (key, value) in comment.split(';').filter_map(|pair| pair.split_once(':')) {
+            let key = key.trim().replace('-', "_").to_ascii_lowercase();
+            let value = value.trim();
+            match key.as_str() {
+                "frozen_string_literal" => {
+                    if value.eq_ignore_ascii_case("true") {
+                        magic.frozen_string_literal = Some(true);
+                    } else if value.eq_ignore_ascii_case("false") {
+                        magic.frozen_string_literal = Some(false);
+                    }
+                }
+                "encoding" | "coding" if i < 2 && !value.is_empty() => {
+                    magic.encoding = Some(value.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+    magic
+}
+
+/// The kind of a literal which has interpolations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InterpolatedKind {
+    String,
+    Regex,
+    Command,
+}
+
+/// A literal whose interpolation is being read by `Tokens`.
+#[derive(Debug, Clone)]
+struct Interpolated {
+    kind: InterpolatedKind,
+    term: Option<char>,
+    level: usize,
+    /// nesting level of braces in the interpolation.
+    braces: usize,
+}
+
+/// The iterator of `Lexer::tokens()`.
+struct Tokens<'a> {
+    lexer: Lexer<'a>,
+    /// the previous token ends an operand, so `/`, `%`, `:` and `<<` are operators.
+    operand_end: bool,
+    /// the previous token is an identifier. (may be a command like `puts /re/`)
+    prev_ident: bool,
+    /// literals whose interpolations are being read.
+    literals: Vec<Interpolated>,
+    /// the interpolation was finished, and the rest of the literal follows.
+    resume: bool,
+    finished: bool,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token, LexerErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.read_token() {
+            Ok(tok) if tok.is_eof() => {
+                self.finished = true;
+                None
+            }
+            Ok(tok) => {
+                if !matches!(tok.kind, TokenKind::Whitespace | TokenKind::Comment(_)) {
+                    self.operand_end = ends_operand(&tok.kind);
+                    self.prev_ident = matches!(tok.kind, TokenKind::Ident(_));
+                }
+                Some(Ok(tok))
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<'a> Tokens<'a> {
+    fn read_token(&mut self) -> Result<Token, LexerErr> {
+        if std::mem::take(&mut self.resume) {
+            let lit = self.literals.pop().unwrap();
+            let open = lit.term.and_then(open_delimiter);
+            let tok = match lit.kind {
+                InterpolatedKind::String => self
+                    .lexer
+                    .read_string_literal_double(open, lit.term, lit.level)?,
+                InterpolatedKind::Regex => {
+                    self.lexer.get_regexp(open, lit.term.unwrap(), lit.level)?
+                }
+                InterpolatedKind::Command => {
+                    self.lexer.read_command_literal(open, lit.term, lit.level)?
+                }
+            };
+            self.push_literal(&tok);
+            return Ok(tok);
+        }
+        let tok = self.lexer.get_token()?;
+        let tok = if self.operand_head(&tok) {
+            self.read_operand(tok)?
+        } else {
+            tok
+        };
+        self.push_literal(&tok);
+        if let Some(lit) = self.literals.last_mut() {
+            match tok.kind {
+                TokenKind::Punct(Punct::LBrace) => lit.braces += 1,
+                TokenKind::Punct(Punct::RBrace) => {
+                    lit.braces = lit.braces.saturating_sub(1);
+                    self.resume = lit.braces == 0;
+                }
+                // `#@foo`, `#$foo` and `#@@foo`
+                TokenKind::InstanceVar(_) | TokenKind::GlobalVar(_) | TokenKind::ClassVar(_)
+                    if lit.braces == 0 =>
+                {
+                    self.resume = true;
+                }
+                _ => {}
+            }
+        }
+        Ok(tok)
+    }
+
+    /// Examine if *tok* is at the beginning of an operand.
+    fn operand_head(&self, tok: &Token) -> bool {
+        if !self.operand_end {
+            return true;
+        }
+        // `puts /re/`
+        let space_before =
+            self.lexer.code[..tok.loc.0].ends_with(|ch: char| ch.is_ascii_whitespace());
+        self.prev_ident && space_before && !self.lexer.trailing_space()
+    }
+
+    /// Read a literal which begins with *tok* at the beginning of an operand.
+    fn read_operand(&mut self, tok: Token) -> Result<Token, LexerErr> {
+        let lexer = &mut self.lexer;
+        match tok.kind {
+            TokenKind::Punct(Punct::Div) => lexer.get_regexp(None, '/', 0),
+            TokenKind::Punct(Punct::Rem) if !lexer.trailing_space() => lexer.get_percent_notation(),
+            TokenKind::Punct(Punct::Colon) if !lexer.trailing_space() => {
+                let save = lexer.save_state();
+                match lexer.read_symbol_literal()? {
+                    Some((id, loc)) => Ok(Annot::new(TokenKind::Symbol(id), tok.loc.merge(loc))),
+                    None => {
+                        lexer.restore_state(save);
+                        Ok(tok)
+                    }
+                }
+            }
+            TokenKind::Punct(Punct::Shl) if !lexer.trailing_space() => {
+                let HeredocBody {
+                    mode,
+                    squiggly,
+                    start,
+                    end,
+                    ..
+                } = lexer.read_heredocument()?;
+                let body = &lexer.code[start..end];
+                let indent = if squiggly { heredoc_indent(body) } else { 0 };
+                let body: String = body
+                    .split_inclusive('\n')
+                    .map(|line| &line[indent_len(line, indent)..])
+                    .collect();
+                let loc = Loc(tok.loc.0, lexer.pos - 1);
+                Ok(match mode {
+                    ParseMode::Command => Annot::new(TokenKind::CommandLit(body), loc),
+                    _ => Annot::new(TokenKind::StringLit(body.into()), loc),
+                })
+            }
+            _ => Ok(tok),
+        }
+    }
+
+    /// Remember the literal if *tok* begins an interpolation.
+    fn push_literal(&mut self, tok: &Token) {
+        let (kind, term, level) = match tok.kind {
+            TokenKind::OpenString(_, term, level) => (InterpolatedKind::String, term, level),
+            TokenKind::OpenRegex(_, term, level) => (InterpolatedKind::Regex, Some(term), level),
+            TokenKind::OpenCommand(_, term, level) => (InterpolatedKind::Command, term, level),
+            _ => return,
+        };
+        self.literals.push(Interpolated {
+            kind,
+            term,
+            level,
+            braces: 0,
+        });
+    }
+}
+
+/// Examine if a token of *kind* can be the end of an operand.
+fn ends_operand(kind: &TokenKind) -> bool {
+    match kind {
+        TokenKind::Ident(_)
+        | TokenKind::NumberedParam(..)
+        | TokenKind::InstanceVar(_)
+        | TokenKind::GlobalVar(_)
+        | TokenKind::SpecialVar(_)
+        | TokenKind::ClassVar(_)
+        | TokenKind::Const(_)
+        | TokenKind::IntegerLit(_)
+        | TokenKind::BignumLit(_)
+        | TokenKind::FloatLit(_)
+        | TokenKind::RationalLit(_)
+        | TokenKind::ImaginaryLit(_)
+        | TokenKind::StringLit(_)
+        | TokenKind::Regex(..)
+        | TokenKind::CommandLit(_)
+        | TokenKind::PercentNotation(..)
+        | TokenKind::Symbol(_) => true,
+        TokenKind::Reserved(reserved) => *reserved == Reserved::End,
+        TokenKind::Punct(punct) => {
+            matches!(punct, Punct::RParen | Punct::RBracket | Punct::RBrace)
+        }
+        _ => false,
+    }
+}
+
+/// Whether the lexer emits trivia tokens or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LexerMode {
+    /// skip whitespaces and comments. (used by the parser)
+    #[default]
+    Default,
+    /// emit `TokenKind::Whitespace` and `TokenKind::Comment`.
+    WithTrivia,
+}
+
 #[derive(Debug, Clone)]
-pub(crate) struct Lexer<'a> {
+pub struct Lexer<'a> {
     token_start_pos: usize,
     pos: usize,
     heredoc_pos: usize,
     buf: Option<Token>,
     buf_skip_lt: Option<Token>,
-    pub code: &'a str,
+    /// the width of the indentation to be removed from each line of `<<~` here documents.
+    heredoc_indent: usize,
+    mode: LexerMode,
+    /// the start of the data section after `__END__`.
+    data_pos: Option<usize>,
+    pub(crate) code: &'a str,
 }
 
 #[cfg(test)]
@@ -62,6 +401,9 @@ impl<'a> Lexer<'a> {
             heredoc_pos: 0,
             buf: None,
             buf_skip_lt: None,
+            heredoc_indent: 0,
+            mode: LexerMode::Default,
+            data_pos: None,
             code,
         }
     }
@@ -73,10 +415,26 @@ impl<'a> Lexer<'a> {
             heredoc_pos: 0,
             buf: None,
             buf_skip_lt: None,
+            heredoc_indent: 0,
+            mode: LexerMode::Default,
+            data_pos: None,
             code: &self.code[..end],
         }
     }
 
+    /// Remove the indentation of *width* columns from each line of the body of `<<~`
+    /// here documents.
+    pub(crate) fn set_heredoc_indent(&mut self, width: usize) {
+        self.heredoc_indent = width;
+        self.skip_heredoc_indent();
+    }
+
+    fn skip_heredoc_indent(&mut self) {
+        if self.heredoc_indent != 0 {
+            self.pos += indent_len(&self.code[self.pos..], self.heredoc_indent);
+        }
+    }
+
     fn error_unexpected(&self, pos: usize) -> LexerErr {
         let loc = Loc(pos, pos);
         LexerErr(
@@ -111,6 +469,42 @@ impl<'a> Lexer<'a> {
         self.code[0..=pos].chars().filter(|ch| *ch == '\n').count() + 1
     }
 
+    /// Iterate over the tokens in *code*.
+    ///
+    /// The iteration finishes at EOF, or after an error is returned. Line terminators are
+    /// also returned as `TokenKind::LineTerm`.
+    ///
+    /// The lexer does not know the syntactic context, so `/`, `%`, `:` and `<<` are read as
+    /// a regexp, a percent literal, a symbol and a here document only at the beginning of
+    /// an operand. (e.g. after an operator, `(` or a line terminator, or after an identifier
+    /// followed by a space like `puts /re/`) This is the same as the parser in most cases,
+    /// but not for ambiguous code like `x /re/` where `x` is a local variable.
+    ///
+    /// A here document is returned as a `TokenKind::StringLit` of the raw body (or a
+    /// `TokenKind::CommandLit` for `` <<`EOS` ``) at the location of `<<EOS`, and the body is
+    /// skipped. Interpolations in strings, regexps and commands are returned as the tokens of
+    /// the embedded code between `TokenKind::OpenString` etc. and the rest of the literal.
+    pub fn tokens(code: &'a str) -> impl Iterator<Item = Result<Token, LexerErr>> + 'a {
+        Self::tokens_with_mode(code, LexerMode::Default)
+    }
+
+    /// Iterate over the tokens in *code* in *mode*. See `tokens()`.
+    pub fn tokens_with_mode(
+        code: &'a str,
+        mode: LexerMode,
+    ) -> impl Iterator<Item = Result<Token, LexerErr>> + 'a {
+        let mut lexer = Lexer::new(code);
+        lexer.mode = mode;
+        Tokens {
+            lexer,
+            operand_end: false,
+            prev_ident: false,
+            literals: vec![],
+            resume: false,
+            finished: false,
+        }
+    }
+
     pub(crate) fn get_token(&mut self) -> Result<Token, LexerErr> {
         self.buf = None;
         self.buf_skip_lt = None;
@@ -146,6 +540,20 @@ impl<'a> Lexer<'a> {
         Ok(tok)
     }
 
+    /// Peek the *n*-th upcoming token (0 is the next token) without consuming.
+    pub(crate) fn peek_n(&mut self, n: usize) -> Result<Token, LexerErr> {
+        let save = self.save_state();
+        let mut res = self.read_token();
+        for _ in 0..n {
+            match res {
+                Ok(ref tok) if !tok.is_eof() => res = self.read_token(),
+                _ => break,
+            }
+        }
+        self.restore_state(save);
+        res
+    }
+
     /// Examine if the next char is a whitespace or not.
     pub(crate) fn trailing_space(&self) -> bool {
         match self.peek() {
@@ -175,15 +583,29 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    /// Get token as a regular expression.
-    pub(crate) fn get_regexp(&mut self) -> Result<Token, LexerErr> {
-        match self.read_regexp_sub()? {
+    /// Read a regular expression literal until *term*.
+    ///
+    /// *open* is the opening delimiter which can be nested. (e.g. `{` for `%r{..}`)
+    pub(crate) fn get_regexp(
+        &mut self,
+        open: Option<char>,
+        term: char,
+        level: usize,
+    ) -> Result<Token, LexerErr> {
+        match self.read_regexp_sub(open, term, level)? {
             InterpolateState::FinishedRegex(s, op) => Ok(self.new_regexlit(s, op)),
-            InterpolateState::NewInterpolation(s, _) => Ok(self.new_open_reg(s.as_string()?)),
+            InterpolateState::NewInterpolation(s, level) => {
+                Ok(self.new_open_reg(s.as_string()?, term, level))
+            }
             _ => unreachable!(),
         }
     }
 
+    /// The start of the data section if `__END__` was found.
+    pub(crate) fn data_pos(&self) -> Option<usize> {
+        self.data_pos
+    }
+
     pub(crate) fn save_state(&self) -> (usize, usize) {
         (self.token_start_pos, self.pos)
     }
@@ -204,7 +626,11 @@ impl<'a> Lexer<'a> {
     fn read_token(&mut self) -> Result<Token, LexerErr> {
         loop {
             self.token_start_pos = self.pos;
-            if let Some(tok) = self.skip_whitespace() {
+            if self.mode == LexerMode::WithTrivia {
+                if let Some(tok) = self.read_trivia() {
+                    return Ok(tok);
+                }
+            } else if let Some(tok) = self.skip_whitespace() {
                 return Ok(tok);
             };
             let pos = self.pos;
@@ -219,7 +645,13 @@ impl<'a> Lexer<'a> {
                 return self.read_number_literal(ch);
             } else if ch.is_ascii_punctuation() {
                 match ch {
-                    '#' => self.goto_eol(),
+                    '#' => {
+                        self.goto_eol();
+                        if self.mode == LexerMode::WithTrivia {
+                            let comment = self.code[pos..self.pos].to_string();
+                            return Ok(Annot::new(TokenKind::Comment(comment), self.cur_loc()));
+                        }
+                    }
                     '"' => return self.read_string_literal_double(None, Some('\"'), 0),
                     '`' => return self.read_command_literal(None, Some('`'), 0),
                     '\'' => {
@@ -458,6 +890,16 @@ impl<'a> Lexer<'a> {
         };
         self.consume_ident();
         let tok = self.current_slice();
+        if var_kind == VarKind::Identifier && tok == "__END__" {
+            if let Some(data_pos) = self.end_marker() {
+                self.data_pos = Some(data_pos);
+                self.pos = self.code.len();
+                return Ok(Annot::new(
+                    TokenKind::Eof,
+                    Loc(self.token_start_pos, self.token_start_pos),
+                ));
+            }
+        }
         if var_kind == VarKind::Identifier && ch == Some('_') {
             let mut iter = tok.chars();
             iter.next();
@@ -577,6 +1019,21 @@ impl<'a> Lexer<'a> {
         ))
     }
 
+    /// Consume the backtick method name in `` def `(cmd) ``, which would be read as
+    /// a command literal by `get_token()`.
+    pub(crate) fn consume_backtick_method_name(&mut self) -> Option<Loc> {
+        self.flush();
+        let save = self.save_state();
+        while self.consume_whitespace() || self.consume_newline() {}
+        let start = self.pos;
+        if self.consume('`') {
+            Some(Loc(start, start))
+        } else {
+            self.restore_state(save);
+            None
+        }
+    }
+
     pub(crate) fn read_symbol_literal(&mut self) -> Result<Option<(String, Loc)>, LexerErr> {
         self.flush();
         self.token_start_pos = self.pos;
@@ -627,6 +1084,7 @@ impl<'a> Lexer<'a> {
         };
         let mut s = ch.to_string();
         let mut float_flag = false;
+        let mut exp_flag = false;
         loop {
             if let Some(ch) = self.consume_numeric() {
                 s.push(ch);
@@ -665,8 +1123,16 @@ impl<'a> Lexer<'a> {
                 }
             }
             float_flag = true;
+            exp_flag = true;
         }
-        let number = if float_flag {
+        // `1r` and `1.5r` are rational literals. (`1e3r` is not allowed.)
+        let is_rational = !exp_flag
+            && self.peek() == Some('r')
+            && !matches!(self.peek2(), Some(ch) if ch != 'i' && (ch.is_ascii_alphanumeric() || ch == '_'));
+        let number = if is_rational {
+            self.get()?;
+            NReal::Rational(Self::rational_from_decimal(&s))
+        } else if float_flag {
             match s.parse::<f64>() {
                 Ok(f) => NReal::Float(f),
                 Err(err) => return Err(Self::error_parse(&format!("{:?}", err), self.pos)),
@@ -687,10 +1153,19 @@ impl<'a> Lexer<'a> {
                 NReal::Bignum(n) => Ok(self.new_bignumlit(n)),
                 NReal::Integer(i) => Ok(self.new_numlit(i)),
                 NReal::Float(f) => Ok(self.new_floatlit(f)),
+                NReal::Rational(r) => Ok(self.new_rationallit(r)),
             }
         }
     }
 
+    /// Convert a decimal literal *s* like "1.5" to the exact rational number.
+    fn rational_from_decimal(s: &str) -> BigRational {
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        let numer = BigInt::parse_bytes(format!("{}{}", int, frac).as_bytes(), 10).unwrap();
+        let denom = num::pow(BigInt::from(10), frac.len());
+        BigRational::new(numer, denom)
+    }
+
     /// Read hexadecimal number.
     fn read_hex_number(&mut self) -> Result<Token, LexerErr> {
         let start_pos = self.pos;
@@ -834,6 +1309,7 @@ impl<'a> Lexer<'a> {
                     if self.heredoc_pos > self.pos {
                         self.pos = self.heredoc_pos;
                     }
+                    self.skip_heredoc_indent();
                 }
                 c => s.push_char(c),
             }
@@ -869,8 +1345,9 @@ impl<'a> Lexer<'a> {
                         continue;
                     };
                     let c = self.get()?;
-                    if c == '\'' {
-                        s.push('\'');
+                    if c == '\'' || c == term || open == Some(c) {
+                        // escaped delimiter
+                        s.push(c);
                     } else if c == '\\' {
                         s.push('\\');
                         if escape_backslash {
@@ -963,15 +1440,26 @@ impl<'a> Lexer<'a> {
     }
 
     /// Scan as regular expression.
-    fn read_regexp_sub(&mut self) -> Result<InterpolateState, LexerErr> {
+    fn read_regexp_sub(
+        &mut self,
+        open: Option<char>,
+        term: char,
+        mut level: usize,
+    ) -> Result<InterpolateState, LexerErr> {
         let mut s = "".to_string();
         let mut char_class = 0;
         loop {
-            match self.get()? {
-                '/' => {
+            let c = self.get()?;
+            if c == term {
+                if level == 0 {
                     let op = self.check_postfix();
                     return Ok(InterpolateState::FinishedRegex(s, op));
                 }
+                level -= 1;
+            } else if open == Some(c) {
+                level += 1;
+            }
+            match c {
                 '[' => {
                     char_class += 1;
                     s.push('[');
@@ -1024,7 +1512,7 @@ impl<'a> Lexer<'a> {
                 }
                 '#' => match self.peek() {
                     Some(ch) if ch == '{' || ch == '$' || ch == '@' => {
-                        return Ok(InterpolateState::NewInterpolation(s.into(), 0))
+                        return Ok(InterpolateState::NewInterpolation(s.into(), level))
                     }
                     _ => s.push('#'),
                 },
@@ -1068,10 +1556,8 @@ impl<'a> Lexer<'a> {
                 Ok(self.new_stringlit(s))
             }
             Some('Q') | None => Ok(self.read_string_literal_double(open, Some(term), 0)?),
-            Some('r') => {
-                let s = self.read_string_literal_single(open, term, true)?;
-                Ok(self.new_percent('r', s))
-            }
+            Some('r') => self.get_regexp(open, term, 0),
+            Some('x') => self.read_command_literal(open, Some(term), 0),
             Some(kind) => {
                 let s = self.read_string_literal_single(open, term, false)?;
                 Ok(self.new_percent(kind, s))
@@ -1129,12 +1615,11 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
-    pub(crate) fn read_heredocument(&mut self) -> Result<(ParseMode, usize, usize), LexerErr> {
+    pub(crate) fn read_heredocument(&mut self) -> Result<HeredocBody, LexerErr> {
         #[derive(Clone, PartialEq)]
         enum TermMode {
             Normal,
             AllowIndent,
-            // TODO currently, not supported.
             Squiggly,
         }
 
@@ -1206,7 +1691,13 @@ impl<'a> Lexer<'a> {
         }
         self.heredoc_pos = self.pos;
         self.restore_state(save);
-        Ok((parse_mode, heredoc_start, heredoc_end))
+        Ok(HeredocBody {
+            mode: parse_mode,
+            tag: self.code[delimiter].to_string(),
+            squiggly: term_mode == TermMode::Squiggly,
+            start: heredoc_start,
+            end: heredoc_end,
+        })
     }
 }
 
@@ -1378,6 +1869,47 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Read a newline as LineTerm, or a run of whitespaces and continuation lines as
+    /// Whitespace.
+    fn read_trivia(&mut self) -> Option<Token> {
+        if self.consume_newline() {
+            return Some(self.new_line_term());
+        }
+        let mut found = false;
+        while self.peek() != Some('\n') && (self.consume_cont_line() || self.consume_whitespace()) {
+            found = true;
+        }
+        if found {
+            Some(Annot::new(TokenKind::Whitespace, self.cur_loc()))
+        } else {
+            None
+        }
+    }
+
+    /// Examine if the current token `__END__` is on a line by itself, and return the start of
+    /// the following line.
+    fn end_marker(&self) -> Option<usize> {
+        let line_head = self.code[..self.token_start_pos]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        if !self.code[line_head..self.token_start_pos]
+            .chars()
+            .all(|ch| ch == ' ' || ch == '\t')
+        {
+            return None;
+        }
+        let rest = &self.code[self.pos..];
+        if rest.is_empty() {
+            Some(self.pos)
+        } else if rest.starts_with('\n') {
+            Some(self.pos + 1)
+        } else if rest.starts_with("\r\n") {
+            Some(self.pos + 2)
+        } else {
+            None
+        }
+    }
+
     fn goto_eol(&mut self) {
         loop {
             match self.peek() {
@@ -1442,6 +1974,10 @@ impl<'a> Lexer<'a> {
         Token::new_imaginarylit(num, self.cur_loc())
     }
 
+    fn new_rationallit(&self, num: BigRational) -> Token {
+        Token::new_rationallit(num, self.cur_loc())
+    }
+
     fn new_stringlit(&self, string: impl Into<RubyString>) -> Token {
         Annot::new(TokenKind::StringLit(string.into()), self.cur_loc())
     }
@@ -1465,8 +2001,8 @@ impl<'a> Lexer<'a> {
         Token::new_open_string(s, delimiter, level, self.cur_loc())
     }
 
-    fn new_open_reg(&self, s: String) -> Token {
-        Token::new_open_reg(s, self.cur_loc())
+    fn new_open_reg(&self, s: String, delimiter: char, level: usize) -> Token {
+        Token::new_open_reg(s, delimiter, level, self.cur_loc())
     }
 
     fn new_open_command(&self, s: String, delimiter: Option<char>, level: usize) -> Token {
@@ -1590,6 +2126,148 @@ mod test {
         };
     }
 
+    #[test]
+    fn tokens() {
+        let tokens: Vec<_> = Lexer::tokens("def foo(x); x + 1; end")
+            .map(|tok| tok.unwrap())
+            .collect();
+        let expected = vec![
+            Token![Reserved(Reserved::Def), 0, 2],
+            Token![Ident("foo"), 4, 6],
+            Token![Punct(Punct::LParen), 7, 7],
+            Token![Ident("x"), 8, 8],
+            Token![Punct(Punct::RParen), 9, 9],
+            Token![Punct(Punct::Semi), 10, 10],
+            Token![Ident("x"), 12, 12],
+            Token![Punct(Punct::Plus), 14, 14],
+            Token![NumLit(1), 16, 16],
+            Token![Punct(Punct::Semi), 17, 17],
+            Token![Reserved(Reserved::End), 19, 21],
+        ];
+        assert_eq!(expected, tokens);
+        let mut tokens = Lexer::tokens("a = \"abc");
+        assert_eq!(Token![Ident("a"), 0, 0], tokens.next().unwrap().unwrap());
+        assert!(tokens.nth(1).unwrap().is_err());
+        assert!(tokens.next().is_none());
+        assert_eq!(0, Lexer::tokens("").count());
+    }
+
+    #[test]
+    fn tokens_literals() {
+        fn kinds(code: &str) -> Vec<TokenKind> {
+            Lexer::tokens(code).map(|tok| tok.unwrap().kind).collect()
+        }
+        assert_eq!(
+            vec![
+                TokenKind::Ident("x".to_string()),
+                TokenKind::Punct(Punct::Assign),
+                TokenKind::Regex("re".to_string(), "mi".to_string()),
+                TokenKind::Punct(Punct::Div),
+                TokenKind::IntegerLit(2),
+            ],
+            kinds("x = /re/i / 2")
+        );
+        assert_eq!(
+            TokenKind::Regex("re".to_string(), "m".to_string()),
+            kinds("puts /re/")[1]
+        );
+        assert_eq!(
+            vec![
+                TokenKind::Punct(Punct::LParen),
+                TokenKind::PercentNotation('w', "a b".to_string()),
+                TokenKind::Punct(Punct::RParen),
+                TokenKind::Punct(Punct::Rem),
+                TokenKind::Ident("w".to_string()),
+            ],
+            kinds("(%w[a b]) % w")
+        );
+        assert_eq!(
+            vec![
+                TokenKind::Ident("f".to_string()),
+                TokenKind::Symbol("sym".to_string()),
+                TokenKind::Punct(Punct::Comma),
+                TokenKind::Ident("a".to_string()),
+                TokenKind::Punct(Punct::Colon),
+                TokenKind::Punct(Punct::Scope),
+                TokenKind::Const("A".to_string()),
+            ],
+            kinds("f :sym, a: ::A")
+        );
+        let tokens: Vec<_> = Lexer::tokens("x = <<~EOS + y\n  a\n    b\n  EOS\nz")
+            .map(|tok| tok.unwrap())
+            .collect();
+        assert_eq!(Token![StringLit("a\n  b\n"), 4, 9], tokens[2]);
+        assert_eq!(
+            vec![
+                TokenKind::Punct(Punct::Plus),
+                TokenKind::Ident("y".to_string()),
+                TokenKind::LineTerm,
+                TokenKind::Ident("z".to_string()),
+            ],
+            tokens[3..]
+                .iter()
+                .map(|tok| tok.kind.clone())
+                .collect::<Vec<_>>()
+        );
+        // the rest of the literal after interpolations.
+        assert_eq!(
+            vec![
+                TokenKind::OpenString("a".to_string(), Some('"'), 0),
+                TokenKind::Punct(Punct::LBrace),
+                TokenKind::Punct(Punct::LBrace),
+                TokenKind::Punct(Punct::RBrace),
+                TokenKind::Punct(Punct::RBrace),
+                TokenKind::OpenString("b".to_string(), Some('"'), 0),
+                TokenKind::InstanceVar("@c".to_string()),
+                TokenKind::StringLit("-d".to_string().into()),
+                TokenKind::Punct(Punct::Div),
+                TokenKind::Ident("e".to_string()),
+            ],
+            kinds("\"a#{{}}b#@c-d\" / e")
+        );
+    }
+
+    #[test]
+    fn trivia_tokens() {
+        let code = "x = 1 # one\n# two\n  y \\\n+ 2";
+        let tokens: Vec<_> = Lexer::tokens_with_mode(code, LexerMode::WithTrivia)
+            .map(|tok| tok.unwrap())
+            .collect();
+        let comments: Vec<_> = tokens
+            .iter()
+            .filter_map(|tok| match &tok.kind {
+                TokenKind::Comment(s) => Some((s.as_str(), tok.loc)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            vec![("# one", Loc(6, 10)), ("# two", Loc(12, 16))],
+            comments
+        );
+        assert_eq!(TokenKind::Whitespace, tokens[1].kind);
+        // trivia tokens cover the whole code.
+        let text: String = tokens
+            .iter()
+            .map(|tok| &code[tok.loc.0..=tok.loc.1])
+            .collect();
+        assert_eq!(code, text);
+        assert!(Lexer::tokens(code).all(|tok| !matches!(
+            tok.unwrap().kind,
+            TokenKind::Whitespace | TokenKind::Comment(_)
+        )));
+    }
+
+    #[test]
+    fn peek_n() {
+        let mut lexer = Lexer::new("a = 5\n");
+        assert_eq!(Token![Ident("a"), 0, 0], lexer.peek_n(0).unwrap());
+        assert_eq!(Token![Punct(Punct::Assign), 2, 2], lexer.peek_n(1).unwrap());
+        assert_eq!(Token![NumLit(5), 4, 4], lexer.peek_n(2).unwrap());
+        assert!(lexer.peek_n(5).unwrap().is_eof());
+        assert_eq!(Token![Ident("a"), 0, 0], lexer.get_token().unwrap());
+        assert_eq!(Token![NumLit(5), 4, 4], lexer.peek_n(1).unwrap());
+    }
+
     #[test]
     fn string_literal1() {
         let program = r#""""#;
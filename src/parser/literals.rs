@@ -41,9 +41,10 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         let mut nodes = vec![Node::new_string(s, start_loc)];
         loop {
             self.parse_template(&mut nodes)?;
+            let open = delimiter.and_then(open_delimiter);
             let tok = self
                 .lexer
-                .read_string_literal_double(None, delimiter, level)?;
+                .read_string_literal_double(open, delimiter, level)?;
             let mut loc = tok.loc();
             match tok.kind {
                 TokenKind::StringLit(mut name) => {
@@ -102,13 +103,34 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                         .collect();
                     Ok(Node::new_array(ary, tok.loc))
                 }
-                'r' => {
-                    let ary = vec![Node::new_string(content.into(), loc)];
-                    let op = self.lexer.check_postfix();
-                    Ok(Node::new_regexp(ary, op, tok.loc))
+                'W' => {
+                    let ary = self.parse_percent_words(loc)?;
+                    Ok(Node::new_array(ary, tok.loc))
+                }
+                'I' => {
+                    let mut ary = vec![];
+                    for node in self.parse_percent_words(loc)? {
+                        let loc = node.loc;
+                        let node = match node.kind {
+                            NodeKind::String(s) => Node::new_symbol(s, loc),
+                            _ => Node::new_mcall_noarg(node, "to_sym".to_string(), false, loc),
+                        };
+                        ary.push(node);
+                    }
+                    Ok(Node::new_array(ary, tok.loc))
                 }
                 _ => Err(error_unexpected(loc, "Unsupported % notation.")),
             }
+        } else if let TokenKind::Regex(..) | TokenKind::OpenRegex(..) = tok.kind {
+            // %r{..}
+            self.parse_regexp_literal(tok, loc)
+        } else if let TokenKind::CommandLit(s) = tok.kind {
+            // %x{..}
+            let content = Node::new_string(s.into(), loc);
+            Ok(Node::new_command(content))
+        } else if let TokenKind::OpenCommand(s, term, level) = tok.kind {
+            let content = self.parse_interporated_string_literal(s.into(), term, level)?;
+            Ok(Node::new_command(content))
         } else if let TokenKind::StringLit(s) = tok.kind {
             Ok(Node::new_string(s, loc))
         } else if let TokenKind::OpenString(s, term, level) = tok.kind {
@@ -124,32 +146,82 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             let loc = self.prev_loc();
             return Err(error_unexpected(loc, "Unexpectd <<."));
         }
-        let (mode, start, end) = self.lexer.read_heredocument()?;
+        let HeredocBody {
+            mode,
+            tag,
+            squiggly,
+            start,
+            end,
+        } = self.lexer.read_heredocument()?;
+        let mut inner_heredocs = vec![];
+        // `<<~` removes the indentation of the least indented line from each line.
+        let indent = if squiggly {
+            heredoc_indent(&self.lexer.code[start..end])
+        } else {
+            0
+        };
         let node = match mode {
-            ParseMode::Single => Node::new_string(
-                self.lexer.code[start..end].to_string().into(),
-                Loc(start, end),
-            ),
+            ParseMode::Single => {
+                let body: String = self.lexer.code[start..end]
+                    .split_inclusive('\n')
+                    .map(|line| &line[indent_len(line, indent)..])
+                    .collect();
+                Node::new_string(body.into(), Loc(start, end))
+            }
             ParseMode::Double => {
                 let mut parser = self.new_with_range(start, end);
+                parser.lexer.set_heredoc_indent(indent);
                 let res = parser.here_double();
+                inner_heredocs = parser.heredocs;
                 res?
             }
             ParseMode::Command => {
                 let mut parser = self.new_with_range(start, end);
+                parser.lexer.set_heredoc_indent(indent);
                 let res = parser.here_command();
+                inner_heredocs = parser.heredocs;
                 res?
             }
         };
+        self.heredocs.push(HeredocInfo::new(
+            tag,
+            squiggly,
+            &node,
+            Loc(start, std::cmp::max(start + 1, end) - 1),
+        ));
+        self.heredocs.append(&mut inner_heredocs);
         Ok(node)
     }
 
+    /// Parse the words of `%W[..]` and `%I[..]` as double-quoted strings.
+    ///
+    /// *loc* is the location of the whole literal. Each word is NodeKind::String or
+    /// NodeKind::InterporatedString.
+    fn parse_percent_words(&mut self, loc: Loc) -> Result<Vec<Node>, LexerErr> {
+        // skip `%`, the kind char and the opening delimiter.
+        let delimiter = self.lexer.code[loc.0 + 2..].chars().next().unwrap();
+        let start = loc.0 + 2 + delimiter.len_utf8();
+        let mut words = vec![];
+        for (start, end) in split_percent_words(self.lexer.code, start, loc.1) {
+            let lexer = self.lexer.new_with_range(start, end);
+            let lexer = std::mem::replace(&mut self.lexer, lexer);
+            let node = self.here_double();
+            self.lexer = lexer;
+            let mut node = node?;
+            node.loc = Loc(start, end - 1);
+            words.push(node);
+        }
+        Ok(words)
+    }
+
     fn here_double(&mut self) -> Result<Node, LexerErr> {
         let tok = self.lexer.read_string_literal_double(None, None, 0)?;
         let loc = tok.loc();
         let node = match tok.kind {
             TokenKind::StringLit(s) => Node::new_string(s, loc),
             TokenKind::OpenString(s, term, level) => {
+                // the first segment of the string is at *loc*.
+                self.prev_loc = loc;
                 self.parse_interporated_string_literal(s.into(), term, level)?
             }
             _ => unreachable!(),
@@ -183,7 +255,8 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             };
             let ident_loc = self.loc();
             let mut symbol_flag = false;
-            let key = match self.peek()?.can_be_symbol() {
+            let tok = self.peek()?;
+            let key = match tok.can_be_symbol() {
                 Some(id) => {
                     let save = self.save_state();
                     self.get().unwrap();
@@ -200,7 +273,21 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             if !symbol_flag {
                 self.expect_punct(Punct::FatArrow)?
             };
-            let value = self.parse_arg()?;
+            let value = match tok.kind {
+                // {key:} is a shorthand of {key: key}.
+                TokenKind::Ident(name) if symbol_flag && self.is_hash_shorthand()? => {
+                    match self.is_local_var(&name) {
+                        Some(outer) => Node::new_lvar(name, outer, ident_loc),
+                        None => {
+                            return Err(error_unexpected(
+                                ident_loc,
+                                format!("undefined local variable `{}'", name),
+                            ))
+                        }
+                    }
+                }
+                _ => self.parse_arg()?,
+            };
             kvp.push((key, value));
             if !self.consume_punct(Punct::Comma)? {
                 break;
@@ -210,6 +297,16 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         Ok(Node::new_hash(kvp, loc.merge(self.prev_loc())))
     }
 
+    /// Examine whether the value of the current pair in a hash literal is omitted.
+    fn is_hash_shorthand(&mut self) -> Result<bool, LexerErr> {
+        let tok = self.peek_no_term()?;
+        Ok(tok.is_line_term()
+            || matches!(
+                tok.kind,
+                TokenKind::Punct(Punct::Comma) | TokenKind::Punct(Punct::RBrace)
+            ))
+    }
+
     pub(super) fn parse_symbol(&mut self) -> Result<Node, LexerErr> {
         let loc = self.prev_loc();
         if self.lexer.trailing_space() {
@@ -236,8 +333,13 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
 
     pub(super) fn parse_regexp(&mut self) -> Result<Node, LexerErr> {
         let start_loc = self.prev_loc();
-        let tok = self.lexer.get_regexp()?;
-        let mut nodes = match tok.kind {
+        let tok = self.lexer.get_regexp(None, '/', 0)?;
+        self.parse_regexp_literal(tok, start_loc)
+    }
+
+    /// Parse the rest of a regexp literal which begins with *tok*.
+    fn parse_regexp_literal(&mut self, tok: Token, start_loc: Loc) -> Result<Node, LexerErr> {
+        let (mut nodes, mut term, mut level) = match tok.kind {
             TokenKind::Regex(s, op) => {
                 return Ok(Node::new_regexp(
                     vec![Node::new_string(s.into(), tok.loc)],
@@ -245,20 +347,25 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
                     tok.loc,
                 ));
             }
-            TokenKind::OpenRegex(s) => vec![Node::new_string(s.into(), tok.loc)],
+            TokenKind::OpenRegex(s, term, level) => {
+                (vec![Node::new_string(s.into(), tok.loc)], term, level)
+            }
             _ => unreachable!(),
         };
+        // the opening delimiter of %r{..}, %r(..), %r[..] and %r<..> can be nested.
+        let open = open_delimiter(term);
         loop {
             self.parse_template(&mut nodes)?;
-            let tok = self.lexer.get_regexp()?;
+            let tok = self.lexer.get_regexp(open, term, level)?;
             let loc = tok.loc();
             match tok.kind {
                 TokenKind::Regex(s, op) => {
                     nodes.push(Node::new_string(s.into(), loc));
                     return Ok(Node::new_regexp(nodes, op, start_loc.merge(loc)));
                 }
-                TokenKind::OpenRegex(s) => {
+                TokenKind::OpenRegex(s, next_term, next_level) => {
                     nodes.push(Node::new_string(s.into(), loc));
+                    (term, level) = (next_term, next_level);
                 }
                 _ => unreachable!(),
             }
@@ -299,6 +406,11 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
         };
         self.loop_stack.pop().unwrap();
         let lvar = self.scope.pop().unwrap().lvar;
+        if !params.is_empty() {
+            if let Some(loc) = lvar.numbered_param {
+                return Err(error_ordinary_param_defined(loc));
+            }
+        }
 
         Ok(Node::new_lambda(params, body, lvar, loc))
     }
@@ -332,11 +444,55 @@ impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
             prev_loc: Loc(0, 0),
             scope: vec![],
             loop_stack: vec![],
+            rescue_depth: self.rescue_depth,
             extern_context: None,
             suppress_acc_assign: false,
             suppress_mul_assign: false,
             suppress_do_block: false,
             defined_mode: false,
+            options: self.options.clone(),
+            heredocs: vec![],
+            errors: None,
+            warnings: vec![],
+        }
+    }
+}
+
+/// Split `code[start..end]` into whitespace-separated words, and return their byte ranges.
+///
+/// Escaped whitespaces and whitespaces in `#{..}` do not separate words.
+fn split_percent_words(code: &str, start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut words = vec![];
+    let mut word_start = None;
+    let mut escaped = false;
+    let mut depth = 0;
+    let mut prev = ' ';
+    for (i, c) in code[start..end].char_indices() {
+        let i = start + i;
+        if word_start.is_none() {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            word_start = Some(i);
         }
+        if escaped {
+            escaped = false;
+            prev = '\0';
+            continue;
+        }
+        if c == '\\' {
+            escaped = true;
+        } else if c == '{' && (prev == '#' || depth > 0) {
+            depth += 1;
+        } else if c == '}' && depth > 0 {
+            depth -= 1;
+        } else if c.is_ascii_whitespace() && depth == 0 {
+            words.push((word_start.take().unwrap(), i));
+        }
+        prev = c;
+    }
+    if let Some(word_start) = word_start {
+        words.push((word_start, end));
     }
+    words
 }
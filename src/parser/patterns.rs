@@ -0,0 +1,478 @@
+use super::*;
+
+impl<'a, OuterContext: LocalsContext> Parser<'a, OuterContext> {
+    /// Parse `in` clauses of `case`/`in`.
+    ///     case SUBJECT
+    ///     in PATTERN [(if | unless) GUARD] then BODY
+    ///     ..
+    ///     else BODY
+    ///     end
+    pub(super) fn parse_case_in(&mut self, subject: Node, loc: Loc) -> Result<Node, LexerErr> {
+        let mut arms = vec![];
+        while self.consume_reserved(Reserved::In)? {
+            let pattern = self.parse_pattern_top()?;
+            // guard expressions can refer the variables bound by the pattern.
+            let guard = if self.consume_reserved_no_skip_line_term(Reserved::If)? {
+                Some(self.parse_expr()?)
+            } else if self.consume_reserved_no_skip_line_term(Reserved::Unless)? {
+                let loc = self.prev_loc();
+                let cond = self.parse_expr()?;
+                Some(Node::new_unop(UnOp::Not, cond, loc))
+            } else {
+                None
+            };
+            self.parse_then()?;
+            let body = self.parse_comp_stmt()?;
+            arms.push(PatternArm::new(Some(pattern), guard, body));
+        }
+        if self.consume_reserved(Reserved::Else)? {
+            let body = self.parse_comp_stmt()?;
+            arms.push(PatternArm::new(None, None, body));
+        }
+        self.expect_reserved(Reserved::End)?;
+        let loc = loc.merge(self.prev_loc());
+        Ok(Node::new_pattern_match(subject, arms, loc))
+    }
+
+    /// Parse a top-level pattern of `in` clauses, where brackets of array patterns
+    /// can be omitted. (`in a, *rest`)
+    fn parse_pattern_top(&mut self) -> Result<Node, LexerErr> {
+        let loc = self.loc();
+        if self.is_hash_pattern_top()? {
+            return self.parse_hash_pattern(loc, false);
+        }
+        let first = self.parse_pattern_element()?;
+        let mut elements = vec![];
+        if self.consume_punct_no_term(Punct::Comma)? {
+            elements.push(first);
+            loop {
+                elements.push(self.parse_pattern_element()?);
+                if !self.consume_punct_no_term(Punct::Comma)? {
+                    break;
+                }
+            }
+        } else if let NodeKind::Splat(_) = first.kind {
+            elements.push(first);
+        } else {
+            return Ok(first);
+        }
+        self.new_array_pattern(elements, loc.merge(self.prev_loc()))
+    }
+
+    /// Parse the pattern of rightward assignment `expr => pattern`.
+    pub(super) fn parse_rightward_assign(&mut self, value: Node) -> Result<Node, LexerErr> {
+        let pattern = self.parse_pattern_top()?;
+        Ok(Node::new_rightward_assign(value, pattern))
+    }
+
+    /// Parse a pattern, which may be alternatives. (`pat | pat`)
+    fn parse_pattern(&mut self) -> Result<Node, LexerErr> {
+        let mut pattern = self.parse_pattern_primary()?;
+        if self.consume_punct_no_term(Punct::BitOr)? {
+            let mut patterns = vec![pattern];
+            loop {
+                patterns.push(self.parse_pattern_primary()?);
+                if !self.consume_punct_no_term(Punct::BitOr)? {
+                    break;
+                }
+            }
+            for pattern in &patterns {
+                check_alternative_bindings(pattern)?;
+            }
+            pattern = Node::new_alternative_pattern(patterns);
+        }
+        if !self.consume_punct_no_term(Punct::FatArrow)? {
+            return Ok(pattern);
+        }
+        // pat => var
+        let tok = self.get()?;
+        match tok.kind {
+            TokenKind::Ident(name) if !is_pseudo_variable(&name) => {
+                let outer = self.add_local_var_if_new(&name);
+                let binding = Node::new_lvar(name, outer, tok.loc);
+                Ok(Node::new_capture_pattern(pattern, binding))
+            }
+            _ => Err(error_unexpected(tok.loc, "Expect an identifier.")),
+        }
+    }
+
+    /// Parse an array pattern after `[`.
+    fn parse_array_pattern(&mut self, loc: Loc) -> Result<Node, LexerErr> {
+        let mut elements = vec![];
+        if !self.consume_punct(Punct::RBracket)? {
+            loop {
+                elements.push(self.parse_pattern_element()?);
+                if !self.consume_punct(Punct::Comma)? {
+                    self.expect_punct(Punct::RBracket)?;
+                    break;
+                }
+                if self.consume_punct(Punct::RBracket)? {
+                    break;
+                }
+            }
+        }
+        self.new_array_pattern(elements, loc.merge(self.prev_loc()))
+    }
+
+    /// Parse an element of array patterns, which can be a rest (`*rest` or `*`).
+    ///
+    /// A rest is returned as `NodeKind::Splat`.
+    fn parse_pattern_element(&mut self) -> Result<Node, LexerErr> {
+        if !self.consume_punct(Punct::Mul)? {
+            return self.parse_pattern();
+        }
+        let loc = self.prev_loc();
+        let binding = match self.peek_no_term()?.kind {
+            TokenKind::Ident(name) if !is_pseudo_variable(&name) => {
+                self.get()?;
+                let outer = self.add_local_var_if_new(&name);
+                Node::new_lvar(name, outer, self.prev_loc())
+            }
+            _ => Node::new_discard(loc),
+        };
+        Ok(Node::new_splat(binding, loc))
+    }
+
+    /// Build `NodeKind::ArrayPattern` from *elements* which may contain a rest.
+    ///
+    /// `NodeKind::FindPattern` is built if both of the first and the last elements are rests.
+    fn new_array_pattern(&mut self, mut elements: Vec<Node>, loc: Loc) -> Result<Node, LexerErr> {
+        let rests: Vec<_> = elements
+            .iter()
+            .enumerate()
+            .filter(|(_, elem)| matches!(elem.kind, NodeKind::Splat(_)))
+            .map(|(i, _)| i)
+            .collect();
+        let rest_binding = |elem: Node| match elem.kind {
+            NodeKind::Splat(binding) => *binding,
+            _ => unreachable!(),
+        };
+        match rests[..] {
+            [] => Ok(Node::new_array_pattern(elements, None, loc)),
+            [i] => {
+                let elem = std::mem::take(&mut elements[i]);
+                elements[i] = rest_binding(elem);
+                Ok(Node::new_array_pattern(elements, Some(i), loc))
+            }
+            [0, last] if last == elements.len() - 1 && last >= 2 => {
+                let post = rest_binding(elements.pop().unwrap());
+                let pre = rest_binding(elements.remove(0));
+                Ok(Node::new_find_pattern(pre, elements, post, loc))
+            }
+            _ => Err(error_unexpected(
+                elements[rests[1]].loc,
+                "Multiple rests in an array pattern.",
+            )),
+        }
+    }
+
+    /// Examine whether the top-level pattern is a hash pattern without braces.
+    /// (`in key: pat` or `in **rest`)
+    fn is_hash_pattern_top(&mut self) -> Result<bool, LexerErr> {
+        let tok = self.peek()?;
+        if tok.kind == TokenKind::Punct(Punct::DMul) {
+            return Ok(true);
+        }
+        if tok.can_be_symbol().is_none() {
+            return Ok(false);
+        }
+        // skip the line terminators before *tok*, and look at the token after it.
+        let mut n = 0;
+        while self.lexer.peek_n(n)?.is_line_term() {
+            n += 1;
+        }
+        Ok(self.lexer.peek_n(n + 1)?.kind == TokenKind::Punct(Punct::Colon))
+    }
+
+    /// Parse a hash pattern after `{`, or a top-level hash pattern without braces.
+    fn parse_hash_pattern(&mut self, loc: Loc, braced: bool) -> Result<Node, LexerErr> {
+        let mut pairs = vec![];
+        let mut rest = None;
+        let mut double_splat = false;
+        loop {
+            if braced && self.consume_punct(Punct::RBrace)? {
+                break;
+            }
+            if self.consume_punct_no_term(Punct::DMul)? {
+                let tok = self.get()?;
+                match tok.kind {
+                    TokenKind::Ident(name) if name == "nil" => double_splat = true,
+                    TokenKind::Ident(name) if !is_pseudo_variable(&name) => {
+                        let outer = self.add_local_var_if_new(&name);
+                        rest = Some(Node::new_lvar(name, outer, tok.loc));
+                    }
+                    _ => return Err(error_unexpected(tok.loc, "Expect an identifier or nil.")),
+                }
+            } else {
+                let key = self.parse_pattern_key()?;
+                let value = if self.is_pattern_end()? {
+                    // `key:` binds the local variable *key*.
+                    let name = match &key.kind {
+                        NodeKind::Symbol(name)
+                            if name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_') =>
+                        {
+                            name.clone()
+                        }
+                        _ => return Err(error_unexpected(key.loc, "Invalid variable name.")),
+                    };
+                    self.add_local_var_if_new(&name);
+                    None
+                } else {
+                    Some(self.parse_pattern()?)
+                };
+                pairs.push((key, value));
+            }
+            let comma = if braced {
+                self.consume_punct(Punct::Comma)?
+            } else {
+                self.consume_punct_no_term(Punct::Comma)?
+            };
+            if !comma {
+                if braced {
+                    self.expect_punct(Punct::RBrace)?;
+                }
+                break;
+            }
+        }
+        let loc = loc.merge(self.prev_loc());
+        Ok(Node::new_hash_pattern(pairs, rest, double_splat, loc))
+    }
+
+    /// Parse a primary pattern.
+    ///
+    /// A local variable is bound for an identifier.
+    fn parse_pattern_primary(&mut self) -> Result<Node, LexerErr> {
+        let tok = self.peek()?;
+        let loc = tok.loc;
+        match tok.kind {
+            TokenKind::Ident(name) if !is_pseudo_variable(&name) => {
+                self.get()?;
+                let outer = self.add_local_var_if_new(&name);
+                Ok(Node::new_lvar(name, outer, loc))
+            }
+            TokenKind::Punct(Punct::BitXor) => {
+                self.get()?;
+                self.parse_pin_operator(loc)
+            }
+            TokenKind::Punct(Punct::LParen) => {
+                self.get()?;
+                let node = self.parse_pattern()?;
+                self.expect_punct(Punct::RParen)?;
+                Ok(node)
+            }
+            TokenKind::Punct(Punct::LBracket) => {
+                self.get()?;
+                self.parse_array_pattern(loc)
+            }
+            TokenKind::Punct(Punct::LBrace) => {
+                self.get()?;
+                self.parse_hash_pattern(loc, true)
+            }
+            _ => self.parse_pattern_value(),
+        }
+    }
+
+    /// Parse a pinned value after `^`. (`^var`, `^@ivar`, `^$gvar`, `^@@cvar` or `^(expr)`)
+    ///
+    /// The pinned local variable is a reference, not a new binding.
+    fn parse_pin_operator(&mut self, loc: Loc) -> Result<Node, LexerErr> {
+        let tok = self.get()?;
+        let inner = match tok.kind {
+            TokenKind::Ident(name) => match self.is_local_var(&name) {
+                Some(outer) => Node::new_lvar(name, outer, tok.loc),
+                None => {
+                    return Err(error_unexpected(
+                        tok.loc,
+                        format!("{}: no such local variable", name),
+                    ))
+                }
+            },
+            TokenKind::InstanceVar(name) => Node::new_instance_var(name, tok.loc),
+            TokenKind::GlobalVar(name) => Node::new_global_var(name, tok.loc),
+            TokenKind::ClassVar(name) => Node::new_class_var(name, tok.loc),
+            TokenKind::Punct(Punct::LParen) => {
+                let node = self.parse_expr()?;
+                self.expect_punct(Punct::RParen)?;
+                node
+            }
+            _ => return Err(error_unexpected(tok.loc, "Unexpected token after '^'.")),
+        };
+        Ok(Node::new_pin_operator(inner, loc))
+    }
+
+    /// Parse a key of hash patterns. (`key:` or `"key":`)
+    fn parse_pattern_key(&mut self) -> Result<Node, LexerErr> {
+        let tok = self.get()?;
+        let loc = tok.loc;
+        match tok.can_be_symbol() {
+            Some(id) if self.consume_punct_no_term(Punct::Colon)? => {
+                Ok(Node::new_symbol(id.to_string(), loc))
+            }
+            _ => Err(error_unexpected(loc, "Expect a key of the hash pattern.")),
+        }
+    }
+
+    /// Parse a value pattern. (literals, constants and ranges of them)
+    fn parse_pattern_value(&mut self) -> Result<Node, LexerErr> {
+        let loc = self.loc();
+        let start = match self.peek_no_term()?.kind {
+            TokenKind::Punct(Punct::Range2) | TokenKind::Punct(Punct::Range3) => Node::new_nil(loc),
+            _ => self.parse_pattern_primitive()?,
+        };
+        if let NodeKind::Const { .. } = start.kind {
+            if let Some(close) = self.consume_const_pattern_open(&start)? {
+                return self.parse_const_pattern(start, close);
+            }
+        }
+        let exclude_end = if self.consume_punct_no_term(Punct::Range2)? {
+            false
+        } else if self.consume_punct_no_term(Punct::Range3)? {
+            true
+        } else {
+            return Ok(start);
+        };
+        // the start or the end of ranges can be omitted. (`..5`, `1..`)
+        let end = if self.is_pattern_end()? {
+            Node::new_nil(self.prev_loc())
+        } else {
+            self.parse_pattern_primitive()?
+        };
+        let loc = start.loc().merge(end.loc());
+        Ok(Node::new_range(start, end, exclude_end, loc))
+    }
+
+    /// Consume `(` or `[` just after *constant* without spaces, and return the closing
+    /// punctuator.
+    fn consume_const_pattern_open(&mut self, constant: &Node) -> Result<Option<Punct>, LexerErr> {
+        let tok = self.peek_no_term()?;
+        let close = match tok.kind {
+            TokenKind::Punct(Punct::LParen) => Punct::RParen,
+            TokenKind::Punct(Punct::LBracket) => Punct::RBracket,
+            _ => return Ok(None),
+        };
+        if tok.loc.0 != constant.loc.1 + 1 {
+            return Ok(None);
+        }
+        self.get()?;
+        Ok(Some(close))
+    }
+
+    /// Parse the arguments of a constant pattern after `Const(` or `Const[`.
+    ///
+    /// The arguments are an array, find or hash pattern without brackets.
+    fn parse_const_pattern(&mut self, constant: Node, close: Punct) -> Result<Node, LexerErr> {
+        let loc = self.prev_loc();
+        let pattern = if self.consume_punct(close)? {
+            Node::new_array_pattern(vec![], None, loc.merge(self.prev_loc()))
+        } else if self.is_hash_pattern_top()? {
+            let mut pattern = self.parse_hash_pattern(loc, false)?;
+            self.expect_punct(close)?;
+            pattern.loc = loc.merge(self.prev_loc());
+            pattern
+        } else {
+            let mut elements = vec![];
+            loop {
+                elements.push(self.parse_pattern_element()?);
+                if !self.consume_punct(Punct::Comma)? {
+                    break;
+                }
+            }
+            self.expect_punct(close)?;
+            self.new_array_pattern(elements, loc.merge(self.prev_loc()))?
+        };
+        Ok(Node::new_const_pattern(constant, pattern))
+    }
+
+    /// Parse a literal or a constant path in patterns.
+    fn parse_pattern_primitive(&mut self) -> Result<Node, LexerErr> {
+        let tok = self.peek()?;
+        let loc = tok.loc;
+        let (toplevel, name) = match tok.kind {
+            TokenKind::Const(name) => {
+                self.get()?;
+                (false, name)
+            }
+            TokenKind::Punct(Punct::Scope) => {
+                self.get()?;
+                (true, self.expect_const()?)
+            }
+            _ => return self.parse_primary(true),
+        };
+        let mut node = Node::new_const(name, toplevel, None, vec![], loc);
+        while self.consume_punct_no_term(Punct::Scope)? {
+            let name = self.expect_const()?;
+            let loc = node.loc.merge(self.prev_loc());
+            if let NodeKind::Const {
+                toplevel,
+                parent,
+                mut prefix,
+                name: parent_name,
+            } = node.kind
+            {
+                prefix.push(parent_name);
+                node = Node::new_const(name, toplevel, parent, prefix, loc);
+            }
+        }
+        Ok(node)
+    }
+
+    /// Examine whether the next token terminates the current pattern.
+    fn is_pattern_end(&mut self) -> Result<bool, LexerErr> {
+        let tok = self.peek_no_term()?;
+        Ok(tok.is_line_term()
+            || tok.is_eof()
+            || matches!(
+                tok.kind,
+                TokenKind::Punct(
+                    Punct::Comma
+                        | Punct::Semi
+                        | Punct::RParen
+                        | Punct::RBracket
+                        | Punct::RBrace
+                        | Punct::BitOr
+                        | Punct::FatArrow
+                ) | TokenKind::Reserved(Reserved::Then | Reserved::If | Reserved::Unless)
+            ))
+    }
+}
+
+/// Check that an alternative of `pat | pat` binds no local variables except ones beginning
+/// with `_`.
+fn check_alternative_bindings(pattern: &Node) -> Result<(), LexerErr> {
+    let illegal = |name: &str, loc: Loc| {
+        error_unexpected(
+            loc,
+            format!("illegal variable in alternative pattern ({})", name),
+        )
+    };
+    match &pattern.kind {
+        // a pinned variable is a reference.
+        NodeKind::PinOperator { .. } => return Ok(()),
+        NodeKind::LocalVar(_, name) if !name.starts_with('_') => {
+            return Err(illegal(name, pattern.loc))
+        }
+        NodeKind::HashPattern { pairs, .. } => {
+            // `key:` binds the local variable *key*.
+            for (key, _) in pairs.iter().filter(|(_, value)| value.is_none()) {
+                if let NodeKind::Symbol(name) = &key.kind {
+                    if !name.starts_with('_') {
+                        return Err(illegal(name, key.loc));
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    pattern
+        .children()
+        .into_iter()
+        .try_for_each(check_alternative_bindings)
+}
+
+/// Examine whether *name* is a pseudo variable, which is not bound in patterns.
+fn is_pseudo_variable(name: &str) -> bool {
+    matches!(
+        name,
+        "true" | "false" | "nil" | "self" | "__LINE__" | "__FILE__"
+    )
+}
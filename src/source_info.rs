@@ -10,6 +10,97 @@ impl Loc {
         use std::cmp::*;
         Loc(min(self.0, loc.0), max(self.1, loc.1))
     }
+
+    /// Split `self` into two parts at byte position *offset*.
+    ///
+    /// The byte at *offset* becomes the head of the latter part.
+    /// Return None if either part would be empty.
+    pub fn split_at(&self, offset: usize) -> Option<(Loc, Loc)> {
+        if self.0 < offset && offset <= self.1 {
+            Some((Loc(self.0, offset - 1), Loc(offset, self.1)))
+        } else {
+            None
+        }
+    }
+
+    /// Move the start of `self` forward by *n* bytes.
+    ///
+    /// Return None if `self` would be empty, as `Loc` can not represent an empty range,
+    /// instead of returning an invalid `Loc` whose start is after its end.
+    pub fn advance(&self, n: usize) -> Option<Loc> {
+        let start = self.0.checked_add(n)?;
+        (start <= self.1).then_some(Loc(start, self.1))
+    }
+
+    /// Move the end of `self` backward by *n* bytes.
+    ///
+    /// Return None if `self` would be empty, as `advance()` does.
+    pub fn shrink_end(&self, n: usize) -> Option<Loc> {
+        let end = self.1.checked_sub(n)?;
+        (self.0 <= end).then_some(Loc(self.0, end))
+    }
+
+    /// Get the line number (1-based) of the start of `self` in *source*.
+    pub fn first_line(&self, source: &str) -> usize {
+        line_of(source, self.0)
+    }
+
+    /// Get the line number (1-based) of the end of `self` in *source*.
+    pub fn last_line(&self, source: &str) -> usize {
+        line_of(source, self.1)
+    }
+
+    /// Check whether `self` touches the line *line_no* (1-based) of *source*.
+    pub fn covers_line(&self, source: &str, line_no: usize) -> bool {
+        self.first_line(source) <= line_no && line_no <= self.last_line(source)
+    }
+
+    /// Get the line number (1-based) and the column (0-based, in bytes) of the start of `self`.
+    /// Same as `SourceInfo::loc_to_line_col()`.
+    pub fn to_line_col(&self, info: &SourceInfo) -> (usize, usize) {
+        info.loc_to_line_col(*self)
+    }
+}
+
+/// Get the line number (1-based) of the byte position *pos* in *source*.
+fn line_of(source: &str, pos: usize) -> usize {
+    let pos = std::cmp::min(pos, source.len());
+    source.as_bytes()[..pos]
+        .iter()
+        .filter(|b| **b == b'\n')
+        .count()
+        + 1
+}
+
+/// Get the largest char boundary of *s* which is not greater than *pos*.
+fn floor_char_boundary(s: &str, pos: usize) -> usize {
+    let mut pos = std::cmp::min(pos, s.len());
+    while !s.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    pos
+}
+
+/// Get the smallest char boundary of *s* which is not less than *pos*.
+fn ceil_char_boundary(s: &str, pos: usize) -> usize {
+    let mut pos = std::cmp::min(pos, s.len());
+    while !s.is_char_boundary(pos) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Get the byte position of the first char in `s[top..end]` at the display column *col* or
+/// after, or *end* if not found.
+fn byte_at_column(s: &str, top: usize, end: usize, col: usize) -> usize {
+    let mut width = 0;
+    for (i, ch) in s[top..end].char_indices() {
+        if width >= col {
+            return top + i;
+        }
+        width += console::measure_text_width(ch.encode_utf8(&mut [0; 4]));
+    }
+    end
 }
 
 /// This struct holds infomation of a certain line in the code.
@@ -35,6 +126,8 @@ pub struct SourceInfo {
     pub path: PathBuf,
     /// source code text.
     pub code: String,
+    /// true if the newline at the end of `code` was appended by `SourceInfo::new()`.
+    newline_appended: bool,
 }
 
 impl Default for SourceInfo {
@@ -57,6 +150,143 @@ impl SourceInfo {
         self.path.file_name().unwrap().to_string_lossy()
     }
 
+    /// Get the directory name of the file, which is the value of `__dir__`.
+    pub fn dir_name(&self) -> Cow<'_, str> {
+        match self.path.parent() {
+            Some(dir) => dir.to_string_lossy(),
+            None => Cow::Borrowed(""),
+        }
+    }
+
+    /// Get the line number (1-based) of the byte position *pos*.
+    pub fn byte_to_line(&self, pos: usize) -> usize {
+        line_of(&self.code, pos)
+    }
+
+    /// Get the number of lines in the code.
+    ///
+    /// `\n`, `\r\n` and bare `\r` are counted as line breaks, and the line after the last
+    /// line break is counted even if it is empty. The newline appended by `SourceInfo::new()`
+    /// is not counted.
+    pub fn line_count(&self) -> usize {
+        let tops = self.line_tops().count();
+        if self.newline_appended {
+            tops - 1
+        } else {
+            tops
+        }
+    }
+
+    /// Get the byte position of the top of the line *line_no* (1-based).
+    ///
+    /// Lines are counted in the same way as `line_count()`, so the line exists if *line_no*
+    /// is in `1..=line_count()`. The empty line after the last line break starts at the end
+    /// of the code. Return None if the line does not exist.
+    pub fn byte_offset_of_line(&self, line_no: usize) -> Option<usize> {
+        if line_no == 0 || line_no > self.line_count() {
+            return None;
+        }
+        self.line_tops().nth(line_no - 1)
+    }
+
+    /// Get the byte range of the line *line_no* (1-based), excluding the trailing line break.
+    ///
+    /// Return None if the line does not exist.
+    pub fn byte_range_of_line(&self, line_no: usize) -> Option<std::ops::Range<usize>> {
+        let top = self.byte_offset_of_line(line_no)?;
+        let end = match self.code.as_bytes()[top..]
+            .iter()
+            .position(|b| *b == b'\n' || *b == b'\r')
+        {
+            Some(len) => top + len,
+            None => self.code.len(),
+        };
+        Some(top..end)
+    }
+
+    /// Get the column (0-based, in bytes) of the byte position *pos*.
+    pub fn byte_to_col(&self, pos: usize) -> usize {
+        let pos = std::cmp::min(pos, self.code.len());
+        match self.code.as_bytes()[..pos]
+            .iter()
+            .rposition(|b| *b == b'\n')
+        {
+            Some(newline) => pos - newline - 1,
+            None => pos,
+        }
+    }
+
+    /// Get the line number (1-based) and the column (0-based, in bytes) of the start of *loc*.
+    pub fn loc_to_line_col(&self, loc: Loc) -> (usize, usize) {
+        (self.byte_to_line(loc.0), self.byte_to_col(loc.0))
+    }
+
+    /// Get the `Loc` of the character at *line* (1-based) and *col* (0-based, in bytes).
+    ///
+    /// *col* may point the newline at the end of the line.
+    /// Return None if the position is out of the line or not on a char boundary.
+    pub fn line_col_to_loc(&self, line: usize, col: usize) -> Option<Loc> {
+        let range = self.byte_range_of_line(line)?;
+        let pos = range.start + col;
+        if pos > range.end || !self.code.is_char_boundary(pos) {
+            return None;
+        }
+        let len = self.code[pos..].chars().next()?.len_utf8();
+        Some(Loc(pos, pos + len - 1))
+    }
+
+    /// Get the source text of *loc*, which may span multiple lines.
+    ///
+    /// # Panics
+    ///
+    /// Panics if *loc* is out of the code or not on char boundaries.
+    /// Use `try_extract_span()` to handle them.
+    pub fn extract_span(&self, loc: Loc) -> &str {
+        match self.try_extract_span(loc) {
+            Some(s) => s,
+            None => panic!(
+                "{:?} is out of the code or not on char boundaries. (code length: {})",
+                loc,
+                self.code.len()
+            ),
+        }
+    }
+
+    /// Get the source text of *loc*, which may span multiple lines.
+    ///
+    /// Return None if *loc* is out of the code or not on char boundaries.
+    pub fn try_extract_span(&self, loc: Loc) -> Option<&str> {
+        if loc.0 > loc.1 {
+            return None;
+        }
+        self.code.get(loc.0..loc.1 + 1)
+    }
+
+    /// Get the column (0-based, in bytes) of *byte_offset*. Same as `byte_column()`.
+    pub fn column_of(&self, byte_offset: usize) -> usize {
+        self.byte_to_col(byte_offset)
+    }
+
+    /// Get the column (0-based) of *byte_offset* in bytes from the line top.
+    pub fn byte_column(&self, byte_offset: usize) -> usize {
+        self.byte_to_col(byte_offset)
+    }
+
+    /// Get the column (0-based) of *byte_offset* in display width from the line top.
+    ///
+    /// CJK characters and other wide characters count as two columns.
+    pub fn display_column(&self, byte_offset: usize) -> usize {
+        let pos = std::cmp::min(byte_offset, self.code.len());
+        let top = pos - self.byte_to_col(pos);
+        match self.code.get(top..pos) {
+            Some(s) => console::measure_text_width(s),
+            // *byte_offset* is not on a char boundary.
+            None => console::measure_text_width(&String::from_utf8_lossy(
+                &self.code.as_bytes()[top..pos],
+            )),
+        }
+    }
+
     /// Show the location of *loc* in source text.
     pub fn show_loc(&self, loc: &Loc) {
         eprint!("{}", self.get_location(loc));
@@ -72,32 +302,33 @@ impl SourceInfo {
         let lines = self.get_lines(loc);
         let mut found = false;
         let term = console::Term::stdout();
-        let term_width = term.size().1 as usize;
+        let term_width = std::cmp::max(term.size().1 as usize, 1);
         for line in &lines {
             if !found {
                 res_string += &format!("{}:{}\n", self.file_name(), line.line_no);
                 found = true;
             };
 
+            // All columns are counted in display width, and all slices are on char boundaries.
             let start = line.top;
-            let mut end = line.end;
-            if self.get_next_char(end) == Some('\n') && end > 0 {
-                end -= 1
-            }
-            let mut lead = if loc.0 <= line.top {
-                0
+            let end = if self.get_next_char(line.end) == Some('\n') {
+                line.end
             } else {
-                console::measure_text_width(&code[start..loc.0])
+                line.end + 1
             };
+            let range_start = floor_char_boundary(&code, std::cmp::max(loc.0, start));
+            let range_end = ceil_char_boundary(&code, std::cmp::min(loc.1, line.end) + 1);
+            let lead = console::measure_text_width(&code[start..range_start]);
+            let length = console::measure_text_width(&code[range_start..range_end]);
+            // scroll the line by the terminal width.
             let offset = lead / term_width * term_width;
-            lead -= offset;
-            let range_start = std::cmp::max(loc.0, line.top);
-            let range_end = std::cmp::min(loc.1, line.end);
-            let term_end = start + (range_end - start) / term_width * term_width + term_width - 1;
-            let length = console::measure_text_width(&code[range_start..=range_end]);
-            res_string += &code[(start + offset)..=std::cmp::min(end, term_end)];
+            let page_end =
+                (lead + std::cmp::max(length, 1) - 1) / term_width * term_width + term_width;
+            let show_start = byte_at_column(&code, start, end, offset);
+            let show_end = byte_at_column(&code, start, end, page_end);
+            res_string += &code[show_start..show_end];
             res_string += "\n";
-            res_string += &" ".repeat(lead);
+            res_string += &" ".repeat(console::measure_text_width(&code[show_start..range_start]));
             res_string += &"^".repeat(length);
             res_string += "\n";
         }
@@ -108,6 +339,10 @@ impl SourceInfo {
                 Some(line) => (line.line_no + 1, line.end + 1, loc.1),
                 None => (1, 0, loc.1),
             };
+            let loc = Loc(
+                floor_char_boundary(&code, loc.0),
+                floor_char_boundary(&code, loc.1),
+            );
             let lead = console::measure_text_width(&code[line.1..loc.0]);
             let length = console::measure_text_width(&code[loc.0..loc.1]);
             let is_cr = loc.1 >= code.len() || self.get_next_char(loc.1) == Some('\n');
@@ -126,14 +361,27 @@ impl SourceInfo {
 }
 
 impl SourceInfo {
+    /// Iterate over the byte positions of the top of each line, including the end of the code
+    /// after the last line break. `\n`, `\r\n` and bare `\r` are line breaks.
+    fn line_tops(&self) -> impl Iterator<Item = usize> + '_ {
+        let bytes = self.code.as_bytes();
+        std::iter::once(0).chain(bytes.iter().enumerate().filter_map(move |(i, b)| match b {
+            b'\n' => Some(i + 1),
+            b'\r' if bytes.get(i + 1) != Some(&b'\n') => Some(i + 1),
+            _ => None,
+        }))
+    }
+
     pub fn new(path: impl Into<PathBuf>, code: impl Into<String>) -> Self {
         let mut code = code.into();
-        if !code.ends_with('\n') {
+        let newline_appended = !code.ends_with('\n');
+        if newline_appended {
             code.push('\n');
         }
         SourceInfo {
             path: path.into(),
             code,
+            newline_appended,
         }
     }
 
@@ -190,3 +438,208 @@ impl SourceInfo {
         lines
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn loc_split() {
+        let loc = Loc(3, 8);
+        assert_eq!(Some((Loc(3, 4), Loc(5, 8))), loc.split_at(5));
+        assert_eq!(Some((Loc(3, 7), Loc(8, 8))), loc.split_at(8));
+        assert_eq!(None, loc.split_at(3));
+        assert_eq!(None, loc.split_at(9));
+        assert_eq!(Some(Loc(5, 8)), loc.advance(2));
+        assert_eq!(Some(Loc(3, 6)), loc.shrink_end(2));
+        assert_eq!(Some(Loc(8, 8)), loc.advance(5));
+        assert_eq!(Some(Loc(3, 3)), loc.shrink_end(5));
+        assert_eq!(None, loc.advance(6));
+        assert_eq!(None, loc.shrink_end(6));
+        assert_eq!(None, loc.shrink_end(9));
+        assert_eq!(None, Loc(0, 0).shrink_end(1));
+        assert_eq!(None, loc.advance(usize::MAX));
+    }
+
+    #[test]
+    fn loc_lines() {
+        let source = "a = 1\nb = [\n  2\n]\n";
+        let loc = Loc(10, 17);
+        assert_eq!(2, loc.first_line(source));
+        assert_eq!(4, loc.last_line(source));
+        assert!(!loc.covers_line(source, 1));
+        assert!(loc.covers_line(source, 2));
+        assert!(loc.covers_line(source, 3));
+        assert!(loc.covers_line(source, 4));
+        assert!(!loc.covers_line(source, 5));
+        // the trailing newline belongs to the line.
+        assert!(!Loc(0, 5).covers_line(source, 2));
+    }
+
+    #[test]
+    fn byte_to_line_col() {
+        let info = SourceInfo::new("", "a = 1\nbb = 2\n\nc");
+        assert_eq!((1, 0), (info.byte_to_line(0), info.byte_to_col(0)));
+        assert_eq!((1, 4), (info.byte_to_line(4), info.byte_to_col(4)));
+        assert_eq!((2, 0), (info.byte_to_line(6), info.byte_to_col(6)));
+        assert_eq!((2, 5), (info.byte_to_line(11), info.byte_to_col(11)));
+        assert_eq!((3, 0), (info.byte_to_line(13), info.byte_to_col(13)));
+        assert_eq!((4, 0), (info.byte_to_line(14), info.byte_to_col(14)));
+    }
+
+    #[test]
+    fn byte_offset_of_line() {
+        let info = SourceInfo::new("", "a = 1\n\nbb = 2");
+        assert_eq!(Some(0), info.byte_offset_of_line(1));
+        assert_eq!(Some(6), info.byte_offset_of_line(2));
+        assert_eq!(Some(7), info.byte_offset_of_line(3));
+        assert_eq!(None, info.byte_offset_of_line(4));
+        assert_eq!(None, info.byte_offset_of_line(0));
+        assert_eq!(Some(0..5), info.byte_range_of_line(1));
+        assert_eq!(Some(6..6), info.byte_range_of_line(2));
+        assert_eq!(Some(7..13), info.byte_range_of_line(3));
+        assert_eq!(None, info.byte_range_of_line(4));
+        for line in 1..=3 {
+            let top = info.byte_offset_of_line(line).unwrap();
+            assert_eq!(line, info.byte_to_line(top));
+        }
+        // the first line of an empty source agrees with line_count().
+        for info in [
+            SourceInfo::default(),
+            SourceInfo {
+                path: PathBuf::new(),
+                code: String::new(),
+                newline_appended: false,
+            },
+        ] {
+            assert_eq!(1, info.line_count());
+            assert_eq!(Some(0), info.byte_offset_of_line(1));
+            assert_eq!(Some(0..0), info.byte_range_of_line(1));
+            assert_eq!(None, info.byte_offset_of_line(2));
+        }
+    }
+
+    #[test]
+    fn loc_to_line_col() {
+        let info = SourceInfo::new("", "a = 1\nb = \"日本\"\nc = [\n  2]");
+        assert_eq!((1, 0), info.loc_to_line_col(Loc(0, 0)));
+        assert_eq!(Some(Loc(0, 0)), info.line_col_to_loc(1, 0));
+        // multi-byte characters
+        assert_eq!((2, 5), info.loc_to_line_col(Loc(11, 13)));
+        assert_eq!((2, 8), info.loc_to_line_col(Loc(14, 16)));
+        assert_eq!(Some(Loc(11, 13)), info.line_col_to_loc(2, 5));
+        assert_eq!(None, info.line_col_to_loc(2, 6));
+        // a loc spanning multiple lines
+        let loc = Loc(23, 28);
+        assert_eq!((3, 4), loc.to_line_col(&info));
+        assert_eq!((4, 3), info.loc_to_line_col(Loc(loc.1, loc.1)));
+        // the last byte of the file is the newline appended by SourceInfo::new().
+        let last = info.code.len() - 1;
+        assert_eq!((4, 4), info.loc_to_line_col(Loc(last, last)));
+        assert_eq!(Some(Loc(last, last)), info.line_col_to_loc(4, 4));
+        assert_eq!(None, info.line_col_to_loc(4, 5));
+        assert_eq!(None, info.line_col_to_loc(5, 0));
+        assert_eq!(None, info.line_col_to_loc(0, 0));
+        for pos in [0, 6, 11, 20, 25, last] {
+            let (line, col) = info.loc_to_line_col(Loc(pos, pos));
+            assert_eq!(pos, info.line_col_to_loc(line, col).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn get_location_multibyte() {
+        let info = SourceInfo::new("", "α = 1\nx = α +\n日本 = \"日本\" + )");
+        assert_eq!(":2\nx = α +\n    ^\n", info.get_location(&Loc(11, 12)));
+        // a loc which ends in the middle of a character.
+        assert_eq!(":2\nx = α +\n    ^\n", info.get_location(&Loc(11, 11)));
+        assert_eq!(":1\nα = 1\n^\n", info.get_location(&Loc(0, 1)));
+        // wide characters count as two columns.
+        assert_eq!(
+            ":3\n日本 = \"日本\" + )\n^^^^\n",
+            info.get_location(&Loc(16, 21))
+        );
+        assert_eq!(
+            ":3\n日本 = \"日本\" + )\n                ^\n",
+            info.get_location(&Loc(36, 36))
+        );
+    }
+
+    #[test]
+    fn line_count_and_offsets_agree() {
+        for code in ["", "a", "a\n", "\n", "a\nb\nc", "a\r\nb\r\n", "a\rb\rc"] {
+            let info = SourceInfo::new("", code);
+            let count = info.line_count();
+            for line in 1..=count {
+                assert!(
+                    info.byte_offset_of_line(line).is_some(),
+                    "{:?} {}",
+                    code,
+                    line
+                );
+                assert!(
+                    info.byte_range_of_line(line).is_some(),
+                    "{:?} {}",
+                    code,
+                    line
+                );
+            }
+            assert_eq!(None, info.byte_offset_of_line(count + 1), "{:?}", code);
+            assert_eq!(None, info.byte_range_of_line(count + 1), "{:?}", code);
+        }
+        // the empty line after the last line break.
+        let info = SourceInfo::new("", "a\n");
+        assert_eq!(Some(2), info.byte_offset_of_line(2));
+        assert_eq!(Some(2..2), info.byte_range_of_line(2));
+        // bare `\r` and `\r\n` are line breaks.
+        let info = SourceInfo::new("", "a\rb\rc");
+        assert_eq!(Some(2..3), info.byte_range_of_line(2));
+        assert_eq!(Some(4..5), info.byte_range_of_line(3));
+        let info = SourceInfo::new("", "a\r\nb\r\n");
+        assert_eq!(Some(0..1), info.byte_range_of_line(1));
+        assert_eq!(Some(3..4), info.byte_range_of_line(2));
+        assert_eq!(Some(6..6), info.byte_range_of_line(3));
+    }
+
+    #[test]
+    fn line_count() {
+        let count = |code: &str| SourceInfo::new("", code).line_count();
+        assert_eq!(1, count(""));
+        assert_eq!(3, count("a\nb\nc"));
+        assert_eq!(2, count("a\n"));
+        assert_eq!(2, count("\n"));
+        assert_eq!(3, count("a\r\nb\r\n"));
+        assert_eq!(3, count("a\rb\rc"));
+        assert_eq!(1, SourceInfo::default().line_count());
+    }
+
+    #[test]
+    fn extract_span() {
+        let info = SourceInfo::new("", "a = 1\nb = [\n  2\n]\nc = \"日本\"");
+        assert_eq!("a", info.extract_span(Loc(0, 0)));
+        assert_eq!("[\n  2\n]", info.extract_span(Loc(10, 16)));
+        assert_eq!("日", info.extract_span(Loc(23, 25)));
+        assert_eq!("\"日本\"", info.extract_span(Loc(22, 29)));
+        // the end is in the middle of `日`.
+        assert_eq!(None, info.try_extract_span(Loc(23, 23)));
+        // the start is in the middle of `日`.
+        assert_eq!(None, info.try_extract_span(Loc(24, 25)));
+        assert_eq!(None, info.try_extract_span(Loc(30, 31)));
+        assert_eq!(None, info.try_extract_span(Loc(3, 2)));
+        assert_eq!(Some("\n"), info.try_extract_span(Loc(30, 30)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn extract_span_out_of_bounds() {
+        SourceInfo::new("", "a").extract_span(Loc(0, 5));
+    }
+
+    #[test]
+    fn display_column() {
+        let info = SourceInfo::new("", "a\n日本語 = 1");
+        assert_eq!(0, info.column_of(2));
+        assert_eq!(9, info.byte_column(11));
+        assert_eq!(6, info.display_column(11));
+        assert_eq!(2, info.display_column(5));
+    }
+}
@@ -1,9 +1,10 @@
 use super::*;
 use enum_iterator::Sequence;
-use num::BigInt;
+use num::{BigInt, BigRational};
 use std::fmt::*;
 
-pub(crate) type Token = Annot<TokenKind>;
+/// A token with its location.
+pub type Token = Annot<TokenKind>;
 
 #[cfg(test)]
 impl Display for Token {
@@ -35,8 +36,9 @@ impl Display for Token {
     }
 }
 
+/// Kind of tokens. See `Lexer::tokens()`.
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum TokenKind {
+pub enum TokenKind {
     Eof,
     Ident(String),
     NumberedParam(u8, String),
@@ -48,6 +50,7 @@ pub(crate) enum TokenKind {
     IntegerLit(i64),
     BignumLit(BigInt),
     FloatLit(f64),
+    RationalLit(BigRational),
     ImaginaryLit(NReal),
     StringLit(RubyString),
     Regex(String, String),
@@ -55,10 +58,16 @@ pub(crate) enum TokenKind {
     Reserved(Reserved),
     Punct(Punct),
     OpenString(String, Option<char>, usize), // (content, delimiter, paren_level)
-    OpenRegex(String),
+    OpenRegex(String, char, usize),          // (content, delimiter, paren_level)
     OpenCommand(String, Option<char>, usize),
     PercentNotation(char, String),
+    /// `:foo` (only in `Lexer::tokens()`)
+    Symbol(String),
     LineTerm,
+    /// whitespaces and continuation lines. (only in `LexerMode::WithTrivia`)
+    Whitespace,
+    /// `# ...` without the newline. (only in `LexerMode::WithTrivia`)
+    Comment(String),
 }
 
 impl std::default::Default for TokenKind {
@@ -67,8 +76,9 @@ impl std::default::Default for TokenKind {
     }
 }
 
+/// Reserved words.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Sequence)]
-pub(crate) enum Reserved {
+pub enum Reserved {
     BEGIN,
     END,
     Alias,
@@ -92,6 +102,7 @@ pub(crate) enum Reserved {
     Or,
     Redo,
     Rescue,
+    Retry,
     Return,
     Super,
     Then,
@@ -109,7 +120,8 @@ impl Debug for Reserved {
 }
 
 impl Reserved {
-    pub(crate) fn to_str(&self) -> &str {
+    /// Get the reserved word as it appears in the code.
+    pub fn to_str(&self) -> &str {
         match self {
             Reserved::BEGIN => "BEGIN",
             Reserved::END => "END",
@@ -133,6 +145,7 @@ impl Reserved {
             Reserved::Next => "next",
             Reserved::Or => "or",
             Reserved::Redo => "redo",
+            Reserved::Retry => "retry",
             Reserved::Rescue => "rescue",
             Reserved::Return => "return",
             Reserved::Super => "super",
@@ -235,6 +248,10 @@ impl Token {
         Annot::new(TokenKind::FloatLit(num), loc)
     }
 
+    pub(crate) fn new_rationallit(num: BigRational, loc: Loc) -> Self {
+        Annot::new(TokenKind::RationalLit(num), loc)
+    }
+
     pub(crate) fn new_imaginarylit(num: NReal, loc: Loc) -> Self {
         Annot::new(TokenKind::ImaginaryLit(num), loc)
     }
@@ -261,8 +278,13 @@ impl Token {
         Annot::new(TokenKind::OpenCommand(s.into(), delimiter, level), loc)
     }
 
-    pub(crate) fn new_open_reg(s: impl Into<String>, loc: Loc) -> Self {
-        Annot::new(TokenKind::OpenRegex(s.into()), loc)
+    pub(crate) fn new_open_reg(
+        s: impl Into<String>,
+        delimiter: char,
+        level: usize,
+        loc: Loc,
+    ) -> Self {
+        Annot::new(TokenKind::OpenRegex(s.into(), delimiter, level), loc)
     }
 
     pub(crate) fn new_percent(kind: char, content: String, loc: Loc) -> Self {
@@ -326,6 +348,7 @@ impl Token {
                     | Reserved::Elsif
                     | Reserved::End
                     | Reserved::When
+                    | Reserved::In
                     | Reserved::Rescue
                     | Reserved::Ensure
             ),
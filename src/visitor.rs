@@ -0,0 +1,275 @@
+use super::*;
+
+macro_rules! visitor {
+    ($($method:ident, $method_mut:ident => $variant:ident,)*) => {
+        ///
+        /// Read-only traversal of the AST.
+        ///
+        /// `visit_node()` dispatches each node to the method for its kind, and the default
+        /// implementation of every method visits the children of the node.
+        /// Implementors override only the methods they care about, and call `walk_node()`
+        /// to continue the traversal into the children.
+        ///
+        /// See `NodeCounter` for an example.
+        ///
+        pub trait Visitor {
+            /// Visit *node*. Override this to observe every node regardless of its kind.
+            fn visit_node(&mut self, node: &Node) {
+                dispatch_node(self, node)
+            }
+
+            $(
+                #[doc = concat!("Visit `NodeKind::", stringify!($variant), "`.")]
+                fn $method(&mut self, node: &Node) {
+                    walk_node(self, node)
+                }
+            )*
+        }
+
+        /// Call the method of *visitor* for the kind of *node*.
+        pub fn dispatch_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+            match &node.kind {
+                $(NodeKind::$variant { .. } => visitor.$method(node),)*
+            }
+        }
+
+        ///
+        /// Mutable traversal of the AST for transformation passes.
+        ///
+        /// The counterpart of `Visitor`. The default implementation of every method visits
+        /// the children of the node with `walk_node_mut()`, so an implementor can transform
+        /// the children first and then rewrite the node itself.
+        ///
+        pub trait MutVisitor {
+            /// Visit *node*. Override this to observe every node regardless of its kind.
+            fn visit_node_mut(&mut self, node: &mut Node) {
+                dispatch_node_mut(self, node)
+            }
+
+            $(
+                #[doc = concat!("Visit `NodeKind::", stringify!($variant), "`.")]
+                fn $method_mut(&mut self, node: &mut Node) {
+                    walk_node_mut(self, node)
+                }
+            )*
+        }
+
+        /// Call the method of *visitor* for the kind of *node*.
+        pub fn dispatch_node_mut<V: MutVisitor + ?Sized>(visitor: &mut V, node: &mut Node) {
+            match &node.kind {
+                $(NodeKind::$variant { .. } => visitor.$method_mut(node),)*
+            }
+        }
+    };
+}
+
+visitor! {
+    visit_self_value, visit_self_value_mut => SelfValue,
+    visit_nil, visit_nil_mut => Nil,
+    visit_integer, visit_integer_mut => Integer,
+    visit_bignum, visit_bignum_mut => Bignum,
+    visit_float, visit_float_mut => Float,
+    visit_rational, visit_rational_mut => Rational,
+    visit_imaginary, visit_imaginary_mut => Imaginary,
+    visit_bool, visit_bool_mut => Bool,
+    visit_string, visit_string_mut => String,
+    visit_bytes, visit_bytes_mut => Bytes,
+    visit_file_literal, visit_file_literal_mut => FileLiteral,
+    visit_line_literal, visit_line_literal_mut => LineLiteral,
+    visit_method_literal, visit_method_literal_mut => MethodLiteral,
+    visit_dir_literal, visit_dir_literal_mut => DirLiteral,
+    visit_interporated_string, visit_interporated_string_mut => InterporatedString,
+    visit_command, visit_command_mut => Command,
+    visit_symbol, visit_symbol_mut => Symbol,
+    visit_range, visit_range_mut => Range,
+    visit_array, visit_array_mut => Array,
+    visit_hash, visit_hash_mut => Hash,
+    visit_regexp, visit_regexp_mut => RegExp,
+    visit_local_var, visit_local_var_mut => LocalVar,
+    visit_numbered_param, visit_numbered_param_mut => NumberedParam,
+    visit_ident, visit_ident_mut => Ident,
+    visit_instance_var, visit_instance_var_mut => InstanceVar,
+    visit_global_var, visit_global_var_mut => GlobalVar,
+    visit_special_var, visit_special_var_mut => SpecialVar,
+    visit_class_var, visit_class_var_mut => ClassVar,
+    visit_const, visit_const_mut => Const,
+    visit_binop, visit_binop_mut => BinOp,
+    visit_unop, visit_unop_mut => UnOp,
+    visit_index, visit_index_mut => Index,
+    visit_splat, visit_splat_mut => Splat,
+    visit_assign_op, visit_assign_op_mut => AssignOp,
+    visit_mul_assign, visit_mul_assign_mut => MulAssign,
+    visit_comp_stmt, visit_comp_stmt_mut => CompStmt,
+    visit_if, visit_if_mut => If,
+    visit_for, visit_for_mut => For,
+    visit_while, visit_while_mut => While,
+    visit_case, visit_case_mut => Case,
+    visit_pattern_match, visit_pattern_match_mut => PatternMatch,
+    visit_array_pattern, visit_array_pattern_mut => ArrayPattern,
+    visit_capture_pattern, visit_capture_pattern_mut => CapturePattern,
+    visit_rightward_assign, visit_rightward_assign_mut => RightwardAssign,
+    visit_pin_operator, visit_pin_operator_mut => PinOperator,
+    visit_find_pattern, visit_find_pattern_mut => FindPattern,
+    visit_hash_pattern, visit_hash_pattern_mut => HashPattern,
+    visit_alternative_pattern, visit_alternative_pattern_mut => AlternativePattern,
+    visit_const_pattern, visit_const_pattern_mut => ConstPattern,
+    visit_begin, visit_begin_mut => Begin,
+    visit_lambda, visit_lambda_mut => Lambda,
+    visit_break, visit_break_mut => Break,
+    visit_next, visit_next_mut => Next,
+    visit_redo, visit_redo_mut => Redo,
+    visit_retry, visit_retry_mut => Retry,
+    visit_return, visit_return_mut => Return,
+    visit_yield, visit_yield_mut => Yield,
+    visit_method_def, visit_method_def_mut => MethodDef,
+    visit_singleton_method_def, visit_singleton_method_def_mut => SingletonMethodDef,
+    visit_class_def, visit_class_def_mut => ClassDef,
+    visit_singleton_class_def, visit_singleton_class_def_mut => SingletonClassDef,
+    visit_method_call, visit_method_call_mut => MethodCall,
+    visit_func_call, visit_func_call_mut => FuncCall,
+    visit_begin_block, visit_begin_block_mut => BeginBlock,
+    visit_end_block, visit_end_block_mut => EndBlock,
+    visit_throw, visit_throw_mut => Throw,
+    visit_catch, visit_catch_mut => Catch,
+    visit_defined, visit_defined_mut => Defined,
+    visit_super, visit_super_mut => Super,
+    visit_alias_method, visit_alias_method_mut => AliasMethod,
+    visit_discard_lhs, visit_discard_lhs_mut => DiscardLhs,
+    visit_visibility_block, visit_visibility_block_mut => VisibilityBlock,
+    visit_proc_new, visit_proc_new_mut => ProcNew,
+    visit_error, visit_error_mut => Error,
+}
+
+/// Visit the children of *node* in source order.
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    for child in node.children() {
+        visitor.visit_node(child);
+    }
+}
+
+/// Visit the children of *node* in source order.
+pub fn walk_node_mut<V: MutVisitor + ?Sized>(visitor: &mut V, node: &mut Node) {
+    for child in node.children_mut() {
+        visitor.visit_node_mut(child);
+    }
+}
+
+///
+/// A `Visitor` which counts the nodes and the method definitions.
+///
+/// ```
+/// use ruruby_parse::*;
+///
+/// let res = Parser::parse_program("def f; 1 + 2; end".to_string(), std::path::PathBuf::new())
+///     .unwrap();
+/// let mut counter = NodeCounter::default();
+/// counter.visit_node(&res.node);
+/// assert_eq!(1, counter.methods);
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeCounter {
+    /// The number of all nodes.
+    pub nodes: usize,
+    /// The number of `MethodDef` and `SingletonMethodDef` nodes.
+    pub methods: usize,
+}
+
+impl Visitor for NodeCounter {
+    fn visit_node(&mut self, node: &Node) {
+        self.nodes += 1;
+        dispatch_node(self, node)
+    }
+
+    fn visit_method_def(&mut self, node: &Node) {
+        self.methods += 1;
+        walk_node(self, node)
+    }
+
+    fn visit_singleton_method_def(&mut self, node: &Node) {
+        self.methods += 1;
+        walk_node(self, node)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::test::parse_ok;
+
+    #[test]
+    fn node_counter() {
+        let res = parse_ok("class A\n  def f(x) = x + 1\n  def self.g; end\nend\nA.new.f(2)");
+        let mut counter = NodeCounter::default();
+        counter.visit_node(&res.node);
+        let mut nodes = 0;
+        res.each_node_with_depth(|_, _| nodes += 1);
+        assert_eq!(nodes, counter.nodes);
+        assert_eq!(2, counter.methods);
+    }
+
+    /// Fold `+`, `-` and `*` of numeric literals.
+    struct ConstantFolder;
+
+    impl MutVisitor for ConstantFolder {
+        fn visit_binop_mut(&mut self, node: &mut Node) {
+            walk_node_mut(self, node);
+            if let NodeKind::BinOp(op, lhs, rhs) = &node.kind {
+                if let (Some(l), Some(r)) = (lhs.kind.as_real(), rhs.kind.as_real()) {
+                    let num = match op {
+                        BinOp::Add => l + r,
+                        BinOp::Sub => l - r,
+                        BinOp::Mul => l * r,
+                        _ => return,
+                    };
+                    node.kind = num.into();
+                }
+            }
+        }
+    }
+
+    fn fold(code: &str) -> Node {
+        let mut node = parse_ok(code).node;
+        ConstantFolder.visit_node_mut(&mut node);
+        node
+    }
+
+    #[test]
+    fn constant_folder() {
+        assert_eq!(NodeKind::Integer(3), fold("1 + 2").kind);
+        assert_eq!(NodeKind::Float(7.5), fold("(1 + 2) * 2 + 1.5").kind);
+        assert_eq!(
+            NodeKind::Rational(num::BigRational::new(1.into(), 2.into())),
+            fold("1 - 0.5r").kind
+        );
+        assert!(matches!(
+            fold("9223372036854775807 + 1").kind,
+            NodeKind::Bignum(_)
+        ));
+        match fold("a = 1; a + 2 * 3").kind {
+            NodeKind::CompStmt(nodes) => match &nodes[1].kind {
+                NodeKind::BinOp(BinOp::Add, _, rhs) => assert_eq!(NodeKind::Integer(6), rhs.kind),
+                kind => panic!("{:?}", kind),
+            },
+            kind => panic!("{:?}", kind),
+        }
+    }
+
+    #[test]
+    fn override_skips_children() {
+        #[derive(Default)]
+        struct Ints(Vec<i64>);
+        impl Visitor for Ints {
+            fn visit_integer(&mut self, node: &Node) {
+                if let NodeKind::Integer(i) = node.kind {
+                    self.0.push(i);
+                }
+            }
+            fn visit_method_def(&mut self, _: &Node) {}
+        }
+        let res = parse_ok("a = 1\ndef f; 2; end\nb = [3, 4 + a]");
+        let mut ints = Ints::default();
+        ints.visit_node(&res.node);
+        assert_eq!(vec![1, 3, 4], ints.0);
+    }
+}